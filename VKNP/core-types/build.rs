@@ -7,6 +7,9 @@ use serde::{Deserialize, Serialize};
 struct TypeInfo {
     name: String,
     rust: String,
+    zero: String,
+    one: String,
+    float: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -7,6 +7,13 @@ pub enum DataType {
 }
 
 impl DataType {
+    /// Every supported type
+    pub const ALL: [DataType; 3] = [
+        DataType::F32,
+        DataType::I32,
+        DataType::U32,
+    ];
+
     /// Size of one element, in bytes
     pub fn size_in_bytes(self) -> usize {
         match self {
@@ -15,15 +22,53 @@ impl DataType {
             DataType::U32 => std::mem::size_of::<u32>(),
         }
     }
+
+    /// Whether this is a floating-point type
+    pub fn is_float(self) -> bool {
+        match self {
+            DataType::F32 => true,
+            DataType::I32 => false,
+            DataType::U32 => false,
+        }
+    }
 }
 
 /// Marker‐trait so we can go from T to DataType
 pub trait Element: bytemuck::Pod {
     const DTYPE: DataType;
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// Value as an `f64` (exact for every supported type)
+    fn to_f64(self) -> f64;
+}
+
+impl Element for f32 {
+    const DTYPE: DataType = DataType::F32;
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
 }
 
-impl Element for f32 { const DTYPE: DataType = DataType::F32; }
+impl Element for i32 {
+    const DTYPE: DataType = DataType::I32;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
 
-impl Element for i32 { const DTYPE: DataType = DataType::I32; }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Element for u32 {
+    const DTYPE: DataType = DataType::U32;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
 
-impl Element for u32 { const DTYPE: DataType = DataType::U32; }
\ No newline at end of file
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
\ No newline at end of file
@@ -15,12 +15,14 @@ impl fmt::Display for BufferId {
 /// Maximum number of dimensions for a view descriptor
 pub const MAX_DIMS: usize = 8; // (B, C, H, W, D, T) + 2 should be enough
 
-/// Descriptor for a view into a buffer
+/// Descriptor for a view into a buffer, in elements.
+/// Strides may be negative (flipped views): `offset` is then the position of the
+/// first element in row-major order, not the lowest one the view touches.
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable, Debug, PartialEq, Eq)]
 pub struct ViewDescriptor {
     pub offset:  u32,
     pub ndim:    u32,
     pub shape:   [u32; MAX_DIMS],
-    pub strides: [u32; MAX_DIMS],
+    pub strides: [i32; MAX_DIMS],
 }
\ No newline at end of file
@@ -0,0 +1,235 @@
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wgpu::{CommandEncoder, ComputePassDescriptor, Device, PollType, SubmissionIndex};
+
+use crate::profiler::{ProfileRecord, Profiler, ProfilerMode, TimestampQueries};
+use crate::{DispatchGrid, GpuContext, Result, VknpError};
+use crate::types::{BufferHandle, AbstractBuffer, AbstractBindGroupLayout, AbstractComputePipeline, KernelArgs, ParamArg};
+
+/// Records many dispatches / copies into one `CommandEncoder`, submitted once.
+/// Each dispatch gets its own compute pass, so ops see each other's writes in order.
+pub struct CommandBatch {
+    ctx:      GpuContext,
+    /// Taken by `submit`
+    encoder:  Option<CommandEncoder>,
+    commands: usize,
+    label:    String,
+    op:       (String, String),
+    profiler: Option<Arc<Profiler>>,
+    queries:  TimestampQueries,
+    /// Buffers bound by recorded dispatches, kept out of pool recycling until submitted
+    retained: Vec<BufferHandle>,
+    /// Run right after the queue submission
+    after_submit: Vec<Box<dyn FnOnce() + Send>>,
+    /// Run if the batch is dropped without being submitted
+    if_abandoned: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+impl CommandBatch {
+    pub(crate) fn new(ctx: &GpuContext, label: &str) -> Self {
+        Self {
+            encoder:  Some(ctx.create_encoder(label)),
+            ctx:      ctx.clone(),
+            commands: 0,
+            label:    label.to_string(),
+            op:       ("dispatch".to_string(), String::new()),
+            profiler: None,
+            queries:  TimestampQueries::default(),
+            retained: Vec::new(),
+            after_submit: Vec::new(),
+            if_abandoned: Vec::new(),
+        }
+    }
+
+    /// Time every following dispatch with `profiler`.
+    /// In `CpuWallClock` mode each dispatch is submitted and waited on by itself.
+    pub fn with_profiler(mut self, profiler: Arc<Profiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Name the following dispatches (op name, entry point) for the profiler.
+    pub fn set_op_label(&mut self, op: &str, entry: &str) {
+        self.op = (op.to_string(), entry.to_string());
+    }
+
+    /// Record a 1-D dispatch of `total_elems` invocations. Workloads above
+    /// `max_compute_workgroups_per_dimension` groups are folded into a 2-D / 3-D grid,
+    /// see `WGSL_PRELUDE` for how kernels recover their linear index.
+    pub fn dispatch_1d(
+        &mut self,
+        pipeline: &AbstractComputePipeline,
+        layout: &AbstractBindGroupLayout,
+        args: &KernelArgs,
+        total_elems: u32,
+        workgroup_size: u32,
+    ) -> Result<()> {
+        let max = self.ctx.device.limits().max_compute_workgroups_per_dimension;
+        let grid = DispatchGrid::for_elems(total_elems, workgroup_size, max)?;
+        self.dispatch(pipeline, layout, args, grid)
+    }
+
+    /// Record a dispatch of an explicit workgroup grid.
+    pub fn dispatch(
+        &mut self,
+        pipeline: &AbstractComputePipeline,
+        layout: &AbstractBindGroupLayout,
+        args: &KernelArgs,
+        grid: DispatchGrid,
+    ) -> Result<()> {
+        grid.check(self.ctx.device.limits().max_compute_workgroups_per_dimension)?;
+
+        let input_refs: Vec<&AbstractBuffer> = args.inputs.iter().map(|arc| arc.as_raw()).collect();
+        let output_refs: Vec<&AbstractBuffer> = args.outputs.iter().map(|arc| arc.as_raw()).collect();
+
+        let pass_label = format!("{}:{}", self.op.0, self.op.1);
+        let bg = self.ctx.create_storage_bind_group(layout, &input_refs, args.params, &output_refs,
+                                                    args.label.unwrap_or(&pass_label));
+
+        let mode = self.profiler.as_ref().map(|p| p.mode());
+        let cpu_start = self.profiler.as_ref().map(|p| p.now_ns());
+        let timestamp_writes = match mode {
+            Some(ProfilerMode::GpuTimestamps) => Some(self.queries.next_pass(&self.ctx, &self.op.0, &self.op.1)),
+            _ => None,
+        };
+
+        {
+            let encoder = self.encoder.as_mut().expect("the encoder is only taken by `submit`");
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some(&pass_label),
+                timestamp_writes,
+            });
+            pass.set_pipeline(pipeline.raw());
+            pass.set_bind_group(0, &bg, &[]);
+            for p in args.params {
+                if let ParamArg::PushConstant(bytes) = p {
+                    pass.set_push_constants(0, bytes);
+                }
+            }
+            pass.dispatch_workgroups(grid.x, grid.y, grid.z);
+        }
+        self.commands += 1;
+        self.retained.extend(args.inputs.iter().chain(args.outputs).cloned());
+        self.retained.extend(args.params.iter().filter_map(|p| match p {
+            ParamArg::Buffer(b) => Some(b.clone()),
+            ParamArg::PushConstant(_) => None,
+        }));
+
+        if let (Some(ProfilerMode::CpuWallClock), Some(start)) = (mode, cpu_start) {
+            self.submit_and_wait()?;
+            let profiler = self.profiler.as_ref().expect("mode comes from the profiler");
+            profiler.push(ProfileRecord {
+                op: self.op.0.clone(),
+                entry: self.op.1.clone(),
+                start_ns: start,
+                end_ns: profiler.now_ns(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Submit what was recorded so far and wait for it, continuing on a fresh encoder.
+    fn submit_and_wait(&mut self) -> Result<()> {
+        let fresh = self.ctx.create_encoder(&self.label);
+        let index = self.submit_recorded(Some(fresh))?;
+        self.ctx.device.poll(PollType::WaitForSubmissionIndex(index))?;
+        Ok(())
+    }
+
+    /// Hand what was recorded so far to the queue, recording on `next` afterwards (if any):
+    /// resolve the timestamp queries, finish the encoder, submit, then run the
+    /// `after_submit` hooks. Shared by `submit` and the mid-batch submissions.
+    fn submit_recorded(&mut self, next: Option<CommandEncoder>) -> Result<SubmissionIndex> {
+        self.ctx.ensure_alive()?;
+        let mut encoder = mem::replace(&mut self.encoder, next).expect("the encoder is only taken by `submit`");
+        let pending = mem::take(&mut self.queries).resolve(&self.ctx, &mut encoder);
+        let (cmd, err) = self.ctx.scoped(|| encoder.finish());
+        if let Some(e) = err {
+            return Err(VknpError::from_wgpu(e, 0));
+        }
+        let index = self.ctx.queue.submit(Some(cmd));
+        if let Some(profiler) = &self.profiler {
+            pending.into_iter().for_each(|p| profiler.push_pending(p));
+        }
+        self.if_abandoned.clear();
+        mem::take(&mut self.after_submit).into_iter().for_each(|f| f());
+        Ok(index)
+    }
+
+    /// Run `f` once the batch has been handed to the queue (e.g. to map again
+    /// staging buffers its copies read from). Dropped if the batch never is.
+    pub fn after_submit(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.after_submit.push(Box::new(f));
+    }
+
+    /// Run `f` if the batch is dropped without being submitted (e.g. to queue again
+    /// uploads it took over). Dropped once the batch is submitted.
+    pub fn if_abandoned(&mut self, f: impl FnOnce() + Send + 'static) {
+        self.if_abandoned.push(Box::new(f));
+    }
+
+    fn encoder(&mut self) -> &mut CommandEncoder {
+        self.encoder.as_mut().expect("the encoder is only taken by `submit`")
+    }
+
+    /// Record a buffer-to-buffer copy of `size` bytes.
+    pub fn copy_buffer_to_buffer(&mut self, src: &AbstractBuffer, dst: &AbstractBuffer, size: u64) {
+        self.encoder().copy_buffer_to_buffer(src.raw(), src.offset(), dst.raw(), dst.offset(), size);
+        self.commands += 1;
+    }
+
+    /// Record zeroing `buf` (offset and size multiples of 4).
+    pub fn clear_buffer(&mut self, buf: &AbstractBuffer) {
+        self.encoder().clear_buffer(buf.raw(), buf.offset(), Some(buf.size()));
+        self.commands += 1;
+    }
+
+    /// Number of recorded commands
+    pub fn len(&self) -> usize {
+        self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands == 0
+    }
+
+    /// Finish the encoder and submit it in a single `queue.submit`.
+    /// Validation errors of any recorded command are reported here.
+    pub fn submit(mut self) -> Result<GpuFence> {
+        let index = self.submit_recorded(None)?;
+        let done = Arc::new(AtomicBool::new(false));
+        let flag = done.clone();
+        self.ctx.queue.on_submitted_work_done(move || flag.store(true, Ordering::Release));
+        Ok(GpuFence { device: self.ctx.device.clone(), index, done })
+    }
+}
+
+impl Drop for CommandBatch {
+    fn drop(&mut self) {
+        mem::take(&mut self.if_abandoned).into_iter().for_each(|f| f());
+    }
+}
+
+/// Completion handle for one submission
+pub struct GpuFence {
+    device: Arc<Device>,
+    index:  SubmissionIndex,
+    done:   Arc<AtomicBool>,
+}
+
+impl GpuFence {
+    /// Non-blocking: poll the device once and report whether the submission finished.
+    pub fn is_complete(&self) -> Result<bool> {
+        if !self.done.load(Ordering::Acquire) {
+            self.device.poll(PollType::Poll)?;
+        }
+        Ok(self.done.load(Ordering::Acquire))
+    }
+
+    /// Block until the submission has finished executing.
+    pub fn wait(&self) -> Result<()> {
+        self.device.poll(PollType::WaitForSubmissionIndex(self.index.clone()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use wgpu::{
+    Adapter, AdapterInfo, Backends, DeviceDescriptor, Features, Instance, InstanceDescriptor,
+    Limits, PowerPreference, RequestAdapterOptions,
+};
+
+use crate::{GpuContext, Result, VknpError};
+
+/// How to pick an adapter among the ones exposed by the selected backends
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AdapterFilter {
+    /// Let wgpu choose, according to power preference / fallback flag
+    #[default]
+    Any,
+    /// First adapter whose name contains this string (case-insensitive)
+    Name(String),
+    /// Adapter at this position in `enumerate_adapters()` (same backends)
+    Index(usize),
+}
+
+/// Builder for [`GpuContext`]: backend, adapter and device selection.
+///
+/// ```ignore
+/// let ctx = GpuContext::builder()
+///     .backends(wgpu::Backends::VULKAN)
+///     .force_fallback_adapter(true) // llvmpipe / lavapipe on CI
+///     .build()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct GpuContextBuilder {
+    backends:               Backends,
+    power_preference:       PowerPreference,
+    adapter_filter:         AdapterFilter,
+    force_fallback_adapter: bool,
+    required_features:      Features,
+    required_limits:        Limits,
+}
+
+impl Default for GpuContextBuilder {
+    fn default() -> Self {
+        Self {
+            backends:               Backends::all(),
+            power_preference:       PowerPreference::default(),
+            adapter_filter:         AdapterFilter::Any,
+            force_fallback_adapter: false,
+            required_features:      Features::empty(),
+            required_limits:        Limits::default(),
+        }
+    }
+}
+
+impl GpuContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict the backends the instance is allowed to use.
+    pub fn backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn power_preference(mut self, pref: PowerPreference) -> Self {
+        self.power_preference = pref;
+        self
+    }
+
+    pub fn adapter_filter(mut self, filter: AdapterFilter) -> Self {
+        self.adapter_filter = filter;
+        self
+    }
+
+    /// Shorthand for `adapter_filter(AdapterFilter::Name(..))`
+    pub fn adapter_name(self, name: impl Into<String>) -> Self {
+        self.adapter_filter(AdapterFilter::Name(name.into()))
+    }
+
+    /// Shorthand for `adapter_filter(AdapterFilter::Index(..))`
+    pub fn adapter_index(self, index: usize) -> Self {
+        self.adapter_filter(AdapterFilter::Index(index))
+    }
+
+    /// Only accept the fallback (software) adapter.
+    pub fn force_fallback_adapter(mut self, force: bool) -> Self {
+        self.force_fallback_adapter = force;
+        self
+    }
+
+    pub fn required_features(mut self, features: Features) -> Self {
+        self.required_features = features;
+        self
+    }
+
+    pub fn required_limits(mut self, limits: Limits) -> Self {
+        self.required_limits = limits;
+        self
+    }
+
+    /// Adapters visible with the current backend selection.
+    pub fn enumerate_adapters(&self) -> Vec<AdapterInfo> {
+        self.instance()
+            .enumerate_adapters(self.backends)
+            .iter()
+            .map(Adapter::get_info)
+            .collect()
+    }
+
+    /// Select an adapter, then request a device with the required features and limits.
+    pub async fn build(self) -> Result<GpuContext> {
+        let instance = self.instance();
+        let adapter = self.select_adapter(&instance).await?;
+
+        let missing = self.required_features - adapter.features();
+        if !missing.is_empty() {
+            return Err(VknpError::Adapter(format!(
+                "'{}' does not support required features: {:?}",
+                adapter.get_info().name, missing
+            )));
+        }
+
+        let (device, queue) = adapter
+            .request_device(&DeviceDescriptor {
+                label: Some("vknp-device"),
+                required_features: self.required_features,
+                required_limits: self.required_limits.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(GpuContext::from_parts(self, adapter, device, queue))
+    }
+
+    /* ------------------------------------------------------------------ */
+    /* Internals                                                          */
+    /* ------------------------------------------------------------------ */
+
+    fn instance(&self) -> Instance {
+        Instance::new(&InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    async fn select_adapter(&self, instance: &Instance) -> Result<Adapter> {
+        let is_fallback = |a: &Adapter| a.get_info().device_type == wgpu::DeviceType::Cpu;
+
+        match &self.adapter_filter {
+            AdapterFilter::Any => instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: self.power_preference,
+                    force_fallback_adapter: self.force_fallback_adapter,
+                    compatible_surface: None,
+                })
+                .await
+                .map_err(|e| VknpError::Adapter(e.to_string())),
+            AdapterFilter::Name(name) => {
+                let needle = name.to_lowercase();
+                instance
+                    .enumerate_adapters(self.backends)
+                    .into_iter()
+                    .filter(|a| !self.force_fallback_adapter || is_fallback(a))
+                    .find(|a| a.get_info().name.to_lowercase().contains(&needle))
+                    .ok_or_else(|| VknpError::Adapter(format!("no adapter matching name '{}'", name)))
+            }
+            AdapterFilter::Index(index) => {
+                let adapter = instance
+                    .enumerate_adapters(self.backends)
+                    .into_iter()
+                    .nth(*index)
+                    .ok_or_else(|| VknpError::Adapter(format!("no adapter at index {}", index)))?;
+                if self.force_fallback_adapter && !is_fallback(&adapter) {
+                    return Err(VknpError::Adapter(format!(
+                        "adapter {} ('{}') is not a fallback adapter",
+                        index, adapter.get_info().name
+                    )));
+                }
+                Ok(adapter)
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Result, VknpError};
+
+/// Name of the WGSL override constant receiving the workgroup size chosen at launch:
+/// `override WG_SIZE: u32 = 64u;` then `@workgroup_size(WG_SIZE)`.
+pub const WORKGROUP_SIZE_OVERRIDE: &str = "WG_SIZE";
+
+/// Whether `src` declares `override NAME`.
+pub fn declares_override(src: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    src.match_indices("override").any(|(pos, kw)| {
+        let rest = &src[pos + kw.len()..];
+        let before_ok = !src[..pos].ends_with(is_ident);
+        let trimmed = rest.trim_start();
+        before_ok
+            && trimmed.len() < rest.len()
+            && trimmed.starts_with(name)
+            && !trimmed[name.len()..].starts_with(is_ident)
+    })
+}
+
+/// WGSL helpers available to every kernel compiled through the `KernelManager`.
+///
+/// Oversized 1-D workloads are folded into 2-D / 3-D grids, so kernels must not use
+/// `global_invocation_id.x` as their element index. Instead:
+///
+/// ```wgsl
+/// override WG_SIZE: u32 = 64u;
+///
+/// @compute @workgroup_size(WG_SIZE)
+/// fn main(@builtin(workgroup_id) wid: vec3<u32>,
+///         @builtin(num_workgroups) nwg: vec3<u32>,
+///         @builtin(local_invocation_index) lid: u32) {
+///   let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
+///   if (i >= total_elems) { return; }   // the grid may overshoot
+///   ...
+/// }
+/// ```
+pub const WGSL_PRELUDE: &str = r#"
+fn vknp_linear_index(wid: vec3<u32>, nwg: vec3<u32>, lid: u32, wg_size: u32) -> u32 {
+  let group = wid.x + nwg.x * (wid.y + nwg.y * wid.z);
+  return group * wg_size + lid;
+}
+"#;
+
+/// Number of workgroups along each axis of a dispatch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DispatchGrid {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl DispatchGrid {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Workgroups covering a `width × height` domain.
+    pub fn for_2d(size: (u32, u32), workgroup: (u32, u32)) -> Self {
+        Self::new(size.0.div_ceil(workgroup.0), size.1.div_ceil(workgroup.1), 1)
+    }
+
+    /// Workgroups covering a `width × height × depth` domain.
+    pub fn for_3d(size: (u32, u32, u32), workgroup: (u32, u32, u32)) -> Self {
+        Self::new(
+            size.0.div_ceil(workgroup.0),
+            size.1.div_ceil(workgroup.1),
+            size.2.div_ceil(workgroup.2),
+        )
+    }
+
+    /// Enough workgroups of `workgroup_size` invocations for `total_elems` elements,
+    /// folded into Y then Z whenever X would exceed `max_per_dim`.
+    /// The grid may overshoot: kernels bound-check their linear index.
+    pub fn for_elems(total_elems: u32, workgroup_size: u32, max_per_dim: u32) -> Result<Self> {
+        let groups = total_elems.div_ceil(workgroup_size) as u64;
+        let max = max_per_dim as u64;
+        if groups <= max {
+            return Ok(Self::new(groups as u32, 1, 1));
+        }
+        if groups > max * max * max {
+            return Err(VknpError::Validation(format!(
+                "{total_elems} elements need {groups} workgroups, more than a {max}³ grid"
+            )));
+        }
+        let z = groups.div_ceil(max * max);
+        let per_z = groups.div_ceil(z);
+        let y = per_z.div_ceil(max);
+        let x = per_z.div_ceil(y);
+        Ok(Self::new(x as u32, y as u32, z as u32))
+    }
+
+    /// Total number of workgroups
+    pub fn count(&self) -> u64 {
+        self.x as u64 * self.y as u64 * self.z as u64
+    }
+
+    /// Error if any axis exceeds `max_per_dim`.
+    pub fn check(&self, max_per_dim: u32) -> Result<()> {
+        if self.x > max_per_dim || self.y > max_per_dim || self.z > max_per_dim {
+            return Err(VknpError::Validation(format!(
+                "dispatch grid {self:?} exceeds {max_per_dim} workgroups per dimension"
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_elems_folds_oversized_workloads() {
+        assert_eq!(DispatchGrid::for_elems(100, 64, 16).unwrap(), DispatchGrid::new(2, 1, 1));
+
+        // 1000 groups > 16: folded into 2-D, then 3-D
+        for (total, max) in [(64_000, 16), (64 * 1000, 40), (64 * 4000, 16), (50_000_000, 65_535)] {
+            let g = DispatchGrid::for_elems(total, 64, max).unwrap();
+            g.check(max).unwrap();
+            assert!(g.count() * 64 >= total as u64, "{g:?} too small for {total}");
+            assert!(g.count() < (total as u64).div_ceil(64) + ((g.y + 1) * g.z) as u64, "{g:?} wastes too much");
+        }
+
+        assert!(DispatchGrid::for_elems(64 * 5000, 64, 16).is_err());
+    }
+
+    #[test]
+    fn test_declares_override() {
+        assert!(declares_override("override WG_SIZE: u32 = 64u;", "WG_SIZE"));
+        assert!(declares_override("@id(0) override  WG_SIZE : u32;", "WG_SIZE"));
+        assert!(!declares_override("override WG_SIZE_X: u32;", "WG_SIZE"));
+        assert!(!declares_override("const WG_SIZE: u32 = 64u;", "WG_SIZE"));
+        assert!(!declares_override("my_override WG_SIZE", "WG_SIZE"));
+    }
+}
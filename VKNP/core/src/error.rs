@@ -0,0 +1,68 @@
+use core_types::{BufferId, DataType};
+use thiserror::Error;
+
+/// Result alias used across the VKNP crates
+pub type Result<T, E = VknpError> = std::result::Result<T, E>;
+
+/// Every failure the GPU stack can report instead of panicking
+#[derive(Debug, Error)]
+pub enum VknpError {
+    #[error("no suitable adapter: {0}")]
+    Adapter(String),
+
+    #[error("failed to request device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+
+    #[error("shader compilation failed for entry point `{entry}`: {message}")]
+    ShaderCompile { entry: String, message: String },
+
+    #[error("validation error: {0}")]
+    Validation(String),
+
+    #[error("internal error of the GPU backend: {0}")]
+    Internal(String),
+
+    #[error("out of memory: cannot allocate {requested} bytes")]
+    OutOfMemory { requested: u64 },
+
+    #[error("device lost: {0}")]
+    DeviceLost(String),
+
+    #[error("device poll failed: {0}")]
+    Poll(#[from] wgpu::PollError),
+
+    #[error("buffer mapping failed: {0}")]
+    BufferMap(#[from] wgpu::BufferAsyncError),
+
+    #[error("missing buffer: {0}")]
+    MissingBuffer(BufferId),
+
+    #[error("unknown device: {0}")]
+    UnknownDevice(usize),
+
+    #[error("tensors live on different devices: expected {expected}, found {found}")]
+    DeviceMismatch { expected: usize, found: usize },
+
+    #[error("shape error: {0}")]
+    Shape(String),
+
+    #[error("dtype error in {context}: expected one of {expected:?}, found {found:?}")]
+    Dtype { context: String, expected: Vec<DataType>, found: DataType },
+
+    #[error("operation error: {0}")]
+    Op(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl VknpError {
+    /// Convert an error caught by a wgpu error scope; `requested` is the allocation size, if any.
+    pub(crate) fn from_wgpu(err: wgpu::Error, requested: u64) -> Self {
+        match err {
+            wgpu::Error::OutOfMemory { .. } => VknpError::OutOfMemory { requested },
+            wgpu::Error::Validation { description, .. } => VknpError::Validation(description),
+            wgpu::Error::Internal { description, .. } => VknpError::Internal(description),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::VknpError;
+
+/// Notifications about the state of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// The device is gone; every later GPU call fails with `VknpError::DeviceLost`.
+    Lost { reason: String },
+    /// An error that escaped every error scope.
+    UncapturedError(String),
+}
+
+/// Shared health state of one device, fed by wgpu's callbacks
+#[derive(Default)]
+pub(crate) struct DeviceHealth {
+    lost:        AtomicBool,
+    reason:      Mutex<Option<String>>,
+    uncaptured:  Mutex<Option<VknpError>>,
+    subscribers: Mutex<Vec<Sender<DeviceEvent>>>,
+}
+
+impl DeviceHealth {
+    pub(crate) fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn lost_reason(&self) -> Option<String> {
+        self.reason.lock().clone()
+    }
+
+    pub(crate) fn mark_lost(&self, reason: String) {
+        // only the first loss is reported
+        if self.lost.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        *self.reason.lock() = Some(reason.clone());
+        self.emit(DeviceEvent::Lost { reason });
+    }
+
+    pub(crate) fn record_uncaptured(&self, err: VknpError) {
+        let msg = err.to_string();
+        *self.uncaptured.lock() = Some(err);
+        self.emit(DeviceEvent::UncapturedError(msg));
+    }
+
+    pub(crate) fn take_uncaptured(&self) -> Option<VknpError> {
+        self.uncaptured.lock().take()
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<DeviceEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// Send to every live subscriber, forgetting the ones that hung up.
+    fn emit(&self, event: DeviceEvent) {
+        self.subscribers.lock().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
@@ -1,42 +1,101 @@
+pub mod batch;
+pub mod builder;
+pub mod dispatch;
+pub mod error;
+pub mod health;
+pub mod profiler;
+pub mod readback;
+pub mod tuner;
 pub mod types;
 
-use anyhow::Result;
+use parking_lot::Mutex;
 use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 use wgpu::{
     util::DeviceExt, BindGroupLayoutDescriptor, BindGroupLayoutEntry, ShaderStages,
-    CommandEncoder, CommandEncoderDescriptor, Device, Instance, PollType, ComputePipelineDescriptor,
+    CommandEncoder, CommandEncoderDescriptor, ErrorFilter, Adapter, AdapterInfo, Device, PollType, ComputePipelineDescriptor,
     PipelineLayoutDescriptor, Queue, ShaderModule, ShaderModuleDescriptor, ShaderSource,
-    PipelineCompilationOptions, BindGroup, BindGroupEntry, BindGroupDescriptor, ComputePassDescriptor,
+    PipelineCompilationOptions, PushConstantRange, BindGroup, BindGroupEntry, BindGroupDescriptor,
 };
 
-use types::{AbstractBuffer, AbstractBindGroupLayout, AbstractComputePipeline, BufferKind, BufferHandle};
+pub use batch::{CommandBatch, GpuFence};
+pub use builder::{AdapterFilter, GpuContextBuilder};
+pub use dispatch::{DispatchGrid, WGSL_PRELUDE, WORKGROUP_SIZE_OVERRIDE};
+pub use error::{Result, VknpError};
+pub use health::DeviceEvent;
+pub use profiler::{ProfileRecord, Profiler, ProfilerMode};
+pub use readback::{DevicePoller, MapReadFuture};
+pub use tuner::WorkgroupTuner;
+use health::DeviceHealth;
+use types::{AbstractBuffer, AbstractBindGroupLayout, AbstractComputePipeline, BufferKind, BufferHandle, KernelArgs, ParamArg, ParamBinding};
 
 /// Context for GPU operations
 #[derive(Clone)]
 pub struct GpuContext {
+    pub adapter: Arc<Adapter>,
     pub device: Arc<Device>,
     pub queue:  Arc<Queue>,
+    health:     Arc<DeviceHealth>,
+    tuner:      Arc<WorkgroupTuner>,
+    builder:    Arc<GpuContextBuilder>,
 }
 
 impl GpuContext {
     /* ------------------------------------------------------------------ */
     /* Construction                                                       */
     /* ------------------------------------------------------------------ */
+
+    /// Default context: any backend, default adapter, default limits.
     pub async fn new() -> Result<Self> {
-        let instance = Instance::default();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
-            .await
-            .map_err(|e| anyhow::anyhow!("No suitable adapter found: {}", e))?;
+        Self::builder().build().await
+    }
+
+    /// Configure backend / adapter / device selection before creating the context.
+    pub fn builder() -> GpuContextBuilder {
+        GpuContextBuilder::new()
+    }
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await?;
+    /// List the adapters available on all backends.
+    pub fn enumerate_adapters() -> Vec<AdapterInfo> {
+        GpuContextBuilder::new().enumerate_adapters()
+    }
 
-        Ok(Self {
+    pub(crate) fn from_parts(builder: GpuContextBuilder, adapter: Adapter, device: Device, queue: Queue) -> Self {
+        let ctx = Self {
+            tuner: Arc::new(WorkgroupTuner::new(&adapter, &device)),
+            adapter: Arc::new(adapter),
             device: Arc::new(device),
             queue: Arc::new(queue),
-        })
+            health: Arc::new(DeviceHealth::default()),
+            builder: Arc::new(builder),
+        };
+        // errors escaping every scope are kept instead of panicking (wgpu's default)
+        ctx.set_uncaptured_error_callback(|_| {});
+        let health = ctx.health.clone();
+        ctx.device.set_device_lost_callback(move |reason, message| {
+            health.mark_lost(format!("{reason:?}: {message}"));
+        });
+        ctx
+    }
+
+    /// Build a fresh device with the same selection settings as this context.
+    /// Buffers and pipelines of the old device are not carried over; pinned workgroup
+    /// sizes are, where the new device accepts them.
+    pub async fn recreate(&self) -> Result<GpuContext> {
+        let ctx = GpuContextBuilder::clone(&self.builder).build().await?;
+        ctx.tuner.inherit(&self.tuner);
+        Ok(ctx)
+    }
+
+    /// Information about the adapter this context was created on.
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.adapter.get_info()
+    }
+
+    /// Workgroup sizes of the kernels launched on this device.
+    pub fn tuner(&self) -> &WorkgroupTuner {
+        &self.tuner
     }
 
     /* ------------------------------------------------------------------ */
@@ -44,100 +103,250 @@ impl GpuContext {
     /* ------------------------------------------------------------------ */
 
     /// Allocate an uninitialised GPU buffer.
-    pub fn create_buffer(&self, size: u64, usage: BufferKind) -> AbstractBuffer {
-        AbstractBuffer(self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
+    pub fn create_buffer(&self, size: u64, usage: BufferKind) -> Result<AbstractBuffer> {
+        self.create_buffer_desc(size, usage, None, false)
+    }
+
+    /// Allocate an uninitialised GPU buffer, named in validation errors and captures.
+    pub fn create_buffer_labeled(&self, size: u64, usage: BufferKind, label: Option<&str>) -> Result<AbstractBuffer> {
+        self.create_buffer_desc(size, usage, label, false)
+    }
+
+    /// Allocate a buffer already mapped for writing (`size` a multiple of 4), see `AbstractBuffer::write_mapped`.
+    pub fn create_buffer_mapped(&self, size: u64, usage: BufferKind, label: Option<&str>) -> Result<AbstractBuffer> {
+        self.create_buffer_desc(size, usage, label, true)
+    }
+
+    fn create_buffer_desc(&self, size: u64, usage: BufferKind, label: Option<&str>, mapped_at_creation: bool) -> Result<AbstractBuffer> {
+        self.ensure_alive()?;
+        self.check_buffer_size(size)?;
+        let (buf, err) = self.scoped(|| self.device.create_buffer(&wgpu::BufferDescriptor {
+            label,
             size,
             usage: usage.into(),
-            mapped_at_creation: false,
-        }))
+            mapped_at_creation,
+        }));
+        match err {
+            Some(e) => Err(VknpError::from_wgpu(e, size)),
+            None => Ok(AbstractBuffer::new(buf)),
+        }
     }
 
     /// Allocate and initialise a GPU buffer from host data.
-    pub fn create_buffer_with_data(&self, data: &[u8], usage: BufferKind) -> AbstractBuffer {
-        AbstractBuffer(self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    pub fn create_buffer_with_data(&self, data: &[u8], usage: BufferKind) -> Result<AbstractBuffer> {
+        self.ensure_alive()?;
+        self.check_buffer_size(data.len() as u64)?;
+        let (buf, err) = self.scoped(|| self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: data,
             usage: usage.into(),
-        }))
+        }));
+        match err {
+            Some(e) => Err(VknpError::from_wgpu(e, data.len() as u64)),
+            None => Ok(AbstractBuffer::new(buf)),
+        }
     }
 
-    /// Blocking write: map-write, copy `data`, unmap.
-    pub fn write_buffer(&self, buffer: &AbstractBuffer, data: &[u8]) {
-        // simple blocking write (MAP_WRITE)
+    /// Blocking write: map-write the start of `buffer`, copy `data`, unmap.
+    pub fn write_buffer(&self, buffer: &AbstractBuffer, data: &[u8]) -> Result<()> {
+        // simple blocking write (MAP_WRITE); mapped sizes must be 4-byte aligned
         let wgpu_buffer = buffer.raw();
-        let slice = wgpu_buffer.slice(..);
-        slice.map_async(wgpu::MapMode::Write, |_| ());
-        // wait
-        self.device.poll(PollType::Wait).expect("Device poll failed");
-        slice.get_mapped_range_mut().copy_from_slice(data);
+        let len = (data.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        let slice = buffer.slice(len);
+        self.map_blocking(&slice, wgpu::MapMode::Write)?;
+        slice.get_mapped_range_mut()[..data.len()].copy_from_slice(data);
         wgpu_buffer.unmap();
+        Ok(())
+    }
+
+    /// Write `data` at the start of a `COPY_DST` buffer through the queue (no staging).
+    /// The write lands before the next submission executes.
+    pub fn write_buffer_queued(&self, buffer: &AbstractBuffer, data: &[u8]) -> Result<()> {
+        self.ensure_alive()?;
+        let mut bytes = data.to_vec();
+        bytes.resize(data.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+        let (_, err) = self.scoped(|| self.queue.write_buffer(buffer.raw(), buffer.offset(), &bytes));
+        match err {
+            Some(e) => Err(VknpError::from_wgpu(e, 0)),
+            None => Ok(()),
+        }
     }
 
     /// Blocking read: map-read entire buffer, return Vec<u8>.
-    pub fn read_buffer(&self, buffer: &AbstractBuffer) -> Vec<u8> {
+    pub fn read_buffer(&self, buffer: &AbstractBuffer) -> Result<Vec<u8>> {
         let wgpu_buffer = buffer.raw();
-        let slice = wgpu_buffer.slice(..);
-        slice.map_async(wgpu::MapMode::Read, |_| ());
-        self.device.poll(PollType::Wait).expect("Device poll failed");
+        let slice = buffer.slice(buffer.size());
+        self.map_blocking(&slice, wgpu::MapMode::Read)?;
         let data = slice.get_mapped_range().to_vec();
         wgpu_buffer.unmap();
-        data
+        Ok(data)
+    }
+
+    fn map_blocking(&self, slice: &wgpu::BufferSlice, mode: wgpu::MapMode) -> Result<()> {
+        self.ensure_alive()?;
+        let status = Arc::new(Mutex::new(None));
+        let cb_status = status.clone();
+        let (_, err) = self.scoped(|| {
+            slice.map_async(mode, move |res| *cb_status.lock() = Some(res));
+        });
+        if let Some(e) = err {
+            return Err(VknpError::from_wgpu(e, 0));
+        }
+        // wait
+        self.device.poll(PollType::Wait)?;
+        let res = status.lock().take();
+        match res {
+            Some(res) => Ok(res?),
+            None => Err(VknpError::DeviceLost("buffer mapping never completed".into())),
+        }
+    }
+
+    /// Requests above the device's `max_buffer_size` can never be satisfied.
+    fn check_buffer_size(&self, size: u64) -> Result<()> {
+        if size > self.device.limits().max_buffer_size {
+            return Err(VknpError::OutOfMemory { requested: size });
+        }
+        Ok(())
+    }
+
+    /// Non-blocking read: resolves once the mapping completes (see `poll` / `spawn_poller`).
+    /// `buffer` must be a `Download` buffer.
+    pub fn read_buffer_async(&self, buffer: BufferHandle) -> MapReadFuture {
+        MapReadFuture::new(buffer)
     }
 
     /* ------------------------------------------------------------------ */
     /* Encoder helpers                                                    */
     /* ------------------------------------------------------------------ */
-    fn create_encoder(&self, label: &str) -> CommandEncoder {
+    pub(crate) fn create_encoder(&self, label: &str) -> CommandEncoder {
         self.device
             .create_command_encoder(&CommandEncoderDescriptor { label: Some(label) })
     }
 
-    fn submit_encoder(&self, encoder: CommandEncoder) {
-        self.queue.submit(Some(encoder.finish()));
+    fn submit_encoder(&self, encoder: CommandEncoder) -> Result<()> {
+        self.ensure_alive()?;
+        let (cmd, err) = self.scoped(|| encoder.finish());
+        if let Some(e) = err {
+            return Err(VknpError::from_wgpu(e, 0));
+        }
+        self.queue.submit(Some(cmd));
+        Ok(())
     }
 
-    pub fn copy_buffer_to_buffer(&self, src: &AbstractBuffer, dst: &AbstractBuffer, size: u64) {
+    /// Start recording several commands for a single submission.
+    pub fn begin_batch(&self, label: &str) -> CommandBatch {
+        CommandBatch::new(self, label)
+    }
+
+    pub fn copy_buffer_to_buffer(&self, src: &AbstractBuffer, dst: &AbstractBuffer, size: u64) -> Result<()> {
         let mut enc = self.create_encoder("copy-b2b");
-        enc.copy_buffer_to_buffer(src.raw(), 0, dst.raw(), 0, size);
-        self.submit_encoder(enc);
+        enc.copy_buffer_to_buffer(src.raw(), src.offset(), dst.raw(), dst.offset(), size);
+        self.submit_encoder(enc)
     }
 
     /* ------------------------------------------------------------------ */
     /* Shaders Preprocessing                                              */
     /* ------------------------------------------------------------------ */
 
-    /// Create a storage buffer layout for a compute shader.
-    pub fn create_storage_layout(&self, n_in: usize, n_out: usize) -> Arc<AbstractBindGroupLayout> {
-        let total = n_in + n_out;
-        let mut entries: Vec<BindGroupLayoutEntry> = Vec::with_capacity(total);
+    /// Rewrite every `var<param> NAME : TYPE;` declaration of `src`, in order of appearance,
+    /// according to `params`. Param `j` uses binding `n_in + j` (unused for push constants).
+    /// Markers inside `//` and `/* */` comments are left alone; the others must match
+    /// `params` one for one.
+    pub fn expand_param_declarations(src: &str, n_in: usize, params: &[ParamBinding]) -> Result<String> {
+        const MARKER: &str = "var<param>";
+        let bytes = src.as_bytes();
+        let mut out = String::with_capacity(src.len());
+        let mut copied = 0;
+        let mut idx = 0;
+        let mut i = 0;
+        // nesting level of block comments, which WGSL allows
+        let mut depth = 0usize;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if rest.starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else if depth > 0 {
+                if rest.starts_with(b"*/") {
+                    depth -= 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            } else if rest.starts_with(b"//") {
+                i += rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            } else if rest.starts_with(MARKER.as_bytes()) {
+                let kind = params.get(idx).ok_or_else(|| VknpError::Validation(format!(
+                    "shader declares more `var<param>` than the {} given params", params.len()
+                )))?;
+                let binding = n_in + idx;
+                out.push_str(&src[copied..i]);
+                match kind {
+                    ParamBinding::PushConstant => out.push_str("var<push_constant>"),
+                    ParamBinding::Uniform => out.push_str(&format!("@group(0) @binding({binding}) var<uniform>")),
+                    ParamBinding::Storage => out.push_str(&format!("@group(0) @binding({binding}) var<storage, read>")),
+                }
+                i += MARKER.len();
+                copied = i;
+                idx += 1;
+            } else {
+                i += 1;
+            }
+        }
+        out.push_str(&src[copied..]);
+
+        if idx != params.len() {
+            return Err(VknpError::Validation(format!(
+                "shader declares {} `var<param>` but {} params were given", idx, params.len()
+            )));
+        }
+        Ok(out)
+    }
+
+    /// Create the bind group layout for a compute shader.
+    /// Binding order: `n_in` read-only storage inputs, then one slot per param (left empty for
+    /// push constants), then `n_out` read-write storage outputs.
+    pub fn create_storage_layout(
+        &self,
+        n_in: usize,
+        params: &[ParamBinding],
+        n_out: usize,
+    ) -> Arc<AbstractBindGroupLayout> {
+        let buffer_entry = |binding: usize, ty: wgpu::BufferBindingType| BindGroupLayoutEntry {
+            binding: binding as u32,
+            visibility: ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let mut entries: Vec<BindGroupLayoutEntry> = Vec::with_capacity(n_in + params.len() + n_out);
 
         // Input buffers
         for i in 0..n_in {
-            entries.push(BindGroupLayoutEntry {
-                binding: i as u32,
-                visibility: ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            });
+            entries.push(buffer_entry(i, wgpu::BufferBindingType::Storage { read_only: true }));
+        }
+
+        // Parameter buffers
+        for (j, p) in params.iter().enumerate() {
+            match p {
+                ParamBinding::PushConstant => {}
+                ParamBinding::Uniform => {
+                    entries.push(buffer_entry(n_in + j, wgpu::BufferBindingType::Uniform));
+                }
+                ParamBinding::Storage => {
+                    entries.push(buffer_entry(n_in + j, wgpu::BufferBindingType::Storage { read_only: true }));
+                }
+            }
         }
 
         // Output buffers
+        let off = n_in + params.len();
         for i in 0..n_out {
-            entries.push(BindGroupLayoutEntry {
-                binding: (n_in + i) as u32,
-                visibility: ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            });
+            entries.push(buffer_entry(off + i, wgpu::BufferBindingType::Storage { read_only: false }));
         }
 
         let bgl = self.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -148,111 +357,249 @@ impl GpuContext {
     }
 
     /// Create a compute pipeline from WGSL source code.
+    /// `push_constant_size` is the byte size of the push-constant block (0 if none),
+    /// `constants` the values of the shader's `override` declarations, `label` names the
+    /// module, layout and pipeline.
+    /// WGSL parse / validation errors are returned as `VknpError::ShaderCompile`.
     pub fn create_compute_pipeline(
         &self,
         src: &str,
         entry: &str,
         layout: &AbstractBindGroupLayout,
-    ) -> Arc<AbstractComputePipeline> {
+        push_constant_size: u32,
+        constants: &[(&str, f64)],
+        label: &str,
+    ) -> Result<Arc<AbstractComputePipeline>> {
+        self.ensure_alive()?;
+        let (pipeline, err) = self.scoped(|| {
+            self.build_compute_pipeline(src, entry, layout, push_constant_size, constants, label)
+        });
+        match err {
+            Some(wgpu::Error::OutOfMemory { .. }) => Err(VknpError::OutOfMemory { requested: 0 }),
+            Some(e) => Err(VknpError::ShaderCompile { entry: entry.to_string(), message: e.to_string() }),
+            None => Ok(Arc::new(AbstractComputePipeline(pipeline))),
+        }
+    }
+
+    fn build_compute_pipeline(
+        &self,
+        src: &str,
+        entry: &str,
+        layout: &AbstractBindGroupLayout,
+        push_constant_size: u32,
+        constants: &[(&str, f64)],
+        label: &str,
+    ) -> wgpu::ComputePipeline {
         // Create shader module
         let module: ShaderModule = self.device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("wgsl-module"),
+            label: Some(label),
             source: ShaderSource::Wgsl(src.into()),
         });
         // Create pipeline layout
+        let push_constant_ranges: Vec<PushConstantRange> = if push_constant_size > 0 {
+            vec![PushConstantRange { stages: ShaderStages::COMPUTE, range: 0..push_constant_size }]
+        } else {
+            Vec::new()
+        };
         let pipeline_layout = self.device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("compute-pl-layout"),
-            bind_group_layouts: &[&layout.0],
-            push_constant_ranges: &[],
+            label: Some(label),
+            bind_group_layouts: &[layout.raw()],
+            push_constant_ranges: &push_constant_ranges,
         });
         // Create compute pipeline
-        let pipeline = self.device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("compute-pipeline"),
+        self.device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(label),
             layout: Some(&pipeline_layout),
             module: &module,
             entry_point: Some(entry),
-            compilation_options: PipelineCompilationOptions::default(),
+            compilation_options: PipelineCompilationOptions { constants, ..Default::default() },
             cache: None,
-        });
-        Arc::new(AbstractComputePipeline(pipeline))
+        })
+    }
+
+    /// Whether push constants of `size` bytes can be used on this device.
+    pub fn supports_push_constants(&self, size: usize) -> bool {
+        self.device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && size as u32 <= self.device.limits().max_push_constant_size
     }
 
     /* ------------------------------------------------------------------ */
     /* Dispatch                                                           */
     /* ------------------------------------------------------------------ */
 
-    fn create_storage_bind_group(
+    pub(crate) fn create_storage_bind_group(
         &self,
         layout: &AbstractBindGroupLayout,
         inputs: &[&AbstractBuffer],
+        params: &[ParamArg],
         outputs: &[&AbstractBuffer],
+        label: &str,
     ) -> BindGroup {
-        let mut entries: Vec<BindGroupEntry> = Vec::with_capacity(inputs.len() + outputs.len());
+        let mut entries: Vec<BindGroupEntry> = Vec::with_capacity(inputs.len() + params.len() + outputs.len());
         for (i, b) in inputs.iter().enumerate() {
             entries.push(BindGroupEntry {
                 binding: i as u32,
-                resource: b.0.as_entire_binding(),
+                resource: b.binding(),
             });
         }
         let off = inputs.len();
+        for (i, p) in params.iter().enumerate() {
+            if let ParamArg::Buffer(b) = p {
+                entries.push(BindGroupEntry {
+                    binding: (off + i) as u32,
+                    resource: b.as_raw().binding(),
+                });
+            }
+        }
+        let off = inputs.len() + params.len();
         for (i, b) in outputs.iter().enumerate() {
             entries.push(BindGroupEntry {
                 binding: (off + i) as u32,
-                resource: b.0.as_entire_binding(),
+                resource: b.binding(),
             });
         }
         self.device.create_bind_group(&BindGroupDescriptor {
-            label: Some("storage-bg"),
-            layout: &layout.0,
+            label: Some(label),
+            layout: layout.raw(),
             entries: &entries,
         })
     }
 
+    /// Dispatch a single 1-D kernel in its own submission (folded if oversized).
     pub fn dispatch_compute_1d(
         &self,
         pipeline: &AbstractComputePipeline,
         layout: &AbstractBindGroupLayout,
-        inputs: &[BufferHandle],
-        outputs: &[BufferHandle],
+        args: &KernelArgs,
         total_elems: u32,
         workgroup_size: u32,
-    ) {
-        let input_refs: Vec<&AbstractBuffer> = inputs.iter().map(|arc| arc.as_raw()).collect();
-        let output_refs: Vec<&AbstractBuffer> = outputs.iter().map(|arc| arc.as_raw()).collect();
-
-        let bg = self.create_storage_bind_group(layout, &input_refs, &output_refs);
-        let (x, _, _) = self.dispatch_size_1d(total_elems, workgroup_size);
-
-        let mut enc = self.create_encoder("dispatch-1d");
-        {
-            let mut pass = enc.begin_compute_pass(&ComputePassDescriptor::default());
-            pass.set_pipeline(&pipeline.0);
-            pass.set_bind_group(0, &bg, &[]);
-            pass.dispatch_workgroups(x, 1, 1);
-        }
-        self.submit_encoder(enc);
+    ) -> Result<()> {
+        let mut batch = self.begin_batch("dispatch-1d");
+        batch.dispatch_1d(pipeline, layout, args, total_elems, workgroup_size)?;
+        batch.submit()?;
+        Ok(())
+    }
+
+    /// Dispatch a kernel over a `width × height` domain in its own submission.
+    pub fn dispatch_compute_2d(
+        &self,
+        pipeline: &AbstractComputePipeline,
+        layout: &AbstractBindGroupLayout,
+        args: &KernelArgs,
+        size: (u32, u32),
+        workgroup_size: (u32, u32),
+    ) -> Result<()> {
+        self.dispatch(pipeline, layout, args, DispatchGrid::for_2d(size, workgroup_size))
+    }
+
+    /// Dispatch a kernel over a `width × height × depth` domain in its own submission.
+    pub fn dispatch_compute_3d(
+        &self,
+        pipeline: &AbstractComputePipeline,
+        layout: &AbstractBindGroupLayout,
+        args: &KernelArgs,
+        size: (u32, u32, u32),
+        workgroup_size: (u32, u32, u32),
+    ) -> Result<()> {
+        self.dispatch(pipeline, layout, args, DispatchGrid::for_3d(size, workgroup_size))
+    }
+
+    /// Dispatch an explicit workgroup grid in its own submission.
+    pub fn dispatch(
+        &self,
+        pipeline: &AbstractComputePipeline,
+        layout: &AbstractBindGroupLayout,
+        args: &KernelArgs,
+        grid: DispatchGrid,
+    ) -> Result<()> {
+        let mut batch = self.begin_batch("dispatch");
+        batch.dispatch(pipeline, layout, args, grid)?;
+        batch.submit()?;
+        Ok(())
     }
 
     /* ------------------------------------------------------------------ */
     /* Misc utils                                                         */
     /* ------------------------------------------------------------------ */
 
+    /// Non-blocking poll: fires the callbacks of finished work (pending mappings, fences).
+    /// Returns `true` if the queue is empty.
+    pub fn poll(&self) -> Result<bool> {
+        Ok(self.device.poll(PollType::Poll)?.is_queue_empty())
+    }
+
+    /// Poll the device every `interval` on a background thread until the poller is dropped.
+    pub fn spawn_poller(&self, interval: Duration) -> Result<DevicePoller> {
+        DevicePoller::spawn(self.device.clone(), interval)
+    }
+
     /// Block until GPU idle / or PollType::Poll for non-blocking.
-    fn device_poll(&self, mode: PollType) {
-        self.device.poll(mode).expect("Device poll failed");
+    pub fn device_poll(&self, mode: PollType) -> Result<()> {
+        self.device.poll(mode)?;
+        Ok(())
     }
 
-    /// Register an uncaptured-error callback (helpful for debugging shaders).
-    fn set_uncaptured_error_callback<F>(&self, cb: F)
+    /// Register a callback told about each error escaping every error scope (helpful for
+    /// debugging shaders). It replaces the previous callback; the error is still kept for
+    /// `take_uncaptured_error` and sent to the `subscribe` receivers.
+    pub fn set_uncaptured_error_callback<F>(&self, cb: F)
     where
-        F: wgpu::UncapturedErrorHandler + 'static,
+        F: Fn(&VknpError) + Send + Sync + 'static,
     {
-        self.device.on_uncaptured_error(Box::new(cb));
+        let health = self.health.clone();
+        self.device.on_uncaptured_error(Box::new(move |e: wgpu::Error| {
+            let err = VknpError::from_wgpu(e, 0);
+            cb(&err);
+            health.record_uncaptured(err);
+        }));
+    }
+
+    /// Take the last error that escaped every error scope, if any.
+    pub fn take_uncaptured_error(&self) -> Option<VknpError> {
+        self.health.take_uncaptured()
+    }
+
+    /* ------------------------------------------------------------------ */
+    /* Device health                                                      */
+    /* ------------------------------------------------------------------ */
+
+    /// Whether the device has been lost (driver reset, removal, `destroy`).
+    /// The loss is only noticed once the device is polled or submitted to.
+    pub fn is_lost(&self) -> bool {
+        self.health.is_lost()
+    }
+
+    /// Reason reported by wgpu when the device was lost.
+    pub fn lost_reason(&self) -> Option<String> {
+        self.health.lost_reason()
+    }
+
+    /// Receive every later `DeviceEvent` of this device.
+    pub fn subscribe(&self) -> Receiver<DeviceEvent> {
+        self.health.subscribe()
+    }
+
+    /// Fail fast with `VknpError::DeviceLost` once the device is gone.
+    pub fn ensure_alive(&self) -> Result<()> {
+        if self.health.is_lost() {
+            return Err(VknpError::DeviceLost(self.health.lost_reason().unwrap_or_default()));
+        }
+        Ok(())
+    }
+
+    /// Run `f` inside validation + out-of-memory error scopes, returning the first error caught.
+    pub(crate) fn scoped<R>(&self, f: impl FnOnce() -> R) -> (R, Option<wgpu::Error>) {
+        self.device.push_error_scope(ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let r = f();
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let oom = pollster::block_on(self.device.pop_error_scope());
+        (r, validation.or(oom))
     }
 
     /// Helper: compute `(x,1,1)` for 1-D dispatch with `workgroup_size`.
     pub fn dispatch_size_1d(&self, total: u32, workgroup_size: u32) -> (u32, u32, u32) {
-        ((total + workgroup_size - 1) / workgroup_size, 1, 1)
+        (total.div_ceil(workgroup_size), 1, 1)
     }
 }
 
@@ -276,4 +623,109 @@ mod tests {
         println!("Device created successfully: {:?}", ctx.device);
         println!("Device limits: {:?}", limits);
     }
+
+    #[test]
+    fn test_read_buffer_async_with_poller() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let data: Vec<u8> = (0..64).collect();
+        let src = ctx.create_buffer_with_data(&data, BufferKind::Main).unwrap();
+        let buf = BufferHandle::new(Arc::new(ctx.create_buffer(data.len() as u64, BufferKind::Download).unwrap()));
+        ctx.copy_buffer_to_buffer(&src, buf.as_raw(), data.len() as u64).unwrap();
+
+        let _poller = ctx.spawn_poller(Duration::from_millis(1)).unwrap();
+        let back = block_on(ctx.read_buffer_async(buf)).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn test_errors_are_returned_not_panicked() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+
+        // Invalid WGSL
+        let layout = ctx.create_storage_layout(0, &[], 0);
+        let err = ctx.create_compute_pipeline("fn main( {", "main", &layout, 0, &[], "broken").unwrap_err();
+        assert!(matches!(err, VknpError::ShaderCompile { .. }), "got {err}");
+
+        // Unsatisfiable allocation
+        let huge = ctx.device.limits().max_buffer_size + 1;
+        let err = ctx.create_buffer(huge, BufferKind::Main).unwrap_err();
+        assert!(matches!(err, VknpError::OutOfMemory { requested } if requested == huge), "got {err}");
+
+        // Invalid copy (size not a multiple of 4) is a validation error
+        let a = ctx.create_buffer(16, BufferKind::Main).unwrap();
+        let b = ctx.create_buffer(16, BufferKind::Main).unwrap();
+        let err = ctx.copy_buffer_to_buffer(&a, &b, 3).unwrap_err();
+        assert!(matches!(err, VknpError::Validation(_)), "got {err}");
+        assert!(ctx.take_uncaptured_error().is_none());
+
+        // backend failures are not reported as the caller's mistakes
+        let internal = wgpu::Error::Internal { source: Box::new(std::fmt::Error), description: "driver".into() };
+        assert!(matches!(VknpError::from_wgpu(internal, 0), VknpError::Internal(_)));
+
+        // a sub-range past the end of its buffer is a validation error
+        assert!(matches!(a.sub_range(8, 16), Err(VknpError::Validation(_))));
+        assert_eq!(a.sub_range(8, 8).unwrap().offset(), 8);
+    }
+
+    #[test]
+    fn test_uncaptured_error_callback_keeps_the_error() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        ctx.set_uncaptured_error_callback(move |e| sink.lock().push(e.to_string()));
+
+        // outside of any error scope: mapped for reading and bound as storage is invalid
+        let _ = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 16,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        assert_eq!(seen.lock().len(), 1);
+        assert!(matches!(ctx.take_uncaptured_error(), Some(VknpError::Validation(_))));
+    }
+
+    #[test]
+    fn test_expand_param_declarations() {
+        let src = "@group(0) @binding(0) var<storage, read> A: array<f32>;\n\
+                   var<param> P: Meta;\n\
+                   var<param> Q: Meta;\n";
+        let out = GpuContext::expand_param_declarations(
+            src, 1, &[ParamBinding::PushConstant, ParamBinding::Uniform],
+        ).unwrap();
+        assert!(out.contains("var<push_constant> P: Meta;"));
+        assert!(out.contains("@group(0) @binding(2) var<uniform> Q: Meta;"));
+
+        // Marker count must match the number of params, none included
+        assert!(GpuContext::expand_param_declarations(src, 1, &[ParamBinding::Storage]).is_err());
+        assert!(GpuContext::expand_param_declarations("fn f() {}", 0, &[ParamBinding::Uniform]).is_err());
+        // Sources without markers are untouched
+        assert_eq!(GpuContext::expand_param_declarations("fn f() {}", 0, &[]).unwrap(), "fn f() {}");
+
+        // Markers in comments neither take a binding nor are rewritten
+        let commented = "// var<param> old: u32;\n\
+                         /* var<param> /* nested */ var<param> */\n\
+                         var<param> P: Meta; // é var<param>\n";
+        let out = GpuContext::expand_param_declarations(commented, 0, &[ParamBinding::Uniform]).unwrap();
+        assert_eq!(out.matches("var<param>").count(), 4);
+        assert!(out.contains("@group(0) @binding(0) var<uniform> P: Meta;"));
+    }
+
+    #[test]
+    fn test_builder_adapter_selection() {
+        // Every enumerated adapter should be selectable by index and by name
+        let adapters = GpuContext::enumerate_adapters();
+        assert!(!adapters.is_empty(), "Expected at least one adapter");
+
+        let by_index = block_on(GpuContext::builder().adapter_index(0).build())
+            .expect("Failed to create GPU context from adapter index");
+        assert_eq!(by_index.adapter_info().name, adapters[0].name);
+
+        let by_name = block_on(GpuContext::builder().adapter_name(adapters[0].name.to_uppercase()).build())
+            .expect("Failed to create GPU context from adapter name");
+        assert_eq!(by_name.adapter_info().name, adapters[0].name);
+
+        // Out-of-range index must be an error, not a panic
+        assert!(block_on(GpuContext::builder().adapter_index(adapters.len()).build()).is_err());
+    }
 }
@@ -0,0 +1,261 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePassTimestampWrites, PollType, QuerySet, QuerySetDescriptor, QueryType};
+
+use crate::{GpuContext, Result, VknpError};
+
+/// Timestamps per query set (two per dispatch)
+const QUERIES_PER_CHUNK: u32 = 256;
+
+/// Where the timings of a `Profiler` come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfilerMode {
+    /// `TIMESTAMP_QUERY` written at the start / end of each compute pass
+    GpuTimestamps,
+    /// Host clock around each dispatch; every op is submitted and waited on alone
+    CpuWallClock,
+}
+
+/// Timing of one dispatched op, in nanoseconds since the profiler's origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileRecord {
+    pub op:       String,
+    pub entry:    String,
+    pub start_ns: f64,
+    pub end_ns:   f64,
+}
+
+impl ProfileRecord {
+    pub fn duration_ns(&self) -> f64 {
+        self.end_ns - self.start_ns
+    }
+}
+
+/// Opt-in per-op profiler, attached to a `CommandBatch` with `with_profiler`.
+///
+/// GPU timestamps are resolved lazily: `records`, `summary` and the trace exports
+/// wait for the submitted batches first.
+pub struct Profiler {
+    ctx:        GpuContext,
+    mode:       ProfilerMode,
+    origin:     Instant,
+    gpu_origin: Mutex<Option<u64>>,
+    records:    Mutex<Vec<ProfileRecord>>,
+    pending:    Mutex<Vec<PendingQueries>>,
+}
+
+impl Profiler {
+    /// GPU timestamps if the device was created with `TIMESTAMP_QUERY`, host clock otherwise.
+    pub fn new(ctx: &GpuContext) -> Arc<Self> {
+        let mode = if ctx.device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            ProfilerMode::GpuTimestamps
+        } else {
+            ProfilerMode::CpuWallClock
+        };
+        Self::with_mode(ctx, mode)
+    }
+
+    /// Host-clock profiler, whatever the device supports.
+    pub fn cpu(ctx: &GpuContext) -> Arc<Self> {
+        Self::with_mode(ctx, ProfilerMode::CpuWallClock)
+    }
+
+    fn with_mode(ctx: &GpuContext, mode: ProfilerMode) -> Arc<Self> {
+        Arc::new(Self {
+            ctx: ctx.clone(),
+            mode,
+            origin: Instant::now(),
+            gpu_origin: Mutex::new(None),
+            records: Mutex::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn mode(&self) -> ProfilerMode {
+        self.mode
+    }
+
+    /// Every record so far, in submission order.
+    pub fn records(&self) -> Result<Vec<ProfileRecord>> {
+        self.resolve_pending()?;
+        Ok(self.records.lock().clone())
+    }
+
+    /// Forget every record.
+    pub fn clear(&self) -> Result<()> {
+        self.resolve_pending()?;
+        self.records.lock().clear();
+        Ok(())
+    }
+
+    /// Per-op table (count, total, mean, min, max), slowest first.
+    pub fn summary(&self) -> Result<String> {
+        let mut groups: HashMap<(String, String), Vec<f64>> = HashMap::new();
+        for r in self.records()? {
+            groups.entry((r.op.clone(), r.entry.clone())).or_default().push(r.duration_ns());
+        }
+        let mut rows: Vec<_> = groups.into_iter().collect();
+        rows.sort_by(|a, b| b.1.iter().sum::<f64>().total_cmp(&a.1.iter().sum::<f64>()));
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{:<16} {:<24} {:>6} {:>12} {:>12} {:>12} {:>12}",
+                         "op", "entry", "count", "total (ms)", "mean (us)", "min (us)", "max (us)");
+        for ((op, entry), durations) in rows {
+            let total: f64 = durations.iter().sum();
+            let min = durations.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = durations.iter().copied().fold(0.0, f64::max);
+            let _ = writeln!(out, "{:<16} {:<24} {:>6} {:>12.3} {:>12.3} {:>12.3} {:>12.3}",
+                             op, entry, durations.len(), total / 1e6,
+                             total / durations.len() as f64 / 1e3, min / 1e3, max / 1e3);
+        }
+        Ok(out)
+    }
+
+    /// Chrome trace-event JSON (loadable in Perfetto / `chrome://tracing`).
+    pub fn chrome_trace_json(&self) -> Result<String> {
+        let cat = match self.mode {
+            ProfilerMode::GpuTimestamps => "gpu",
+            ProfilerMode::CpuWallClock => "cpu",
+        };
+        let mut out = String::from("{\"displayTimeUnit\":\"ns\",\"traceEvents\":[");
+        for (i, r) in self.records()?.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":0,\"args\":{{\"entry\":\"{}\"}}}}",
+                json_escape(&r.op), cat, r.start_ns / 1e3, r.duration_ns() / 1e3, json_escape(&r.entry),
+            );
+        }
+        out.push_str("]}");
+        Ok(out)
+    }
+
+    /// Write `chrome_trace_json` to `path`.
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.chrome_trace_json()?)?;
+        Ok(())
+    }
+
+    /* ------------------------------------------------------------------ */
+    /* Recording (used by CommandBatch)                                   */
+    /* ------------------------------------------------------------------ */
+
+    pub(crate) fn now_ns(&self) -> f64 {
+        self.origin.elapsed().as_nanos() as f64
+    }
+
+    pub(crate) fn push(&self, record: ProfileRecord) {
+        self.records.lock().push(record);
+    }
+
+    pub(crate) fn push_pending(&self, pending: PendingQueries) {
+        self.pending.lock().push(pending);
+    }
+
+    /// Map every resolved query buffer and turn it into records.
+    fn resolve_pending(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let period = self.ctx.queue.get_timestamp_period() as f64;
+        for p in pending {
+            let slice = p.readback.slice(..);
+            let status = Arc::new(Mutex::new(None));
+            let cb_status = status.clone();
+            slice.map_async(wgpu::MapMode::Read, move |res| *cb_status.lock() = Some(res));
+            self.ctx.device.poll(PollType::Wait)?;
+            match status.lock().take() {
+                Some(res) => res?,
+                None => return Err(VknpError::DeviceLost("timestamp readback never completed".into())),
+            }
+            let ticks: Vec<u64> = bytemuck::pod_collect_to_vec(&slice.get_mapped_range());
+            p.readback.unmap();
+
+            let mut origin = self.gpu_origin.lock();
+            let base = *origin.get_or_insert_with(|| ticks.first().copied().unwrap_or(0));
+            let to_ns = |t: u64| t.saturating_sub(base) as f64 * period;
+            let mut records = self.records.lock();
+            for (i, (op, entry)) in p.labels.into_iter().enumerate() {
+                records.push(ProfileRecord { op, entry, start_ns: to_ns(ticks[2 * i]), end_ns: to_ns(ticks[2 * i + 1]) });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Timestamp queries of one batch, recorded chunk by chunk
+#[derive(Default)]
+pub(crate) struct TimestampQueries {
+    chunks: Vec<(QuerySet, Vec<(String, String)>)>,
+}
+
+impl TimestampQueries {
+    /// Reserve the begin / end slots of the next pass.
+    pub(crate) fn next_pass(&mut self, ctx: &GpuContext, op: &str, entry: &str) -> ComputePassTimestampWrites<'_> {
+        let full = self.chunks.last().is_none_or(|(_, labels)| labels.len() as u32 * 2 >= QUERIES_PER_CHUNK);
+        if full {
+            let set = ctx.device.create_query_set(&QuerySetDescriptor {
+                label: Some("vknp-profiler-queries"),
+                ty:    QueryType::Timestamp,
+                count: QUERIES_PER_CHUNK,
+            });
+            self.chunks.push((set, Vec::new()));
+        }
+        let (set, labels) = self.chunks.last_mut().expect("a chunk was just pushed");
+        let idx = labels.len() as u32 * 2;
+        labels.push((op.to_string(), entry.to_string()));
+        ComputePassTimestampWrites {
+            query_set: set,
+            beginning_of_pass_write_index: Some(idx),
+            end_of_pass_write_index: Some(idx + 1),
+        }
+    }
+
+    /// Record the resolve + copy of every chunk into `encoder`; the readbacks are valid once submitted.
+    pub(crate) fn resolve(self, ctx: &GpuContext, encoder: &mut CommandEncoder) -> Vec<PendingQueries> {
+        self.chunks.into_iter().map(|(set, labels)| {
+            let size = labels.len() as u64 * 2 * std::mem::size_of::<u64>() as u64;
+            let resolve = ctx.device.create_buffer(&BufferDescriptor {
+                label: Some("vknp-profiler-resolve"),
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let readback = ctx.device.create_buffer(&BufferDescriptor {
+                label: Some("vknp-profiler-readback"),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.resolve_query_set(&set, 0..labels.len() as u32 * 2, &resolve, 0);
+            encoder.copy_buffer_to_buffer(&resolve, 0, &readback, 0, size);
+            PendingQueries { readback, labels }
+        }).collect()
+    }
+}
+
+/// Resolved timestamps waiting to be read back
+pub(crate) struct PendingQueries {
+    readback: Buffer,
+    labels:   Vec<(String, String)>,
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,98 @@
+use parking_lot::Mutex;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use wgpu::{BufferAsyncError, Device, PollType};
+
+use crate::Result;
+use crate::types::BufferHandle;
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), BufferAsyncError>>,
+    waker:  Option<Waker>,
+}
+
+/// Resolves to the content of a `MAP_READ` buffer once its mapping completes.
+///
+/// Mapping only progresses when the device is polled: either through
+/// `GpuContext::poll` or a `DevicePoller` running in the background.
+pub struct MapReadFuture {
+    buffer: BufferHandle,
+    state:  Arc<Mutex<MapState>>,
+}
+
+impl MapReadFuture {
+    pub(crate) fn new(buffer: BufferHandle) -> Self {
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let cb_state = state.clone();
+        let buf = buffer.as_raw();
+        buf.slice(buf.size()).map_async(wgpu::MapMode::Read, move |res| {
+            let mut st = cb_state.lock();
+            st.result = Some(res);
+            if let Some(w) = st.waker.take() {
+                w.wake();
+            }
+        });
+        Self { buffer, state }
+    }
+}
+
+impl Future for MapReadFuture {
+    type Output = Result<Vec<u8>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut st = self.state.lock();
+        match st.result.take() {
+            Some(Ok(())) => {
+                let buf = self.buffer.as_raw();
+                let data = buf.slice(buf.size()).get_mapped_range().to_vec();
+                buf.raw().unmap();
+                Poll::Ready(Ok(data))
+            }
+            Some(Err(e)) => Poll::Ready(Err(e.into())),
+            None => {
+                st.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Background thread polling the device so pending mappings resolve on their own.
+/// Stops (and joins) when dropped.
+pub struct DevicePoller {
+    stop:   Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DevicePoller {
+    pub(crate) fn spawn(device: Arc<Device>, interval: Duration) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("vknp-device-poller".into())
+            .spawn(move || {
+                while !flag.load(Ordering::Acquire) {
+                    if device.poll(PollType::Poll).is_err() {
+                        break;
+                    }
+                    std::thread::sleep(interval);
+                }
+            })?;
+        Ok(Self { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for DevicePoller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
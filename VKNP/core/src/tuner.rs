@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use parking_lot::Mutex;
+use wgpu::{Adapter, Device};
+
+use core_types::DataType;
+use crate::error::{Result, VknpError};
+
+/// Entry point, and the dtypes of the one variant a size is pinned for (`None`: all of them)
+type PinKey = (String, Option<(Vec<DataType>, Vec<DataType>)>);
+
+/// Picks the workgroup size of the kernels launched on one device: `LaunchConfig::Auto`
+/// tasks and the memory manager's own kernels (fill, copy, generate, random).
+///
+/// The default comes from the device type (CPU adapters prefer small groups, discrete
+/// GPUs large ones) and is clamped to `max_compute_invocations_per_workgroup` /
+/// `max_compute_workgroup_size_x`. Entry points can be pinned with `set`, or one dtype
+/// variant of them with `set_variant`, which takes precedence.
+pub struct WorkgroupTuner {
+    preferred: u32,
+    max:       u32,
+    pinned:    Mutex<HashMap<PinKey, u32>>,
+}
+
+impl WorkgroupTuner {
+    pub(crate) fn new(adapter: &Adapter, device: &Device) -> Self {
+        let limits = device.limits();
+        let max = limits.max_compute_invocations_per_workgroup.min(limits.max_compute_workgroup_size_x);
+        let wanted = match adapter.get_info().device_type {
+            wgpu::DeviceType::DiscreteGpu => 256,
+            wgpu::DeviceType::IntegratedGpu | wgpu::DeviceType::VirtualGpu => 128,
+            wgpu::DeviceType::Cpu | wgpu::DeviceType::Other => 64,
+        };
+        Self { preferred: largest_pow2_at_most(wanted.min(max)), max, pinned: Mutex::new(HashMap::new()) }
+    }
+
+    /// Size used for kernels without a pinned size
+    pub fn preferred(&self) -> u32 {
+        self.preferred
+    }
+
+    /// Largest 1-D workgroup the device accepts
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Pin the size used for every dtype variant of `entry`.
+    pub fn set(&self, entry: &str, size: u32) -> Result<()> {
+        self.check(size)?;
+        self.pinned.lock().insert((entry.to_string(), None), size);
+        Ok(())
+    }
+
+    /// Pin the size used for the variant of `entry` reading `t_in` and writing `t_out`.
+    pub fn set_variant(&self, entry: &str, t_in: &[DataType], t_out: &[DataType], size: u32) -> Result<()> {
+        self.check(size)?;
+        self.pinned.lock().insert((entry.to_string(), Some((t_in.to_vec(), t_out.to_vec()))), size);
+        Ok(())
+    }
+
+    /// Size for the variant of `entry` reading `t_in` and writing `t_out`: pinned for that
+    /// variant, else for the entry, else the preferred one.
+    pub fn size_for(&self, entry: &str, t_in: &[DataType], t_out: &[DataType]) -> u32 {
+        let pinned = self.pinned.lock();
+        pinned.get(&(entry.to_string(), Some((t_in.to_vec(), t_out.to_vec()))))
+            .or_else(|| pinned.get(&(entry.to_string(), None)))
+            .copied()
+            .unwrap_or(self.preferred)
+    }
+
+    /// `size` must be a workgroup size this device accepts.
+    pub fn check(&self, size: u32) -> Result<()> {
+        if size == 0 || size > self.max {
+            return Err(VknpError::Validation(format!(
+                "workgroup size {size} outside 1..={} on this device", self.max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Take over the pins of `old` (the tuner of a lost device) that fit this device.
+    pub(crate) fn inherit(&self, old: &WorkgroupTuner) {
+        let kept = old.pinned.lock().iter()
+            .filter(|&(_, &size)| size <= self.max)
+            .map(|(key, &size)| (key.clone(), size))
+            .collect::<Vec<_>>();
+        self.pinned.lock().extend(kept);
+    }
+}
+
+fn largest_pow2_at_most(n: u32) -> u32 {
+    if n == 0 { 1 } else { 1 << (31 - n.leading_zeros()) }
+}
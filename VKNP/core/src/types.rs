@@ -1,11 +1,13 @@
 use std::sync::Arc;
 use wgpu::{Buffer, BufferUsages, BindGroupLayout, ComputePipeline};
+use crate::error::{Result, VknpError};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BufferKind {
     Main,
     Upload,
     Download,
+    Uniform,
 }
 impl From<BufferKind> for BufferUsages {
     fn from(kind: BufferKind) -> Self {
@@ -13,19 +15,100 @@ impl From<BufferKind> for BufferUsages {
             BufferKind::Main => BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
             BufferKind::Upload => BufferUsages::MAP_WRITE | BufferUsages::COPY_SRC,
             BufferKind::Download => BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            BufferKind::Uniform => BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         }
     }
 }
 
+/// How a kernel parameter block is bound to the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParamBinding {
+    /// `var<push_constant>` (needs `Features::PUSH_CONSTANTS`, at most one per kernel)
+    PushConstant,
+    /// `var<uniform>` buffer
+    Uniform,
+    /// `var<storage, read>` buffer
+    Storage,
+}
+
+/// A parameter block resolved for one dispatch
+pub enum ParamArg {
+    /// Bytes for the push-constant range (length a multiple of 4)
+    PushConstant(Vec<u8>),
+    /// Uniform or storage buffer bound at the param's slot
+    Buffer(BufferHandle),
+}
+
+/// A GPU buffer, or an aligned sub-range of one (slab allocations).
+/// Bindings, copies and mappings only ever touch `offset..offset + size`.
 #[derive(Debug, Eq, PartialEq)]
-pub struct AbstractBuffer(pub(crate) Buffer);
+pub struct AbstractBuffer {
+    buffer: Buffer,
+    offset: u64,
+    size:   u64,
+}
 impl AbstractBuffer {
+    pub(crate) fn new(buffer: Buffer) -> Self {
+        let size = buffer.size();
+        Self { buffer, offset: 0, size }
+    }
+
     pub(crate) fn raw(&self) -> &wgpu::Buffer {
-        &self.0
+        &self.buffer
+    }
+
+    /// Byte offset of this range in the device buffer
+    pub fn offset(&self) -> u64 {
+        self.offset
     }
 
     pub fn size(&self) -> u64 {
-        self.0.size()
+        self.size
+    }
+
+    /// `size` bytes starting `offset` bytes into this range, sharing the device buffer.
+    /// Storage bindings need `offset` to be a multiple of `min_storage_buffer_offset_alignment`.
+    pub fn sub_range(&self, offset: u64, size: u64) -> Result<Self> {
+        if offset.checked_add(size).is_none_or(|end| end > self.size) {
+            return Err(VknpError::Validation(format!(
+                "sub-range {offset}+{size} outside a {}-byte buffer", self.size
+            )));
+        }
+        Ok(Self { buffer: self.buffer.clone(), offset: self.offset + offset, size })
+    }
+
+    /// Whether both ranges live in the same device buffer
+    pub fn same_buffer(&self, other: &AbstractBuffer) -> bool {
+        self.buffer == other.buffer
+    }
+
+    /// Copy `data` at `offset` of this range, which must currently be mapped for writing
+    /// (`offset` a multiple of `MAP_ALIGNMENT`, length a multiple of 4).
+    pub fn write_mapped(&self, offset: u64, data: &[u8]) {
+        let start = self.offset + offset;
+        self.buffer.slice(start..start + data.len() as u64).get_mapped_range_mut().copy_from_slice(data);
+    }
+
+    /// Unmap the device buffer (every range of it).
+    pub fn unmap(&self) {
+        self.buffer.unmap();
+    }
+
+    /// Map this range again; `callback` runs from a device poll once the GPU is done with it.
+    pub fn map_async(&self, mode: wgpu::MapMode, callback: impl FnOnce(crate::Result<()>) + Send + 'static) {
+        self.slice(self.size).map_async(mode, move |res| callback(res.map_err(Into::into)));
+    }
+
+    pub(crate) fn slice(&self, len: u64) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(self.offset..self.offset + len)
+    }
+
+    pub(crate) fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: self.offset,
+            size:   wgpu::BufferSize::new(self.size),
+        })
     }
 }
 
@@ -53,8 +136,36 @@ impl BufferHandle {
     pub fn strong_count(&self) -> usize { Arc::strong_count(&self.0) }
 }
 
+/// Release guard of a pooled allocation, shared by the clones of its `BufferToken`
+pub trait TokenGuard: Send + Sync {
+    /// Name the allocation (leak reports, labels of the dispatches binding it)
+    fn set_label(&self, label: &str);
+}
+
+/// Keeps the underlying buffer alive while a tensor references it.
+/// Clones share one release guard, dropped with the last of them.
 #[derive(Clone)]
-pub struct BufferToken(Arc<AbstractBuffer>);
+pub struct BufferToken {
+    _buffer: Option<Arc<AbstractBuffer>>,
+    guard:   Option<Arc<dyn TokenGuard>>,
+}
 impl BufferToken {
-    pub fn new(inner: Arc<AbstractBuffer>) -> Self { BufferToken(inner) }
-}
\ No newline at end of file
+    pub fn new(inner: Arc<AbstractBuffer>) -> Self { BufferToken { _buffer: Some(inner), guard: None } }
+    /// Token whose last clone drops `guard` (typically handing the allocation back to its
+    /// pool, which owns the buffer meanwhile and may move it, e.g. to host memory).
+    pub fn with_guard(guard: Arc<dyn TokenGuard>) -> Self { BufferToken { _buffer: None, guard: Some(guard) } }
+    /// Name the allocation behind this token (no-op for unpooled buffers).
+    pub fn set_label(&self, label: &str) {
+        if let Some(guard) = &self.guard {
+            guard.set_label(label);
+        }
+    }
+}
+/// Everything bound to a kernel for one dispatch
+pub struct KernelArgs<'a> {
+    pub inputs:  &'a [BufferHandle],
+    pub params:  &'a [ParamArg],
+    pub outputs: &'a [BufferHandle],
+    /// Label of the bind group (e.g. the tensors bound), shown in validation errors
+    pub label:   Option<&'a str>,
+}
@@ -4,17 +4,45 @@ use std::{
 };
 use parking_lot::Mutex;
 
-use vknp_core::{GpuContext, types::AbstractBindGroupLayout, types::AbstractComputePipeline};
+use vknp_core::{GpuContext, Result, WGSL_PRELUDE, WORKGROUP_SIZE_OVERRIDE, dispatch::declares_override, types::AbstractBindGroupLayout, types::AbstractComputePipeline, types::ParamBinding};
 use core_types::DataType;
 
-/// Signature of a specialized kernel: shader + dtypes
+/// Everything a kernel is specialized on
+pub struct KernelSpec<'a> {
+    /// Name of the op, for the pipeline label
+    pub op:             &'a str,
+    pub src:            &'a str,
+    pub entry:          &'a str,
+    pub t_in:           Vec<DataType>,
+    pub t_out:          Vec<DataType>,
+    pub params:         &'a [ParamBinding],
+    pub push_size:      u32,
+    /// Value of the `WG_SIZE` override, if the kernel declares it
+    pub workgroup_size: u32,
+}
+
+/// Signature of a specialized kernel: shader + dtypes + launch size
 #[derive(Clone, PartialEq, Eq, Hash)]
 struct KernelKey {
+    op:   Arc<str>,
     src:  Arc<str>,
     ent:  Arc<str>,
     t_in: Vec<DataType>,
     t_out: Vec<DataType>,
-    p_len: usize,
+    params: Vec<ParamBinding>,
+    push_size: u32,
+    workgroup_size: u32,
+}
+
+impl KernelKey {
+    /// e.g. `add:add_kernel(f32,f32)->(f32)`
+    fn label(&self) -> String {
+        let list = |types: &[DataType]| types.iter()
+            .map(|t| format!("{t:?}").to_lowercase())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{}:{}({})->({})", self.op, self.ent, list(&self.t_in), list(&self.t_out))
+    }
 }
 
 struct PipelineBundle {
@@ -35,18 +63,17 @@ impl KernelManager {
 
     pub fn get(
         &self,
-        src: &str,
-        entry: &str,
-        t_in: Vec<DataType>,
-        t_out: Vec<DataType>,
-        p_len: usize,
-    ) -> Result<(Arc<AbstractComputePipeline>, Arc<AbstractBindGroupLayout>), String> {
+        spec: KernelSpec,
+    ) -> Result<(Arc<AbstractComputePipeline>, Arc<AbstractBindGroupLayout>)> {
         let key = KernelKey {
-            src:  Arc::from(src),
-            ent:  Arc::from(entry),
-            t_in,
-            t_out,
-            p_len,
+            op:   Arc::from(spec.op),
+            src:  Arc::from(spec.src),
+            ent:  Arc::from(spec.entry),
+            t_in: spec.t_in,
+            t_out: spec.t_out,
+            params: spec.params.to_vec(),
+            push_size: spec.push_size,
+            workgroup_size: spec.workgroup_size,
         };
 
         // cache lookup
@@ -54,19 +81,53 @@ impl KernelManager {
             return Ok((b.pipeline.clone(), b.layout.clone()));
         }
 
-        // create layout + pipeline via GpuContext
-        let n_in = key.t_in.len() + key.p_len;
-        let n_out = key.t_out.len();
-        let layout   = self.ctx.create_storage_layout(n_in, n_out);
-        let pipeline = self.ctx.create_compute_pipeline(src, entry, &layout);
+        self.ctx.ensure_alive()?;
+        let bundle = Arc::new(Self::build(&self.ctx, &key)?);
+        self.cache.lock().insert(key, bundle.clone());
 
-        let bundle = Arc::new(PipelineBundle { pipeline: pipeline.clone(), layout: layout.clone() });
-        self.cache.lock().insert(key, bundle);
+        Ok((bundle.pipeline.clone(), bundle.layout.clone()))
+    }
 
-        Ok((pipeline, layout))
+    /// Move to a new device (typically after a loss) and rebuild every cached pipeline on it.
+    /// Pipelines of a workgroup size the new device does not accept are dropped: their next
+    /// launch resolves a size on it. On failure, the manager is left on the old device.
+    pub fn recreate(&mut self, ctx: GpuContext) -> Result<()> {
+        let keys: Vec<KernelKey> = self.cache.lock().keys()
+            .filter(|key| ctx.tuner().check(key.workgroup_size).is_ok())
+            .cloned()
+            .collect();
+        let mut cache = HashMap::with_capacity(keys.len());
+        for key in keys {
+            let bundle = Arc::new(Self::build(&ctx, &key)?);
+            cache.insert(key, bundle);
+        }
+        self.ctx = ctx;
+        *self.cache.get_mut() = cache;
+        Ok(())
+    }
+
+    /// Resolve `var<param>` declarations and prepend the WGSL prelude,
+    /// then create layout + pipeline via GpuContext.
+    fn build(ctx: &GpuContext, key: &KernelKey) -> Result<PipelineBundle> {
+        let n_in = key.t_in.len();
+        let n_out = key.t_out.len();
+        let src = GpuContext::expand_param_declarations(&key.src, n_in, &key.params)?;
+        let src = format!("{WGSL_PRELUDE}{src}");
+        let constants: &[(&str, f64)] = if declares_override(&key.src, WORKGROUP_SIZE_OVERRIDE) {
+            &[(WORKGROUP_SIZE_OVERRIDE, key.workgroup_size as f64)]
+        } else {
+            &[]
+        };
+        let layout   = ctx.create_storage_layout(n_in, &key.params, n_out);
+        let pipeline = ctx.create_compute_pipeline(&src, &key.ent, &layout, key.push_size, constants, &key.label())?;
+        Ok(PipelineBundle { pipeline, layout })
     }
-}
 
+    /// Number of cached pipelines
+    pub fn len(&self) -> usize {
+        self.cache.lock().len()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -96,14 +157,84 @@ mod tests {
         let t_out = vec![DataType::F32];
 
         // Compile the kernel
-        let (pipeline, layout) = manager.get(&src, entry, t_in.clone(), t_out.clone(), 0)
+        let spec = || KernelSpec {
+            op: "add", src, entry, t_in: t_in.clone(), t_out: t_out.clone(), params: &[], push_size: 0, workgroup_size: 64,
+        };
+        let (pipeline, layout) = manager.get(spec())
             .expect("shader compilation failed");
 
         // Retrieve and compare
-        let (pipeline2, layout2) = manager.get(&src, entry, t_in.clone(), t_out.clone(), 0)
+        let (pipeline2, layout2) = manager.get(spec())
             .expect("shader compilation failed");
 
         assert_eq!(pipeline, pipeline2);
         assert_eq!(layout, layout2);
+        assert_eq!(manager.len(), 1);
+    }
+
+    #[test]
+    fn pipeline_label_names_op_and_dtypes() {
+        let key = KernelKey {
+            op: Arc::from("add"),
+            src: Arc::from(""),
+            ent: Arc::from("add_kernel"),
+            t_in: vec![DataType::F32, DataType::I32],
+            t_out: vec![DataType::F32],
+            params: vec![],
+            push_size: 0,
+            workgroup_size: 64,
+        };
+        assert_eq!(key.label(), "add:add_kernel(f32,i32)->(f32)");
+    }
+
+    #[test]
+    fn bad_shader_is_an_error() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let manager = KernelManager::new(ctx);
+
+        let src = "@compute @workgroup_size(64) fn k() { let x: f32 = 1u; }";
+        let spec = || KernelSpec {
+            op: "k", src, entry: "k", t_in: vec![], t_out: vec![], params: &[], push_size: 0, workgroup_size: 64,
+        };
+        let err = manager.get(spec()).err().unwrap();
+        assert!(matches!(err, vknp_core::VknpError::ShaderCompile { .. }), "got {err}");
+
+        // nothing was cached: a second call fails the same way
+        assert!(manager.get(spec()).is_err());
+    }
+
+    #[test]
+    fn failed_recreate_keeps_the_old_pipelines() {
+        let plain = block_on(GpuContext::new()).unwrap();
+        if !plain.adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            return;
         }
-    }
\ No newline at end of file
+        let limits = wgpu::Limits { max_push_constant_size: 128, ..Default::default() };
+        let pushing = block_on(GpuContext::builder()
+            .required_features(wgpu::Features::PUSH_CONSTANTS)
+            .required_limits(limits)
+            .build()).unwrap();
+        let mut manager = KernelManager::new(pushing.clone());
+
+        let src = r#"
+            var<param> P: u32;
+            @group(0) @binding(1) var<storage, read_write> C: array<u32>;
+            @compute @workgroup_size(64)
+            fn k() { C[0] = P; }
+        "#;
+        let params = [ParamBinding::PushConstant];
+        let spec = |push_size| KernelSpec {
+            op: "k", src, entry: "k", t_in: vec![], t_out: vec![DataType::U32], params: &params, push_size, workgroup_size: 64,
+        };
+        let (pushed, _) = manager.get(spec(4)).unwrap();
+        let uniform = [ParamBinding::Uniform];
+        let (bound, _) = manager.get(KernelSpec { params: &uniform, push_size: 0, ..spec(0) }).unwrap();
+
+        // the device without push constants cannot build the first one: nothing is swapped in
+        assert!(manager.recreate(plain).is_err());
+        assert!(Arc::ptr_eq(&manager.ctx.device, &pushing.device));
+        assert_eq!(manager.len(), 2);
+        assert_eq!(manager.get(spec(4)).unwrap().0, pushed);
+        assert_eq!(manager.get(KernelSpec { params: &uniform, push_size: 0, ..spec(0) }).unwrap().0, bound);
+    }
+}
@@ -1,91 +1,254 @@
 mod kernel_manager;
+mod registry;
+
+use std::sync::Arc;
 
 use memory::MemoryManager;
-use vknp_ops::types::{GpuTask, PreparedOp};
-use vknp_core::{GpuContext, types::BufferHandle};
+use core_types::BufferId;
+use vknp_ops::types::{GpuTask, LaunchConfig, ParamBuffer, PreparedOp};
+use vknp_core::{CommandBatch, GpuContext, GpuFence, Profiler, Result, VknpError, WORKGROUP_SIZE_OVERRIDE, dispatch::declares_override, types::{BufferHandle, BufferKind, BufferToken, KernelArgs, ParamArg, ParamBinding}};
 
-use kernel_manager::KernelManager;
+use kernel_manager::{KernelManager, KernelSpec};
+pub use registry::{DeviceRegistry, DeviceSlot};
+pub use vknp_core::WorkgroupTuner;
 
 
 /// Execution engine for running GPU tasks.
 pub struct ExecutionEngine {
-    ctx:     GpuContext,
-    kernels: KernelManager,
+    ctx:      GpuContext,
+    kernels:  KernelManager,
+    profiler: Option<Arc<Profiler>>,
 }
 
 impl ExecutionEngine {
     pub fn new(ctx: GpuContext) -> Self {
-        Self { kernels: KernelManager::new(ctx.clone()), ctx }
+        Self { kernels: KernelManager::new(ctx.clone()), profiler: None, ctx }
+    }
+
+    /// Start timing every task run by this engine (GPU timestamps when the device has
+    /// `TIMESTAMP_QUERY`, host clock otherwise).
+    pub fn enable_profiling(&mut self) -> Arc<Profiler> {
+        self.set_profiler(Profiler::new(&self.ctx))
+    }
+
+    /// Time every task run by this engine with a given profiler.
+    pub fn set_profiler(&mut self, profiler: Arc<Profiler>) -> Arc<Profiler> {
+        self.profiler = Some(profiler.clone());
+        profiler
+    }
+
+    pub fn disable_profiling(&mut self) -> Option<Arc<Profiler>> {
+        self.profiler.take()
     }
 
-    fn run_gpu_task(&self, task: GpuTask, mm: &MemoryManager) -> anyhow::Result<()> {
-        // 1) Allocate and write parameter buffers
-        let mut param_ids = Vec::with_capacity(task.params.len());
-        for p in &task.params {
-            let (id, _) = mm.allocate_raw(p.bytes.len())?;
-            mm.write_to_buffer(id, &p.bytes)?;
-            param_ids.push(id);
+    pub fn profiler(&self) -> Option<&Arc<Profiler>> {
+        self.profiler.as_ref()
+    }
+
+    /// Workgroup sizes used for `LaunchConfig::Auto` tasks on this device (shared with
+    /// the memory manager's kernels through the context).
+    pub fn tuner(&self) -> &WorkgroupTuner {
+        self.ctx.tuner()
+    }
+
+    /// Decide how each param is actually bound on this device: at most one push-constant
+    /// block, and only if the device supports it at that size; otherwise uniform.
+    fn resolve_param_bindings(&self, params: &[ParamBuffer]) -> Vec<ParamBinding> {
+        let mut pushed = false;
+        params.iter().map(|p| match p.binding {
+            ParamBinding::PushConstant if !pushed && self.ctx.supports_push_constants(p.bytes.len()) => {
+                pushed = true;
+                ParamBinding::PushConstant
+            }
+            ParamBinding::PushConstant => ParamBinding::Uniform,
+            other => other,
+        }).collect()
+    }
+
+    /// Move to a new device (typically after a loss), rebuilding every cached pipeline.
+    pub fn recreate(&mut self, ctx: GpuContext) -> Result<()> {
+        self.kernels.recreate(ctx.clone())?;
+        self.ctx = ctx;
+        Ok(())
+    }
+
+    /// The device this engine dispatches to.
+    pub fn context(&self) -> &GpuContext {
+        &self.ctx
+    }
+
+    /// Number of pipelines compiled so far
+    pub fn cached_kernels(&self) -> usize {
+        self.kernels.len()
+    }
+
+    fn record_gpu_task(&self, batch: &mut CommandBatch, task: GpuTask, mm: &MemoryManager) -> Result<()> {
+        self.ctx.ensure_alive()?;
+
+        // 1) Parameter blocks: push constants, or recycled buffers written through the queue
+        let bindings = self.resolve_param_bindings(&task.params);
+        let mut push_size = 0u32;
+        // the batch holds the param buffers until it is submitted: the tokens can drop with this call
+        let mut param_tokens: Vec<BufferToken> = Vec::new();
+        let mut params: Vec<ParamArg> = Vec::with_capacity(task.params.len());
+        for (p, b) in task.params.iter().zip(&bindings) {
+            let kind = match b {
+                ParamBinding::PushConstant => {
+                    let mut bytes = p.bytes.clone();
+                    bytes.resize(bytes.len().next_multiple_of(4), 0);
+                    push_size = bytes.len() as u32;
+                    params.push(ParamArg::PushConstant(bytes));
+                    continue;
+                }
+                ParamBinding::Uniform => BufferKind::Uniform,
+                ParamBinding::Storage => BufferKind::Main,
+            };
+            let (buf, token) = mm.alloc_param(&p.bytes, kind)?;
+            param_tokens.push(token);
+            params.push(ParamArg::Buffer(buf));
+        }
+
+        // 2) Workgroup size, given to the kernel through its `WG_SIZE` override
+        if task.launch == LaunchConfig::Auto && !declares_override(&task.pipeline_source, WORKGROUP_SIZE_OVERRIDE) {
+            return Err(VknpError::Validation(format!(
+                "`{}` uses LaunchConfig::Auto but does not declare `override {WORKGROUP_SIZE_OVERRIDE}`",
+                task.entry_point
+            )));
         }
+        let workgroup_size = match task.launch {
+            LaunchConfig::Fixed(size) => size,
+            LaunchConfig::Auto => self.tuner().size_for(&task.entry_point, &task.input_types, &task.output_types),
+        };
+        self.tuner().check(workgroup_size)?;
 
-        // 2) Pipeline + layout
-        let (pipeline, layout) = self.kernels
-            .get(&task.pipeline_source, &task.entry_point, task.input_types, task.output_types, task.params.len())
-            .map_err(|e| anyhow::anyhow!("failed to get kernel: {e}"))?;
+        // 3) Pipeline + layout
+        let (pipeline, layout) = self.kernels.get(KernelSpec {
+            op:             &task.op_name,
+            src:            &task.pipeline_source,
+            entry:          &task.entry_point,
+            t_in:           task.input_types,
+            t_out:          task.output_types,
+            params:         &bindings,
+            push_size,
+            workgroup_size,
+        })?;
 
-        // 3) Total from the 1st output
+        // 4) Total from the 1st output
         let total: u32 = {
             let vd = &task.output_descs[0];
             (0..vd.ndim as usize).map(|i| vd.shape[i]).product()
         };
 
-        // 4) Create immutable references and dispatch
-        {
-            let inputs: Vec<BufferHandle> = task.input_ids.iter()
-                .map(|&id| mm.get_ref(id).ok_or_else(|| anyhow::anyhow!("missing input buffer: {:?}", id)))
-                .collect::<Result<_, _>>()?;
+        // 5) Resolve buffers (paging spilled ones back in) and record the dispatch
+        let inputs: Vec<BufferHandle> = task.input_ids.iter()
+            .map(|&id| mm.resident(id))
+            .collect::<Result<_, _>>()?;
 
-            let outputs: Vec<BufferHandle> = task.output_ids.iter()
-                .map(|&id| mm.get_ref(id).ok_or_else(|| anyhow::anyhow!("missing output buffer: {:?}", id)))
-                .collect::<Result<_, _>>()?;
+        let mut outputs: Vec<BufferHandle> = task.output_ids.iter()
+            .map(|&id| mm.resident(id))
+            .collect::<Result<_, _>>()?;
 
-            let param_bufs: Vec<BufferHandle> = param_ids.iter()
-                .map(|&id| mm.get_ref(id).ok_or_else(|| anyhow::anyhow!("param buffer missing: {:?}", id)))
-                .collect::<Result<_, _>>()?;
+        // 6) Uploads queued on the staging belt (page-ins included) land before this dispatch
+        mm.flush_uploads_into(batch);
 
-            let all_inputs: Vec<BufferHandle> =
-                inputs.iter().cloned().chain(param_bufs.iter().cloned()).collect();
-
-            self.ctx.dispatch_compute_1d(&pipeline, &layout, &all_inputs, &outputs, total, 64);
+        // 7) A dispatch cannot write a buffer it also reads: an output sharing a slab block
+        //    with an input or storage param is written to a scratch copy of its allocation
+        //    (so elements outside its view are kept), then copied back into place
+        let read: Vec<&BufferHandle> = inputs.iter()
+            .chain(params.iter().filter_map(|p| match p {
+                ParamArg::Buffer(b) => Some(b),
+                ParamArg::PushConstant(_) => None,
+            }))
+            .collect();
+        let mut write_backs: Vec<(BufferHandle, BufferHandle, BufferToken)> = Vec::new();
+        for out in outputs.iter_mut() {
+            if read.iter().any(|r| r.as_raw().same_buffer(out.as_raw())) {
+                let (scratch, token) = mm.allocate_scratch(out.as_raw().size() as usize)?;
+                batch.copy_buffer_to_buffer(out.as_raw(), scratch.as_raw(), out.as_raw().size());
+                write_backs.push((scratch.clone(), std::mem::replace(out, scratch), token));
+            }
         }
 
-        // 5) Re-borrow mutably to release
-        for id in param_ids {
-            mm.release(id);
+        let label = bind_group_label(&task.op_name, mm, &task.input_ids, &task.output_ids);
+        let args = KernelArgs { inputs: &inputs, params: &params, outputs: &outputs, label: Some(&label) };
+        batch.set_op_label(&task.op_name, &task.entry_point);
+        batch.dispatch_1d(&pipeline, &layout, &args, total, workgroup_size)?;
+        for (scratch, out, _token) in write_backs {
+            batch.copy_buffer_to_buffer(scratch.as_raw(), out.as_raw(), out.as_raw().size());
         }
 
         Ok(())
     }
 
-    pub fn run_prepared(
+    /// Start a batch on this engine's device.
+    pub fn begin_batch(&self) -> CommandBatch {
+        self.begin_labeled_batch("vknp-batch")
+    }
+
+    fn begin_labeled_batch(&self, label: &str) -> CommandBatch {
+        let batch = self.ctx.begin_batch(label);
+        match &self.profiler {
+            Some(p) => batch.with_profiler(p.clone()),
+            None => batch,
+        }
+    }
+
+    /// Record a prepared op (every `Composite` child included) into `batch`, one compute pass per task.
+    pub fn record_prepared(
         &self,
+        batch: &mut CommandBatch,
         prepared: PreparedOp,
         mm: &MemoryManager,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         match prepared {
-            PreparedOp::Gpu(task) => {
-                // Only check success of one operation
-                self.run_gpu_task(task, mm)?;
-                Ok(())
-            }
+            PreparedOp::Gpu(task) => self.record_gpu_task(batch, *task, mm),
             PreparedOp::Composite(ops) => {
-                // Check success of all sub-operations
                 for sub_op in ops {
-                    self.run_prepared(sub_op, mm)?;
+                    self.record_prepared(batch, sub_op, mm)?;
                 }
                 Ok(())
             }
         }
     }
+
+    /// Record a whole chain of ops and submit it once.
+    pub fn run_batch(
+        &self,
+        ops: impl IntoIterator<Item = PreparedOp>,
+        mm: &MemoryManager,
+    ) -> Result<GpuFence> {
+        let mut batch = self.begin_batch();
+        for op in ops {
+            self.record_prepared(&mut batch, op, mm)?;
+        }
+        batch.submit()
+    }
+
+    /// Run a single prepared op (one submission, composites included).
+    pub fn run_prepared(
+        &self,
+        prepared: PreparedOp,
+        mm: &MemoryManager,
+    ) -> Result<()> {
+        let label = match &prepared {
+            PreparedOp::Gpu(task) => format!("vknp-{}", task.op_name),
+            PreparedOp::Composite(_) => "vknp-composite".to_string(),
+        };
+        let mut batch = self.begin_labeled_batch(&label);
+        self.record_prepared(&mut batch, prepared, mm)?;
+        batch.submit()?;
+        Ok(())
+    }
+}
+
+/// Label of a dispatch's bind group: the op and the tensors it binds, by name when
+/// they have one, e.g. `matmul(weights, BufferId(3)) -> (BufferId(4))`.
+fn bind_group_label(op: &str, mm: &MemoryManager, inputs: &[BufferId], outputs: &[BufferId]) -> String {
+    let names = |ids: &[BufferId]| ids.iter()
+        .map(|&id| mm.label_of(id).unwrap_or_else(|| id.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{op}({}) -> ({})", names(inputs), names(outputs))
 }
 
 
@@ -96,8 +259,10 @@ impl ExecutionEngine {
 mod tests {
     use super::*;
     use vknp_ops::OpRegistry;
+    use vknp_ops::types::OpError;
     use pollster::block_on;
-    use vknp_core::GpuContext;
+    use vknp_core::{GpuContext, ProfilerMode};
+    use core_types::DataType;
     use tensor::Tensor;
 
     #[test]
@@ -111,7 +276,8 @@ mod tests {
         // --- tensors -----------------------------------------------------
         let a = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0], &[4], 0);
         let b = Tensor::<f32>::from_vec(&mm, &[5.0, 6.0, 7.0, 8.0], &[1, 4], 0);
-        let c = Tensor::<f32>::empty(&mm, &[4], 0);
+        // [4] and [1, 4] broadcast to [1, 4]
+        let c = Tensor::<f32>::empty(&mm, &[1, 4], 0);
 
         // --- registry & prepare ------------------------------------------
         let mut reg = OpRegistry::new();
@@ -128,4 +294,499 @@ mod tests {
         let result: Vec<f32> = c.to_vec(&mm);
         assert_eq!(result, vec![6.0, 8.0, 10.0, 12.0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn run_add_on_slab_allocated_tensors() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::with_config(ctx.clone(), memory::pool::PoolConfig::slab());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        // every operand is a sub-range of the same backing buffer, bound at its own offset
+        let tensors: Vec<_> = (0..8).map(|i| Tensor::<f32>::from_vec(&mm, &[i as f32; 5], &[5], 0)).collect();
+        let out: Vec<_> = (0..4).map(|_| Tensor::<f32>::empty(&mm, &[5], 0)).collect();
+        for (pair, c) in tensors.chunks(2).zip(&out) {
+            let op = reg.check_and_prepare("add", &[(&pair[0]).into(), (&pair[1]).into()], &[c.into()]).unwrap();
+            engine.run_prepared(op, &mm).unwrap();
+        }
+        for (i, c) in out.iter().enumerate() {
+            assert_eq!(c.to_vec(&mm), vec![(4 * i + 1) as f32; 5]);
+        }
+
+        let [main, _, _, _, scratch] = mm.pool_stats();
+        assert_eq!(main.device_allocs, 1);
+        assert_eq!(main.slab_allocs, 12);
+        // the outputs share their block with the inputs: written through scratch buffers
+        assert!(scratch.device_allocs >= 1);
+    }
+
+    #[test]
+    fn run_add_pages_spilled_inputs_back_in() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        let a = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0], &[3], 0);
+        let b = Tensor::<f32>::from_vec(&mm, &[10.0, 20.0, 30.0], &[3], 0);
+        let c = Tensor::<f32>::empty(&mm, &[3], 0);
+        b.pin(&mm).unwrap();
+        assert!(mm.evict(a.buffer_id()).unwrap());
+        assert!(!mm.evict(b.buffer_id()).unwrap(), "pinned tensors stay resident");
+
+        let op = reg.check_and_prepare("add", &[(&a).into(), (&b).into()], &[(&c).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert!(!mm.is_spilled(a.buffer_id()));
+        assert_eq!(c.to_vec(&mm), vec![11.0, 22.0, 33.0]);
+    }
+
+    #[test]
+    fn run_add_broadcasts_into_allocated_outputs() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        let col = Tensor::<f32>::from_vec(&mm, &[10.0, 20.0, 30.0], &[3, 1], 0);
+        let row = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0], &[4], 0);
+        let (op, outputs) = reg.prepare_alloc("add", &[(&col).into(), (&row).into()], &mm).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+
+        let c = Tensor::<f32>::try_from(outputs.into_iter().next().unwrap()).unwrap();
+        assert_eq!(c.shape(), vec![3, 4]);
+        assert_eq!(c.to_vec(&mm), vec![
+            11.0, 12.0, 13.0, 14.0,
+            21.0, 22.0, 23.0, 24.0,
+            31.0, 32.0, 33.0, 34.0,
+        ]);
+    }
+
+    #[test]
+    fn run_copy_between_views_and_dtypes() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        let a = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], &[2, 3], 0);
+        let at = a.transpose(0, 1).unwrap();
+        let (op, outputs) = reg.prepare_alloc("copy", &[(&at).into()], &mm).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        let c = Tensor::<f32>::try_from(outputs.into_iter().next().unwrap()).unwrap();
+        assert_eq!(c.to_vec(&mm), vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+
+        // f32 → i32, flipped, into a column of a larger tensor
+        let m = Tensor::<i32>::zeros(&mm, &[3, 2], 0);
+        let col = m.narrow(1, 1, 1).unwrap();
+        let src = c.narrow(1, 0, 1).unwrap().flip(&[0]).unwrap();
+        let op = reg.check_and_prepare("copy", &[(&src).into()], &[(&col).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert_eq!(m.to_vec(&mm), vec![0, 3, 0, 2, 0, 1]);
+    }
+
+    #[test]
+    fn run_generators_with_scalars() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        // 5, 3, 1, -1 down a column of a larger tensor
+        let m = Tensor::<i32>::zeros(&mm, &[4, 2], 0);
+        let col = m.narrow(1, 1, 1).unwrap();
+        let op = reg.check_and_prepare_with("arange", &[], &[(&col).into()], &[5.0, -2.0]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert_eq!(m.to_vec(&mm), vec![0, 5, 0, 3, 0, 1, 0, -1]);
+
+        // omitted scalars take their default: endpoint = 1
+        let x = Tensor::<f32>::empty(&mm, &[3], 0);
+        engine.run_prepared(reg.check_and_prepare_with("linspace", &[], &[(&x).into()], &[-1.0, 1.0]).unwrap(), &mm).unwrap();
+        assert_eq!(x.to_vec(&mm), vec![-1.0, 0.0, 1.0]);
+
+        // a batch of masks over the last two dimensions
+        let masks = Tensor::<u32>::empty(&mm, &[2, 2, 2], 0);
+        engine.run_prepared(reg.check_and_prepare("tril", &[], &[(&masks).into()]).unwrap(), &mm).unwrap();
+        assert_eq!(masks.to_vec(&mm), vec![1, 0, 1, 1, 1, 0, 1, 1]);
+
+        let err = reg.check_and_prepare_with("eye", &[], &[(&masks).into()], &[0.0, 1.0]).unwrap_err();
+        assert!(matches!(err, OpError::ScalarArity { expected: 1, found: 2, .. }), "{err}");
+
+        // an integer output cannot hold a fractional step; a float one can
+        let err = reg.check_and_prepare_with("arange", &[], &[(&col).into()], &[0.0, 0.5]).unwrap_err();
+        assert!(matches!(err, OpError::Invalid(_)), "{err}");
+        assert!(reg.check_and_prepare_with("arange", &[], &[(&x).into()], &[0.0, 0.5]).is_ok());
+        let err = reg.check_and_prepare_with("triu", &[], &[(&masks).into()], &[0.5]).unwrap_err();
+        assert!(matches!(err, OpError::Invalid(_)), "{err}");
+    }
+
+    #[test]
+    fn run_random_ops_match_the_generator() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        // element i depends on its row-major index only, not on the layout of the output
+        let mut g = tensor::Generator::new(9);
+        g.uniform::<f32>(&mm, &[3], 0.0, 1.0, 0).unwrap();
+        let state = g.state();
+        let expected = g.uniform::<f32>(&mm, &[2, 3], 5.0, 6.0, 0).unwrap();
+        let out = Tensor::<f32>::empty(&mm, &[3, 2], 0);
+        let view = out.transpose(0, 1).unwrap();
+        let scalars = [5.0, 6.0, state.seed as f64, state.stream as f64, state.offset as f64];
+        engine.run_prepared(reg.check_and_prepare_with("uniform", &[], &[(&view).into()], &scalars).unwrap(), &mm).unwrap();
+        assert_eq!(view.contiguous(&mm).unwrap().to_vec(&mm), expected.to_vec(&mm));
+
+        // “choice” samples its input, converting to the output dtype
+        let src = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0], &[2, 2], 0);
+        let picked = Tensor::<i32>::empty(&mm, &[4], 0);
+        let op = reg.check_and_prepare_with("choice", &[(&src).into()], &[(&picked).into()], &[0.0, 3.0]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        let mut picked = picked.to_vec(&mm);
+        picked.sort();
+        assert_eq!(picked, vec![1, 2, 3, 4]);
+
+        let ints = Tensor::<f32>::empty(&mm, &[4], 0);
+        let err = reg.check_and_prepare("randint", &[], &[(&ints).into()]).unwrap_err();
+        assert!(matches!(err, OpError::DtypeMismatch { .. }), "{err}");
+
+        // the scalars are checked as `Generator` does
+        let ints = Tensor::<i32>::empty(&mm, &[4], 0);
+        let err = reg.check_and_prepare_with("randint", &[], &[(&ints).into()], &[3.0, 3.0]).unwrap_err();
+        assert!(matches!(err, OpError::Invalid(VknpError::Op(_))), "{err}");
+        let five = Tensor::<f32>::empty(&mm, &[5], 0);
+        let err = reg.check_and_prepare_with("choice", &[(&src).into()], &[(&five).into()], &[0.0]).unwrap_err();
+        assert!(matches!(err, OpError::Invalid(VknpError::Shape(_))), "{err}");
+    }
+
+    #[test]
+    fn run_in_place_add_on_a_partial_view_keeps_the_rest() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        let t = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], &[2, 3], 0);
+        let b = Tensor::<f32>::from_vec(&mm, &[10.0, 20.0], &[2, 1], 0);
+        let col = t.narrow(1, 0, 1).unwrap();
+        let op = reg.check_and_prepare("add", &[(&col).into(), (&b).into()], &[(&col).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert_eq!(t.to_vec(&mm), vec![11.0, 2.0, 3.0, 24.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn run_add_on_transposed_and_flipped_views() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        let a = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], &[2, 3], 0);
+        let b = Tensor::<f32>::from_vec(&mm, &[10.0, 20.0, 30.0, 40.0, 50.0, 60.0], &[3, 2], 0);
+        let c = Tensor::<f32>::empty(&mm, &[3, 2], 0);
+        let at = a.transpose(0, 1).unwrap();
+        let bf = b.flip(&[0]).unwrap();
+
+        let op = reg.check_and_prepare("add", &[(&at).into(), (&bf).into()], &[(&c).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert_eq!(c.to_vec(&mm), vec![51.0, 64.0, 32.0, 45.0, 13.0, 26.0]);
+    }
+
+    #[test]
+    fn bind_group_label_names_the_tensors() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let w = Tensor::<f32>::empty(&mm, &[4], 0).with_name("weights");
+        let x = Tensor::<f32>::empty(&mm, &[4], 0);
+        let y = Tensor::<f32>::empty(&mm, &[4], 0).with_name("y");
+        let label = bind_group_label("add", &mm, &[w.buffer_id(), x.buffer_id()], &[y.buffer_id()]);
+        assert_eq!(label, format!("add(weights, {}) -> (y)", x.buffer_id()));
+    }
+
+    #[test]
+    fn registry_routes_by_device_and_copies_across_devices() {
+        // Two contexts on the same adapter are enough to exercise routing
+        let mut reg = DeviceRegistry::new();
+        let d0 = reg.add(block_on(GpuContext::new()).unwrap());
+        let d1 = reg.add(block_on(GpuContext::new()).unwrap());
+        let mm0 = memory::DeviceMemory::memory(&reg, d0).unwrap();
+        let mm1 = memory::DeviceMemory::memory(&reg, d1).unwrap();
+
+        let a0 = Tensor::<f32>::from_vec(mm0, &[1.0, 2.0, 3.0], &[3], d0);
+        let b1 = Tensor::<f32>::from_vec(mm1, &[10.0, 20.0, 30.0], &[3], d1);
+        let c1 = Tensor::<f32>::empty(mm1, &[3], d1);
+
+        let mut ops = OpRegistry::new();
+        ops.collect_inventory();
+
+        // mixing devices is rejected
+        let err = ops
+            .check_and_prepare("add", &[(&a0).into(), (&b1).into()], &[(&c1).into()])
+            .unwrap_err();
+        assert!(matches!(err, vknp_ops::types::OpError::DeviceMismatch { expected: 0, found: 1, .. }));
+
+        // host-staged copy, then the op runs on device 1
+        let a1 = a0.to_device(&reg, d1).unwrap();
+        assert_eq!(a1.device_id(), d1);
+        assert_eq!(a1.to_vec(mm1), vec![1.0, 2.0, 3.0]);
+
+        let op = ops
+            .check_and_prepare("add", &[(&a1).into(), (&b1).into()], &[(&c1).into()])
+            .unwrap();
+        reg.run_prepared(op).unwrap();
+        assert_eq!(c1.to_vec(mm1), vec![11.0, 22.0, 33.0]);
+
+        // unknown device
+        assert!(a0.to_device(&reg, 7).is_err());
+    }
+
+    #[test]
+    fn run_task_with_each_param_binding() {
+        const SCALE_WGSL: &str = r#"
+            struct P { k: f32, n: u32 };
+            @group(0) @binding(0) var<storage, read> X: array<f32>;
+            var<param> p: P;
+            @group(0) @binding(2) var<storage, read_write> Y: array<f32>;
+            @compute @workgroup_size(64)
+            fn scale(@builtin(global_invocation_id) gid: vec3<u32>) {
+                if (gid.x >= p.n) { return; }
+                Y[gid.x] = X[gid.x] * p.k;
+            }
+        "#;
+
+        // Push constants only if the adapter has them; otherwise they fall back to uniform
+        let plain = block_on(GpuContext::new()).unwrap();
+        let mut contexts = vec![plain.clone()];
+        if plain.adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            let limits = wgpu::Limits { max_push_constant_size: 128, ..Default::default() };
+            contexts.push(block_on(GpuContext::builder()
+                .required_features(wgpu::Features::PUSH_CONSTANTS)
+                .required_limits(limits)
+                .build()).unwrap());
+        }
+
+        for ctx in contexts {
+            let mm = MemoryManager::new(ctx.clone());
+            let engine = ExecutionEngine::new(ctx.clone());
+            let x = Tensor::<f32>::from_vec(&mm, &[1.0, 2.0, 3.0], &[3], 0);
+
+            for param in [
+                ParamBuffer::push_constant(bytemuck::cast_slice(&[2.0f32.to_bits(), 3]).to_vec()),
+                ParamBuffer::uniform(bytemuck::cast_slice(&[2.0f32.to_bits(), 3]).to_vec()),
+                ParamBuffer::storage(bytemuck::cast_slice(&[2.0f32.to_bits(), 3]).to_vec()),
+            ] {
+                let y = Tensor::<f32>::empty(&mm, &[3], 0);
+                let task = GpuTask {
+                    op_name:         "scale".to_string(),
+                    device_id:       0,
+                    pipeline_source: SCALE_WGSL.to_string(),
+                    entry_point:     "scale".to_string(),
+                    input_descs:     vec![*x.view()],
+                    output_descs:    vec![*y.view()],
+                    input_types:     vec![x.dtype()],
+                    output_types:    vec![y.dtype()],
+                    input_ids:       vec![x.buffer_id()],
+                    output_ids:      vec![y.buffer_id()],
+                    params:          vec![param],
+                    launch:          LaunchConfig::Fixed(64),
+                };
+                engine.run_prepared(PreparedOp::Gpu(Box::new(task)), &mm).unwrap();
+                assert_eq!(y.to_vec(&mm), vec![2.0, 4.0, 6.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn run_op_chain_in_one_batch() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx);
+
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        // x1 = x0 + one, x2 = x1 + one, ... : every op depends on the previous one
+        let one = Tensor::<f32>::from_vec(&mm, &[1.0; 5], &[5], 0);
+        let mut chain = vec![Tensor::<f32>::from_vec(&mm, &[0.0, 1.0, 2.0, 3.0, 4.0], &[5], 0)];
+        let mut ops = Vec::new();
+        for i in 0..8 {
+            let next = Tensor::<f32>::empty(&mm, &[5], 0);
+            ops.push(reg.check_and_prepare("add", &[(&chain[i]).into(), (&one).into()], &[(&next).into()]).unwrap());
+            chain.push(next);
+        }
+
+        // a composite is flattened into the same batch
+        let last = chain.last().unwrap();
+        let out = Tensor::<f32>::empty(&mm, &[5], 0);
+        let tail = reg.check_and_prepare("add", &[last.into(), last.into()], &[(&out).into()]).unwrap();
+        ops.push(PreparedOp::Composite(vec![tail]));
+
+        let fence = engine.run_batch(ops.clone(), &mm).unwrap();
+        fence.wait().unwrap();
+        assert!(fence.is_complete().unwrap());
+        assert_eq!(out.to_vec(&mm), vec![16.0, 18.0, 20.0, 22.0, 24.0]);
+
+        // one param buffer per op inside a batch, all of them recycled by the next one
+        let uniforms = mm.pool_stats()[1];
+        assert_eq!(uniforms.device_allocs, 9);
+        engine.run_batch(ops, &mm).unwrap().wait().unwrap();
+        assert_eq!(mm.pool_stats()[1].device_allocs, 9);
+        assert_eq!(mm.pool_stats()[1].reuses, uniforms.reuses + 9);
+        assert_eq!(out.to_vec(&mm), vec![16.0, 18.0, 20.0, 22.0, 24.0]);
+    }
+
+    #[test]
+    fn device_loss_fails_fast_and_recreate_recovers() {
+        let mut reg = DeviceRegistry::new();
+        let d = reg.add(block_on(GpuContext::new()).unwrap());
+        let mut ops = OpRegistry::new();
+        ops.collect_inventory();
+
+        let run_add = |reg: &DeviceRegistry| -> Result<Vec<f32>> {
+            let mm = memory::DeviceMemory::memory(reg, d).unwrap();
+            let a = Tensor::<f32>::try_from_vec(mm, &[1.0, 2.0], &[2], d)?;
+            let c = Tensor::<f32>::try_empty(mm, &[2], d)?;
+            let op = ops.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&c).into()])?;
+            reg.run_prepared(op)?;
+            c.try_to_vec(mm)
+        };
+        assert_eq!(run_add(&reg).unwrap(), vec![2.0, 4.0]);
+        assert_eq!(reg.engine(d).unwrap().cached_kernels(), 1);
+
+        // lose the device: the loss is reported once it is polled
+        let ctx = reg.context(d).unwrap().clone();
+        let events = ctx.subscribe();
+        ctx.device.destroy();
+        let _ = ctx.poll();
+        assert!(ctx.is_lost());
+        assert!(matches!(events.try_recv(), Ok(vknp_core::DeviceEvent::Lost { .. })));
+        assert!(matches!(run_add(&reg), Err(VknpError::DeviceLost(_))));
+
+        // fresh device, pipelines rebuilt from the cache keys
+        block_on(reg.recreate(d)).unwrap();
+        assert!(!reg.context(d).unwrap().is_lost());
+        assert_eq!(reg.engine(d).unwrap().cached_kernels(), 1);
+        assert_eq!(run_add(&reg).unwrap(), vec![2.0, 4.0]);
+    }
+
+    #[test]
+    fn run_add_beyond_one_dimension_of_workgroups() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+
+        // more than max_compute_workgroups_per_dimension × 64 elements: folded into a 2-D grid
+        let n = ctx.device.limits().max_compute_workgroups_per_dimension as usize * 64 + 1000;
+        let a_host: Vec<f32> = (0..n).map(|i| (i % 1024) as f32).collect();
+        let a = Tensor::<f32>::from_vec(&mm, &a_host, &[n], 0);
+        let b = Tensor::<f32>::from_vec(&mm, &vec![1.0; n], &[n], 0);
+        let c = Tensor::<f32>::empty(&mm, &[n], 0);
+
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+        let op = reg.check_and_prepare("add", &[(&a).into(), (&b).into()], &[(&c).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+
+        let out = c.to_vec(&mm);
+        assert!(out.iter().zip(&a_host).all(|(o, a)| *o == a + 1.0));
+    }
+
+    #[test]
+    fn workgroup_size_is_tuned_per_device_and_kernel() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let engine = ExecutionEngine::new(ctx.clone());
+        let tuner = engine.tuner();
+        assert!(tuner.preferred() <= ctx.device.limits().max_compute_invocations_per_workgroup);
+        assert!(tuner.set("add_strided", tuner.max() + 1).is_err());
+
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+        let a = Tensor::<f32>::from_vec(&mm, &(0..300).map(|i| i as f32).collect::<Vec<_>>(), &[300], 0);
+
+        // every size compiles its own pipeline and gives the same result
+        for (n, size) in [16, 32, tuner.max()].into_iter().enumerate() {
+            tuner.set("add_strided", size).unwrap();
+            let c = Tensor::<f32>::empty(&mm, &[300], 0);
+            let op = reg.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&c).into()]).unwrap();
+            engine.run_prepared(op, &mm).unwrap();
+            assert_eq!(c.to_vec(&mm), (0..300).map(|i| 2.0 * i as f32).collect::<Vec<_>>());
+            assert_eq!(engine.cached_kernels(), n + 1);
+        }
+
+        // a pin for one dtype variant takes precedence over the entry's, for that variant only
+        let (ints, floats) = ([DataType::I32; 2], [DataType::F32; 2]);
+        tuner.set_variant("add_strided", &ints, &ints[..1], 32).unwrap();
+        assert_eq!(tuner.size_for("add_strided", &ints, &ints[..1]), 32);
+        assert_eq!(tuner.size_for("add_strided", &floats, &floats[..1]), tuner.max());
+        tuner.set_variant("add_strided", &floats, &floats[..1], 16).unwrap();
+        let c = Tensor::<f32>::empty(&mm, &[300], 0);
+        let op = reg.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&c).into()]).unwrap();
+        engine.run_prepared(op, &mm).unwrap();
+        assert_eq!(c.to_vec(&mm), (0..300).map(|i| 2.0 * i as f32).collect::<Vec<_>>());
+        assert_eq!(engine.cached_kernels(), 3, "the size-16 pipeline is reused");
+
+        // `Auto` needs the override to be declared
+        let c = Tensor::<f32>::empty(&mm, &[300], 0);
+        let PreparedOp::Gpu(mut task) = reg.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&c).into()]).unwrap() else {
+            unreachable!()
+        };
+        task.pipeline_source = task.pipeline_source.replace("override WG_SIZE : u32 = 64u;", "const WG_SIZE : u32 = 64u;");
+        let err = engine.run_prepared(PreparedOp::Gpu(task), &mm).unwrap_err();
+        assert!(matches!(err, VknpError::Validation(_)), "got {err}");
+    }
+
+    #[test]
+    fn profile_every_dispatched_op() {
+        let plain = block_on(GpuContext::new()).unwrap();
+        let mut contexts = vec![(plain.clone(), ProfilerMode::CpuWallClock)];
+        if plain.adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            let timed = block_on(GpuContext::builder().required_features(wgpu::Features::TIMESTAMP_QUERY).build()).unwrap();
+            contexts.push((timed, ProfilerMode::GpuTimestamps));
+        }
+
+        let mut reg = OpRegistry::new();
+        reg.collect_inventory();
+
+        for (ctx, mode) in contexts {
+            let mm = MemoryManager::new(ctx.clone());
+            let mut engine = ExecutionEngine::new(ctx.clone());
+            let profiler = engine.enable_profiling();
+            assert_eq!(profiler.mode(), mode);
+
+            let a = Tensor::<f32>::from_vec(&mm, &[1.0; 64], &[64], 0);
+            let b = Tensor::<f32>::empty(&mm, &[64], 0);
+            let c = Tensor::<f32>::empty(&mm, &[64], 0);
+            let ops = vec![
+                reg.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&b).into()]).unwrap(),
+                reg.check_and_prepare("add", &[(&b).into(), (&b).into()], &[(&c).into()]).unwrap(),
+            ];
+            engine.run_batch(ops, &mm).unwrap().wait().unwrap();
+            assert_eq!(c.to_vec(&mm), vec![4.0; 64]);
+
+            let records = profiler.records().unwrap();
+            assert_eq!(records.len(), 2);
+            assert!(records.iter().all(|r| r.op == "add" && r.entry == "add_strided" && r.end_ns >= r.start_ns));
+
+            let summary = profiler.summary().unwrap();
+            assert!(summary.lines().nth(1).unwrap().starts_with("add"));
+            let trace = profiler.chrome_trace_json().unwrap();
+            assert!(trace.starts_with("{\"displayTimeUnit\""));
+            assert_eq!(trace.matches("\"ph\":\"X\"").count(), 2);
+
+            // nothing is recorded once disabled
+            engine.disable_profiling();
+            engine.run_prepared(reg.check_and_prepare("add", &[(&a).into(), (&a).into()], &[(&b).into()]).unwrap(), &mm).unwrap();
+            assert_eq!(profiler.records().unwrap().len(), 2);
+        }
+    }
+}
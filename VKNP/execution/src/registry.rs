@@ -0,0 +1,115 @@
+use memory::{DeviceMemory, MemoryManager};
+use vknp_core::{GpuContext, GpuContextBuilder, Result, VknpError};
+use vknp_ops::types::PreparedOp;
+
+use crate::ExecutionEngine;
+
+/// Everything needed to run ops on one device
+pub struct DeviceSlot {
+    pub ctx:    GpuContext,
+    pub memory: MemoryManager,
+    pub engine: ExecutionEngine,
+}
+
+impl DeviceSlot {
+    pub fn new(ctx: GpuContext) -> Self {
+        Self {
+            memory: MemoryManager::new(ctx.clone()),
+            engine: ExecutionEngine::new(ctx.clone()),
+            ctx,
+        }
+    }
+}
+
+/// Owns one context / memory manager / kernel cache per device.
+/// The position of a device in the registry is the `device_id` carried by tensors.
+#[derive(Default)]
+pub struct DeviceRegistry {
+    devices: Vec<DeviceSlot>,
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One device per adapter visible to `builder`, in enumeration order.
+    /// The builder's adapter filter is overridden; backends, features and limits are kept.
+    pub async fn with_all_adapters(builder: GpuContextBuilder) -> Result<Self> {
+        let n = builder.enumerate_adapters().len();
+        let mut reg = Self::new();
+        for i in 0..n {
+            let ctx = builder.clone().adapter_index(i).build().await?;
+            reg.add(ctx);
+        }
+        Ok(reg)
+    }
+
+    /// Register a context, returning its `device_id`.
+    pub fn add(&mut self, ctx: GpuContext) -> usize {
+        self.devices.push(DeviceSlot::new(ctx));
+        self.devices.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub fn get(&self, device_id: usize) -> Option<&DeviceSlot> {
+        self.devices.get(device_id)
+    }
+
+    pub fn context(&self, device_id: usize) -> Option<&GpuContext> {
+        self.get(device_id).map(|d| &d.ctx)
+    }
+
+    pub fn engine(&self, device_id: usize) -> Option<&ExecutionEngine> {
+        self.get(device_id).map(|d| &d.engine)
+    }
+
+    /// Replace a lost device with a fresh one built from the same settings.
+    /// Cached pipelines are rebuilt; tensors of the old device must be re-created.
+    pub async fn recreate(&mut self, device_id: usize) -> Result<()> {
+        let slot = self.devices.get_mut(device_id)
+            .ok_or(VknpError::UnknownDevice(device_id))?;
+        let ctx = slot.ctx.recreate().await?;
+        slot.engine.recreate(ctx.clone())?;
+        slot.memory.recreate(ctx.clone());
+        slot.ctx = ctx;
+        Ok(())
+    }
+
+    /// Run a prepared op on the device its tasks were prepared for.
+    pub fn run_prepared(&self, prepared: PreparedOp) -> Result<()> {
+        let Some(device_id) = prepared.device_id() else {
+            return Ok(()); // nothing to dispatch
+        };
+        check_single_device(&prepared, device_id)?;
+
+        let slot = self.get(device_id)
+            .ok_or(VknpError::UnknownDevice(device_id))?;
+        slot.engine.run_prepared(prepared, &slot.memory)
+    }
+}
+
+impl DeviceMemory for DeviceRegistry {
+    fn memory(&self, device_id: usize) -> Option<&MemoryManager> {
+        self.get(device_id).map(|d| &d.memory)
+    }
+}
+
+/// Composite ops must not mix devices.
+fn check_single_device(op: &PreparedOp, device_id: usize) -> Result<()> {
+    match op {
+        PreparedOp::Gpu(task) if task.device_id != device_id => Err(VknpError::DeviceMismatch {
+            expected: device_id,
+            found:    task.device_id,
+        }),
+        PreparedOp::Gpu(_) => Ok(()),
+        PreparedOp::Composite(ops) => ops.iter().try_for_each(|o| check_single_device(o, device_id)),
+    }
+}
@@ -0,0 +1,118 @@
+//! Strided copy between two views, converting the element type: materialises
+//! non-contiguous views and assigns between tensors of any layout and dtype.
+
+use bytemuck::{Pod, Zeroable};
+
+use core_types::{BufferId, DataType, ViewDescriptor, MAX_DIMS};
+
+/// One side of `MemoryManager::copy_view`: a view of a buffer holding `dtype` elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StridedView {
+    pub id:    BufferId,
+    pub view:  ViewDescriptor,
+    pub dtype: DataType,
+}
+
+/// `ViewDescriptor` laid out for a uniform block
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct ViewU {
+    offset:  u32,
+    ndim:    u32,
+    _pad0:   [u32; 2],
+    shape:   [u32; MAX_DIMS],
+    strides: [i32; MAX_DIMS],
+}
+
+impl From<&ViewDescriptor> for ViewU {
+    fn from(v: &ViewDescriptor) -> Self {
+        ViewU { offset: v.offset, ndim: v.ndim, _pad0: [0; 2], shape: v.shape, strides: v.strides }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct CopyMeta {
+    src:         ViewU,
+    dst:         ViewU,
+    total_elems: u32,
+    _pad1:       [u32; 3],
+}
+
+/// Uniform block of a copy from `src` to `dst`, which have the same shape.
+pub fn copy_params(src: &ViewDescriptor, dst: &ViewDescriptor) -> Vec<u8> {
+    let total = dst.shape[..dst.ndim as usize].iter().product();
+    let meta = CopyMeta { src: src.into(), dst: dst.into(), total_elems: total, _pad1: [0; 3] };
+    bytemuck::bytes_of(&meta).to_vec()
+}
+
+/// WGSL name of an element type
+pub(crate) fn wgsl_type(dtype: DataType) -> &'static str {
+    match dtype {
+        DataType::F32 => "f32",
+        DataType::I32 => "i32",
+        DataType::U32 => "u32",
+    }
+}
+
+/// WGSL counterpart of `ViewU`, and the offset of row-major element `i` of a view
+pub(crate) const VIEW_WGSL: &str = r#"
+const MAX_DIMS : u32 = 8u;
+
+struct View {
+  offset  : u32,
+  ndim    : u32,
+  _pad0   : vec2<u32>,
+  shape   : array<vec4<u32>, MAX_DIMS / 4u>,
+  strides : array<vec4<i32>, MAX_DIMS / 4u>,
+};
+
+fn view_offset(i: u32, v: View) -> u32 {
+  var idx = i;
+  var off = i32(v.offset);
+  var d: i32 = i32(v.ndim) - 1;
+  loop {
+    if (d < 0) { break; }
+    let du : u32 = u32(d);
+    let dim = v.shape[du / 4u][du % 4u];
+    off = off + i32(idx % dim) * v.strides[du / 4u][du % 4u];
+    idx = idx / dim;
+    d = d - 1;
+  }
+  return u32(off);
+}
+"#;
+
+/// Entry point of the `copy_wgsl` kernels
+pub const COPY_ENTRY: &str = "copy_strided";
+
+/// Kernel copying element `i` (row-major) of the `src` view to element `i` of the `dst`
+/// view, converted from `src` to `dst` type. Bindings: input 0, uniform params 1 (`var<param>`
+/// for the op registry), output 2.
+pub fn copy_wgsl(src: DataType, dst: DataType) -> String {
+    format!(r#"{VIEW_WGSL}
+override WG_SIZE : u32 = 64u;
+
+struct Meta {{
+  src         : View,
+  dst         : View,
+  total_elems : u32,
+  _pad1       : vec3<u32>,
+}};
+
+@group(0) @binding(0) var<storage, read>       S : array<{src}>;
+var<param>                                     M : Meta;
+@group(0) @binding(2) var<storage, read_write> D : array<{dst}>;
+
+@compute @workgroup_size(WG_SIZE)
+fn {COPY_ENTRY}(
+  @builtin(workgroup_id) wid: vec3<u32>,
+  @builtin(num_workgroups) nwg: vec3<u32>,
+  @builtin(local_invocation_index) lid: u32,
+) {{
+  let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
+  if (i >= M.total_elems) {{ return; }}
+  D[view_offset(i, M.dst)] = {dst}(S[view_offset(i, M.src)]);
+}}
+"#, src = wgsl_type(src), dst = wgsl_type(dst))
+}
@@ -0,0 +1,27 @@
+/// Entry point of `FILL_WGSL`
+pub(crate) const FILL_ENTRY: &str = "fill_words";
+
+/// Writes one 32-bit pattern over the first `fill.x` words of `out`
+pub(crate) const FILL_WGSL: &str = r#"
+override WG_SIZE : u32 = 64u;
+
+var<param>                                     fill : vec4<u32>;
+@group(0) @binding(1) var<storage, read_write> out  : array<u32>;
+
+@compute @workgroup_size(WG_SIZE)
+fn fill_words(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) nwg: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
+    if (i < fill.x) {
+        out[i] = fill.y;
+    }
+}
+"#;
+
+/// Uniform block of a fill of `words` words with `word`
+pub(crate) fn fill_params(words: u32, word: u32) -> Vec<u8> {
+    bytemuck::cast_slice(&[words, word, 0, 0]).to_vec()
+}
@@ -0,0 +1,170 @@
+//! Device-side generators: ranges, evenly spaced values and triangular masks written
+//! straight into a view, so index and coordinate tensors never go through the host.
+
+use bytemuck::{Pod, Zeroable};
+
+use core_types::{DataType, ViewDescriptor};
+
+use crate::copy::{wgsl_type, ViewU, VIEW_WGSL};
+
+/// Values written at each row-major index `i` of a view of `n` elements
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sequence {
+    /// `start + i * step`, exact for integer types
+    Arange { start: f64, step: f64 },
+    /// `n` values evenly spaced from `start` to `stop`, which is the last one with `endpoint`
+    Linspace { start: f64, stop: f64, endpoint: bool },
+    /// `base` raised to the values of the matching `Linspace`
+    Logspace { start: f64, stop: f64, endpoint: bool, base: f64 },
+    /// Over the last two dimensions: 1 where `col - row == k`, 0 elsewhere
+    Eye { k: i64 },
+    /// Over the last two dimensions: 1 where `col - row <= k`, 0 elsewhere
+    Tril { k: i64 },
+    /// Over the last two dimensions: 1 where `col - row >= k`, 0 elsewhere
+    Triu { k: i64 },
+}
+
+/// Bit 0 of `GenMeta::flags`: round integer results down, as NumPy casts them
+const FLAG_FLOOR: u32 = 1;
+/// Bit 1 of `GenMeta::flags`: the last element is exactly `last`
+const FLAG_ENDPOINT: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct GenMeta {
+    dst:        ViewU,
+    kind:       u32,
+    total:      u32,
+    rows:       u32,
+    cols:       u32,
+    k:          i32,
+    flags:      u32,
+    start_bits: u32,
+    step_bits:  u32,
+    start:      f32,
+    step:       f32,
+    last:       f32,
+    base:       f32,
+}
+
+/// Uniform block generating `seq` as `dtype` elements into the `dst` view.
+pub fn gen_params(seq: &Sequence, dtype: DataType, dst: &ViewDescriptor) -> Vec<u8> {
+    let shape = &dst.shape[..dst.ndim as usize];
+    let total: u32 = shape.iter().product();
+    let (rows, cols) = match shape {
+        [.., r, c] => (*r, *c),
+        [c] => (1, *c),
+        [] => (1, 1),
+    };
+    let mut meta = GenMeta {
+        dst: dst.into(),
+        total,
+        rows: rows.max(1),
+        cols: cols.max(1),
+        flags: if dtype.is_float() { 0 } else { FLAG_FLOOR },
+        ..Zeroable::zeroed()
+    };
+    let spaced = |meta: &mut GenMeta, start: f64, stop: f64, endpoint: bool| {
+        let div = if endpoint { total.saturating_sub(1) } else { total };
+        meta.start = start as f32;
+        meta.step = if div > 0 { ((stop - start) / div as f64) as f32 } else { 0.0 };
+        meta.last = stop as f32;
+        if endpoint && total > 1 {
+            meta.flags |= FLAG_ENDPOINT;
+        }
+    };
+    match *seq {
+        Sequence::Arange { start, step } if dtype.is_float() => {
+            meta.kind = 0;
+            meta.start = start as f32;
+            meta.step = step as f32;
+        }
+        Sequence::Arange { start, step } => {
+            // two's complement wrapping gives exact i32 and u32 results alike
+            meta.kind = 1;
+            meta.start_bits = start as i64 as u32;
+            meta.step_bits = step as i64 as u32;
+        }
+        Sequence::Linspace { start, stop, endpoint } => {
+            meta.kind = 2;
+            spaced(&mut meta, start, stop, endpoint);
+        }
+        Sequence::Logspace { start, stop, endpoint, base } => {
+            meta.kind = 3;
+            spaced(&mut meta, start, stop, endpoint);
+            meta.base = base as f32;
+        }
+        Sequence::Eye { k } => (meta.kind, meta.k) = (4, k as i32),
+        Sequence::Tril { k } => (meta.kind, meta.k) = (5, k as i32),
+        Sequence::Triu { k } => (meta.kind, meta.k) = (6, k as i32),
+    }
+    bytemuck::bytes_of(&meta).to_vec()
+}
+
+/// Entry point of the `gen_wgsl` kernels
+pub const GEN_ENTRY: &str = "generate";
+
+/// Kernel writing the `Sequence` encoded by `gen_params` into the `D` view of `dtype`
+/// elements. Bindings: uniform params 0 (`var<param>` for the op registry), output 1.
+pub fn gen_wgsl(dtype: DataType) -> String {
+    format!(r#"{VIEW_WGSL}
+override WG_SIZE : u32 = 64u;
+
+struct Meta {{
+  dst        : View,
+  kind       : u32,
+  total      : u32,
+  rows       : u32,
+  cols       : u32,
+  k          : i32,
+  flags      : u32,
+  start_bits : u32,
+  step_bits  : u32,
+  start      : f32,
+  step       : f32,
+  last       : f32,
+  base       : f32,
+}};
+
+var<param>                                     M : Meta;
+@group(0) @binding(1) var<storage, read_write> D : array<{t}>;
+
+fn spaced(i: u32) -> {t} {{
+  var x = M.start + f32(i) * M.step;
+  if ((M.flags & 2u) != 0u && i + 1u == M.total) {{ x = M.last; }}
+  if (M.kind == 3u) {{ x = pow(M.base, x); }}
+  if ((M.flags & 1u) != 0u) {{
+    // pow is not exact: snap near-integers before rounding down
+    let r = round(x);
+    if (abs(x - r) <= 1e-5 * max(abs(x), 1.0)) {{ x = r; }} else {{ x = floor(x); }}
+  }}
+  return {t}(x);
+}}
+
+fn mask(i: u32) -> {t} {{
+  let row = (i / M.cols) % M.rows;
+  let col = i % M.cols;
+  let d = i32(col) - i32(row);
+  let on = (M.kind == 4u && d == M.k) || (M.kind == 5u && d <= M.k) || (M.kind == 6u && d >= M.k);
+  return select({t}(0), {t}(1), on);
+}}
+
+@compute @workgroup_size(WG_SIZE)
+fn {GEN_ENTRY}(
+  @builtin(workgroup_id) wid: vec3<u32>,
+  @builtin(num_workgroups) nwg: vec3<u32>,
+  @builtin(local_invocation_index) lid: u32,
+) {{
+  let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
+  if (i >= M.total) {{ return; }}
+  var v : {t};
+  switch M.kind {{
+    case 0u: {{ v = {t}(M.start + f32(i) * M.step); }}
+    case 1u: {{ v = bitcast<{t}>(M.start_bits + i * M.step_bits); }}
+    case 2u, 3u: {{ v = spaced(i); }}
+    default: {{ v = mask(i); }}
+  }}
+  D[view_offset(i, M.dst)] = v;
+}}
+"#, t = wgsl_type(dtype))
+}
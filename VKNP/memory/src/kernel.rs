@@ -0,0 +1,51 @@
+//! Compute kernels of the memory manager itself (fill, copy, generate, random): each
+//! binds its inputs, one uniform param block and one output, and runs one invocation
+//! per element.
+
+use std::sync::Arc;
+
+use vknp_core::{CommandBatch, GpuContext, Result, WGSL_PRELUDE, WORKGROUP_SIZE_OVERRIDE};
+use vknp_core::types::{AbstractBindGroupLayout, AbstractComputePipeline, BufferHandle, KernelArgs, ParamArg, ParamBinding};
+
+/// A memory kernel compiled for one set of dtypes, at the workgroup size the device's tuner picked
+pub(crate) struct MemoryKernel {
+    pipeline: Arc<AbstractComputePipeline>,
+    layout:   Arc<AbstractBindGroupLayout>,
+    entry:    String,
+    /// Value of its `WG_SIZE` override
+    pub(crate) workgroup_size: u32,
+}
+
+impl MemoryKernel {
+    /// Compile `entry` of `wgsl`, whose `var<param>` is bound as a uniform block after
+    /// `n_in` inputs.
+    pub(crate) fn new(ctx: &GpuContext, wgsl: &str, entry: &str, n_in: usize, label: &str, workgroup_size: u32) -> Result<Self> {
+        let params = [ParamBinding::Uniform];
+        let layout = ctx.create_storage_layout(n_in, &params, 1);
+        let wgsl = GpuContext::expand_param_declarations(wgsl, n_in, &params)?;
+        let wgsl = format!("{WGSL_PRELUDE}{wgsl}");
+        let constants = [(WORKGROUP_SIZE_OVERRIDE, workgroup_size as f64)];
+        let pipeline = ctx.create_compute_pipeline(&wgsl, entry, &layout, 0, &constants, label)?;
+        Ok(Self { pipeline, layout, entry: entry.to_string(), workgroup_size })
+    }
+
+    /// Record a dispatch over `total` elements, named `op` for the profiler.
+    pub(crate) fn record(
+        &self,
+        batch:  &mut CommandBatch,
+        inputs: &[BufferHandle],
+        params: BufferHandle,
+        dst:    BufferHandle,
+        total:  u32,
+        op:     &str,
+    ) -> Result<()> {
+        let args = KernelArgs {
+            inputs,
+            params:  &[ParamArg::Buffer(params)],
+            outputs: &[dst],
+            label:   None,
+        };
+        batch.set_op_label(op, &self.entry);
+        batch.dispatch_1d(&self.pipeline, &self.layout, &args, total, self.workgroup_size)
+    }
+}
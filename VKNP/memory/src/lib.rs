@@ -1,87 +1,703 @@
-mod pool;
+pub mod copy;
+mod fill;
+pub mod generate;
+mod kernel;
+pub mod pool;
+pub mod philox;
+pub mod staging;
 
-use anyhow::Result;
 use bytemuck::{cast_slice, Pod};
+use std::collections::HashMap;
+use std::fmt;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-use core_types::BufferId;
-use pool::BufferPool;
-use vknp_core::GpuContext;
+use core_types::{BufferId, DataType, Element, ViewDescriptor};
+use copy::{wgsl_type, StridedView, COPY_ENTRY};
+use fill::{FILL_ENTRY, FILL_WGSL};
+use generate::{Sequence, GEN_ENTRY};
+use kernel::MemoryKernel;
+use philox::{Distribution, RandomState, RAND_ENTRY};
+use pool::{BufferPool, LiveAllocation, PoolConfig, PoolStats};
+use staging::StagingBelt;
+use vknp_core::{CommandBatch, GpuContext, Result, VknpError};
 use vknp_core::types::{BufferKind, BufferHandle, BufferToken};
 
-/// Manages three buffer pools on **one** GPU device:
-/// - `main_pool`         : STORAGE buffers that hold tensor data
+/// Lookup of the `MemoryManager` owning a given device index.
+/// Implemented by multi-device registries so tensors can move between devices.
+pub trait DeviceMemory {
+    fn memory(&self, device_id: usize) -> Option<&MemoryManager>;
+}
+
+/// Uploads up to this size go through the staging belt
+const BELT_CHUNK_SIZE: u64 = 1 << 20;
+
+/// `budget` value meaning "no budget"
+const UNLIMITED: u64 = u64::MAX;
+
+/// Totals of one `MemoryManager`, in bytes of device memory unless noted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Per pool: main, uniform, upload staging, download staging, scratch
+    pub pools: [PoolStats; 5],
+    /// Held by live allocations
+    pub live_bytes: u64,
+    /// Held on the device: every pool's `reserved_bytes` plus the staging belt
+    pub reserved_bytes: u64,
+    /// Peak of `reserved_bytes`
+    pub peak_bytes: u64,
+    /// Allocations handed out / released, every pool
+    pub allocs: u64,
+    pub frees: u64,
+    /// Host bytes held by spilled allocations
+    pub spilled_bytes: u64,
+    /// Staging traffic: bytes copied host → device and device → host
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    /// Cap on `reserved_bytes`, if any
+    pub budget: Option<u64>,
+}
+
+/// A buffer still allocated, as listed by `MemoryManager::report_leaks`
+#[derive(Debug, Clone)]
+pub struct Leak {
+    /// Owning pool: "main", "uniform", "upload", "download" or "scratch"
+    pub pool:       &'static str,
+    pub allocation: LiveAllocation,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.pool, self.allocation)
+    }
+}
+
+/// Manages five buffer pools on **one** GPU device:
+/// - `main_pool`         : STORAGE buffers that hold tensor data (and storage params)
+/// - `uniform_pool`      : UNIFORM + COPY_DST    (per-dispatch param blocks)
 /// - `staging_upload`    : MAP_WRITE + COPY_SRC  (CPU → GPU)
 /// - `staging_download`  : MAP_READ  + COPY_DST  (GPU → CPU)
+/// - `scratch_pool`      : STORAGE buffers never sub-allocated (outputs moved out of a slab block)
+///
+/// plus a pre-mapped `upload_belt` for small uploads, flushed with the next submission.
+///
+/// An optional budget caps the device memory the five pools and the belt hold together.
+/// Under pressure, the least recently used unpinned tensors are spilled to host memory:
+/// their ids stay valid and they are paged back in when next accessed (`resident`).
 pub struct MemoryManager {
     ctx:              GpuContext,
     main_pool:        BufferPool,
+    uniform_pool:     BufferPool,
     staging_upload:   BufferPool,
     staging_download: BufferPool,
+    scratch_pool:     BufferPool,
+    upload_belt:      StagingBelt,
+    budget:           AtomicU64,
+    peak:             AtomicU64,
+    uploaded:         AtomicU64,
+    downloaded:       AtomicU64,
+    assert_no_leaks:  AtomicBool,
+    /// Fill / copy / generate / random kernels, by pipeline label
+    kernels:          Mutex<HashMap<String, Arc<MemoryKernel>>>,
 }
 
 impl MemoryManager {
     pub fn new(ctx: GpuContext) -> Self {
-        let main_pool        = BufferPool::new(ctx.clone(), BufferKind::Main);
+        Self::with_config(ctx, PoolConfig::default())
+    }
+
+    /// Custom size classes for the bindable pools (main and uniform), e.g. `PoolConfig::slab()`
+    /// to sub-allocate small tensors out of shared buffers. Staging pools keep the defaults.
+    pub fn with_config(ctx: GpuContext, config: PoolConfig) -> Self {
+        let main_pool        = BufferPool::with_config(ctx.clone(), BufferKind::Main, config);
+        let uniform_pool     = BufferPool::with_config(ctx.clone(), BufferKind::Uniform, config);
         let staging_upload   = BufferPool::new(ctx.clone(), BufferKind::Upload);
         let staging_download = BufferPool::new(ctx.clone(), BufferKind::Download);
-        Self { ctx, main_pool, staging_upload, staging_download }
+        let scratch_pool     = BufferPool::new(ctx.clone(), BufferKind::Main);
+        let upload_belt      = StagingBelt::new(ctx.clone(), BELT_CHUNK_SIZE);
+        Self {
+            ctx, main_pool, uniform_pool, staging_upload, staging_download, scratch_pool, upload_belt,
+            budget:     AtomicU64::new(UNLIMITED),
+            peak:       AtomicU64::new(0),
+            uploaded:   AtomicU64::new(0),
+            downloaded: AtomicU64::new(0),
+            assert_no_leaks: AtomicBool::new(false),
+            kernels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Move to a new device (typically after a loss). Every buffer of the old device is
+    /// dropped: ids handed out before are no longer valid and must be re-uploaded.
+    /// The budget is kept.
+    pub fn recreate(&mut self, ctx: GpuContext) {
+        let budget = self.budget();
+        let assert_no_leaks = self.assert_no_leaks.swap(false, Ordering::Relaxed);
+        *self = Self::with_config(ctx, self.main_pool.config());
+        self.set_budget(budget);
+        self.assert_no_leaks_on_drop(assert_no_leaks);
     }
 
-    /// Raw allocation
+    /// Cap the device memory held by this manager (`None`: no cap). An allocation that
+    /// would exceed it first reclaims recycled buffers, then spills cold tensors to host
+    /// memory (tensor allocations only), and fails with `OutOfMemory` if that is not enough.
+    pub fn set_budget(&self, bytes: Option<u64>) {
+        self.budget.store(bytes.unwrap_or(UNLIMITED), Ordering::Relaxed);
+    }
+
+    pub fn budget(&self) -> Option<u64> {
+        Some(self.budget.load(Ordering::Relaxed)).filter(|&b| b != UNLIMITED)
+    }
+
+    pub fn context(&self) -> &GpuContext {
+        &self.ctx
+    }
+
+    /// Every buffer still allocated, pool by pool. Allocation sites are recorded
+    /// with the `track-allocations` feature.
+    pub fn report_leaks(&self) -> Vec<Leak> {
+        let names = ["main", "uniform", "upload", "download", "scratch"];
+        names.into_iter().zip(self.pools())
+            .flat_map(|(pool, p)| p.live().into_iter().map(move |allocation| Leak { pool, allocation }))
+            .collect()
+    }
+
+    /// Panic when this manager is dropped while buffers are still allocated (for tests).
+    pub fn assert_no_leaks_on_drop(&self, enabled: bool) {
+        self.assert_no_leaks.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Raw allocation. The buffer goes back to the pool when the last clone of the token
+    /// is dropped (or on `release`), once in-flight GPU work is done with it.
     pub fn allocate_raw(&self, size_bytes: usize) -> Result<(BufferId, BufferToken)> {
-        self.main_pool.alloc_buffer(size_bytes)
+        self.ctx.ensure_alive()?;
+        self.alloc_in(&self.main_pool, size_bytes, None)
+    }
+
+    /// `allocate_raw` with a name, shown in leak reports and in the labels of the
+    /// dispatches binding the buffer.
+    pub fn allocate_labeled(&self, size_bytes: usize, label: &str) -> Result<(BufferId, BufferToken)> {
+        self.ctx.ensure_alive()?;
+        self.alloc_in(&self.main_pool, size_bytes, Some(label))
+    }
+
+    /// `allocate_raw`, zeroed on the device (no upload). Later work on the queue sees the zeros.
+    pub fn allocate_zeroed(&self, size_bytes: usize) -> Result<(BufferId, BufferToken)> {
+        let (id, token) = self.allocate_raw(size_bytes)?;
+        let dst = self.resident(id)?;
+        let mut batch = self.ctx.begin_batch("vknp-fill");
+        // a recycled buffer may still have uploads queued: they must not land after the clear
+        self.flush_uploads_into(&mut batch);
+        batch.clear_buffer(dst.as_raw());
+        batch.submit()?;
+        Ok((id, token))
+    }
+
+    /// `allocate_raw`, with every element set to `value` by a fill kernel (no upload).
+    pub fn allocate_filled<T: Element>(&self, size_bytes: usize, value: T) -> Result<(BufferId, BufferToken)> {
+        let word: [u8; 4] = bytemuck::bytes_of(&value).try_into().map_err(|_| VknpError::Validation(format!(
+            "fills take 32-bit elements, {:?} is {} bytes", T::DTYPE, T::DTYPE.size_in_bytes()
+        )))?;
+        let word = u32::from_ne_bytes(word);
+        if word == 0 {
+            return self.allocate_zeroed(size_bytes);
+        }
+        let (id, token) = self.allocate_raw(size_bytes)?;
+        let words = size_bytes.div_ceil(4) as u32;
+        if words == 0 {
+            return Ok((id, token));
+        }
+        let dst = self.resident(id)?;
+        let kernel = self.fill_kernel()?;
+        let (params, _params_token) = self.alloc_param(&fill::fill_params(words, word), BufferKind::Uniform)?;
+        let mut batch = self.ctx.begin_batch("vknp-fill");
+        self.flush_uploads_into(&mut batch);
+        kernel.record(&mut batch, &[], params, dst, words, "fill")?;
+        batch.submit()?;
+        Ok((id, token))
+    }
+
+    fn fill_kernel(&self) -> Result<Arc<MemoryKernel>> {
+        self.kernel("fill", FILL_ENTRY, &[], DataType::U32, || FILL_WGSL.to_string())
+    }
+
+    /// Kernel `entry` of the `wgsl` reading `t_in` and writing `t_out`, compiled on first
+    /// use and again when the tuner's size for it changes.
+    fn kernel(
+        &self,
+        op:    &str,
+        entry: &str,
+        t_in:  &[DataType],
+        t_out: DataType,
+        wgsl:  impl FnOnce() -> String,
+    ) -> Result<Arc<MemoryKernel>> {
+        let size = self.ctx.tuner().size_for(entry, t_in, &[t_out]);
+        let inputs = t_in.iter().map(|&t| wgsl_type(t)).collect::<Vec<_>>().join(",");
+        let label = format!("{op}:{entry}({inputs})->({})", wgsl_type(t_out));
+        if let Some(kernel) = self.kernels.lock().get(&label).filter(|k| k.workgroup_size == size) {
+            return Ok(kernel.clone());
+        }
+        let kernel = Arc::new(MemoryKernel::new(&self.ctx, &wgsl(), entry, t_in.len(), &label, size)?);
+        self.kernels.lock().insert(label, kernel.clone());
+        Ok(kernel)
+    }
+
+    /// Copy every element of the `src` view into the same position of the `dst` view
+    /// (same shape), converting it to the `dst` dtype. Runs on the device; later work
+    /// on the queue sees the result.
+    pub fn copy_view(&self, src: &StridedView, dst: &StridedView) -> Result<()> {
+        let shape = |v: &ViewDescriptor| v.shape[..v.ndim as usize].to_vec();
+        if shape(&src.view) != shape(&dst.view) {
+            return Err(VknpError::Shape(format!(
+                "cannot copy a {:?} view into a {:?} one", shape(&src.view), shape(&dst.view)
+            )));
+        }
+        let total: u32 = shape(&dst.view).iter().product();
+        if total == 0 {
+            return Ok(());
+        }
+        let mut src_buf = self.resident(src.id)?;
+        let dst_buf = self.resident(dst.id)?;
+        let kernel = self.copy_kernel(src.dtype, dst.dtype)?;
+        let (params, _params_token) = self.alloc_param(&copy::copy_params(&src.view, &dst.view), BufferKind::Uniform)?;
+
+        let mut batch = self.ctx.begin_batch("vknp-copy");
+        self.flush_uploads_into(&mut batch);
+        // a dispatch cannot read the buffer it writes: read a snapshot of the source instead
+        let mut _scratch_token = None;
+        if src_buf.as_raw().same_buffer(dst_buf.as_raw()) {
+            let (scratch, token) = self.allocate_scratch(src_buf.as_raw().size() as usize)?;
+            batch.copy_buffer_to_buffer(src_buf.as_raw(), scratch.as_raw(), src_buf.as_raw().size());
+            src_buf = scratch;
+            _scratch_token = Some(token);
+        }
+        kernel.record(&mut batch, &[src_buf], params, dst_buf, total, "copy")?;
+        batch.submit()?;
+        Ok(())
+    }
+
+    fn copy_kernel(&self, src: DataType, dst: DataType) -> Result<Arc<MemoryKernel>> {
+        self.kernel("copy", COPY_ENTRY, &[src], dst, || copy::copy_wgsl(src, dst))
+    }
+
+    /// Write `seq` into the `dst` view, on the device.
+    pub fn generate(&self, dst: &StridedView, seq: &Sequence) -> Result<()> {
+        let total: u32 = dst.view.shape[..dst.view.ndim as usize].iter().product();
+        if total == 0 {
+            return Ok(());
+        }
+        let dst_buf = self.resident(dst.id)?;
+        let kernel = self.gen_kernel(dst.dtype)?;
+        let (params, _params_token) = self.alloc_param(&generate::gen_params(seq, dst.dtype, &dst.view), BufferKind::Uniform)?;
+
+        let mut batch = self.ctx.begin_batch("vknp-generate");
+        self.flush_uploads_into(&mut batch);
+        kernel.record(&mut batch, &[], params, dst_buf, total, "generate")?;
+        batch.submit()?;
+        Ok(())
+    }
+
+    fn gen_kernel(&self, dtype: DataType) -> Result<Arc<MemoryKernel>> {
+        self.kernel("generate", GEN_ENTRY, &[], dtype, || generate::gen_wgsl(dtype))
+    }
+
+    /// Draw `dist` from `state` into the `dst` view, on the device. A `Choice` picks the
+    /// elements of `src` (whose first dimension must hold `n` elements) when given.
+    pub fn random(&self, dst: &StridedView, dist: &Distribution, state: &RandomState, src: Option<&StridedView>) -> Result<()> {
+        let total: u32 = dst.view.shape[..dst.view.ndim as usize].iter().product();
+        if total == 0 {
+            return Ok(());
+        }
+        let dst_buf = self.resident(dst.id)?;
+        let src_buf = src.map(|s| self.resident(s.id)).transpose()?;
+        let kernel = self.rand_kernel(dst.dtype, src.map(|s| s.dtype))?;
+        let bytes = philox::rand_params(dist, state, &dst.view, src.map(|s| &s.view));
+        let (params, _params_token) = self.alloc_param(&bytes, BufferKind::Uniform)?;
+
+        let mut batch = self.ctx.begin_batch("vknp-random");
+        self.flush_uploads_into(&mut batch);
+        let inputs: Vec<BufferHandle> = src_buf.into_iter().collect();
+        kernel.record(&mut batch, &inputs, params, dst_buf, total, "random")?;
+        batch.submit()?;
+        Ok(())
+    }
+
+    fn rand_kernel(&self, dtype: DataType, src: Option<DataType>) -> Result<Arc<MemoryKernel>> {
+        let t_in: Vec<DataType> = src.into_iter().collect();
+        self.kernel("random", RAND_ENTRY, &t_in, dtype, || philox::rand_wgsl(dtype, src))
+    }
+
+    /// Name of a tensor buffer, if it was given one
+    pub fn label_of(&self, id: BufferId) -> Option<String> {
+        self.main_pool.label(id)
+    }
+
+    /// Every allocation goes through here: budget check, then peak accounting.
+    fn alloc_in(&self, pool: &BufferPool, size_bytes: usize, label: Option<&str>) -> Result<(BufferId, BufferToken)> {
+        let evict = std::ptr::eq(pool, &self.main_pool);
+        self.make_room(pool.growth_of(size_bytes as u64), size_bytes, evict)?;
+        let allocation = pool.alloc_buffer_labeled(size_bytes, label)?;
+        self.note_peak();
+        Ok(allocation)
+    }
+
+    fn note_peak(&self) {
+        self.peak.fetch_max(self.reserved_bytes(), Ordering::Relaxed);
+    }
+
+    /// Make `bytes` more bytes fit in the budget: reclaim recycled buffers, then
+    /// (if `evict`) spill cold tensors.
+    fn make_room(&self, bytes: u64, requested: usize, evict: bool) -> Result<()> {
+        let budget = self.budget.load(Ordering::Relaxed);
+        let over = || self.reserved_bytes().saturating_add(bytes).saturating_sub(budget);
+        if over() == 0 {
+            return Ok(());
+        }
+        self.reclaim()?;
+        if over() > 0 && evict {
+            self.spill_lru(over())?;
+            self.reclaim()?;
+        }
+        if over() > 0 {
+            return Err(VknpError::OutOfMemory { requested: requested as u64 });
+        }
+        Ok(())
     }
 
-    /// Raw deallocation
+    /// Spill least recently used tensors until `bytes` of device memory are released.
+    fn spill_lru(&self, bytes: u64) -> Result<()> {
+        let mut freed = 0;
+        for id in self.main_pool.eviction_order() {
+            if freed >= bytes {
+                break;
+            }
+            let before = self.main_pool.stats().live_bytes;
+            if self.spill_to_host(id)? {
+                freed += before - self.main_pool.stats().live_bytes;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a tensor to host memory and release its device buffer. `false` if it is
+    /// pinned, already spilled, or still referenced by a batch not yet submitted.
+    pub fn evict(&self, id: BufferId) -> Result<bool> {
+        if !self.main_pool.is_evictable(id) {
+            return Ok(false);
+        }
+        self.spill_to_host(id)
+    }
+
+    /// `evict` without the up-front check, for ids taken from `eviction_order`
+    /// (`BufferPool::spill` still refuses one that changed meanwhile).
+    fn spill_to_host(&self, id: BufferId) -> Result<bool> {
+        let size = self.main_pool.get_buffer_size(id).ok_or(VknpError::MissingBuffer(id))?;
+        let host = self.read_bytes(id, 0, size)?;
+        Ok(self.main_pool.spill(id, host))
+    }
+
+    /// Device buffer of a tensor, paged back in (and re-uploaded) first if it was spilled.
+    pub fn resident(&self, id: BufferId) -> Result<BufferHandle> {
+        if let Some(buffer) = self.main_pool.get(id) {
+            self.main_pool.touch(id);
+            return Ok(buffer);
+        }
+        let size = self.main_pool.get_buffer_size(id).ok_or(VknpError::MissingBuffer(id))?;
+        self.make_room(self.main_pool.growth_of(size as u64), size, true)?;
+        match self.main_pool.restore(id)? {
+            Some((buffer, host)) => {
+                self.note_peak();
+                self.write_range(id, 0, &host)?;
+                Ok(buffer)
+            }
+            // restored by someone else meanwhile, or released
+            None => self.main_pool.get(id).ok_or(VknpError::MissingBuffer(id)),
+        }
+    }
+
+    /// Keep a tensor resident (paging it in if needed) until `unpin`.
+    pub fn pin(&self, id: BufferId) -> Result<()> {
+        self.resident(id)?;
+        self.main_pool.set_pinned(id, true);
+        Ok(())
+    }
+
+    pub fn unpin(&self, id: BufferId) {
+        self.main_pool.set_pinned(id, false);
+    }
+
+    /// Whether a tensor is currently spilled to host memory
+    pub fn is_spilled(&self, id: BufferId) -> bool {
+        self.main_pool.is_spilled(id)
+    }
+
+    /// Release unreferenced buffers, wait for in-flight work to retire pending
+    /// releases, then free every recycled buffer.
+    fn reclaim(&self) -> Result<()> {
+        self.clear_unused();
+        self.ctx.device_poll(wgpu::PollType::Wait)?;
+        self.trim();
+        Ok(())
+    }
+
+    fn reserved_bytes(&self) -> u64 {
+        let pools: u64 = self.pools().map(|p| p.stats().reserved_bytes).sum();
+        pools + self.upload_belt.chunks() as u64 * self.upload_belt.chunk_size()
+    }
+
+    /// Totals across the pools, peak usage and staging traffic.
+    pub fn stats(&self) -> MemoryStats {
+        let pools = self.pool_stats();
+        MemoryStats {
+            pools,
+            live_bytes:       pools.iter().map(|p| p.live_bytes).sum(),
+            reserved_bytes:   self.reserved_bytes(),
+            peak_bytes:       self.peak.load(Ordering::Relaxed).max(self.reserved_bytes()),
+            allocs:           pools.iter().map(|p| p.allocs).sum(),
+            frees:            pools.iter().map(|p| p.frees).sum(),
+            spilled_bytes:    pools.iter().map(|p| p.spilled_bytes).sum(),
+            uploaded_bytes:   self.uploaded.load(Ordering::Relaxed),
+            downloaded_bytes: self.downloaded.load(Ordering::Relaxed),
+            budget:           self.budget(),
+        }
+    }
+
+    /// Raw deallocation, without waiting for the tokens to be dropped
     pub fn release(&self, id: BufferId) {
         self.main_pool.release_buffer(id);
     }
 
-    /// Raw upload: CPU → GPU.
+    /// Parameter block for one dispatch, written through the queue. `kind` is `Uniform`
+    /// or `Main` (read-only storage). Drop the token once the dispatch is recorded:
+    /// the batch keeps the buffer out of recycling until it is submitted.
+    pub fn alloc_param(&self, bytes: &[u8], kind: BufferKind) -> Result<(BufferHandle, BufferToken)> {
+        let pool = self.param_pool(kind);
+        let (id, token) = self.alloc_in(pool, bytes.len(), None)?;
+        let buf = pool.get(id).ok_or(VknpError::MissingBuffer(id))?;
+        self.ctx.write_buffer_queued(buf.as_raw(), bytes)?;
+        Ok((buf, token))
+    }
+
+    /// Storage buffer of its own (never a slab sub-range) for intermediate results.
+    /// Like params, it can be dropped once the dispatches using it are recorded.
+    pub fn allocate_scratch(&self, size_bytes: usize) -> Result<(BufferHandle, BufferToken)> {
+        let (id, token) = self.alloc_in(&self.scratch_pool, size_bytes, None)?;
+        let buf = self.scratch_pool.get(id).ok_or(VknpError::MissingBuffer(id))?;
+        Ok((buf, token))
+    }
+
+    fn param_pool(&self, kind: BufferKind) -> &BufferPool {
+        match kind {
+            BufferKind::Uniform => &self.uniform_pool,
+            _ => &self.main_pool,
+        }
+    }
+
+    /// Recycle unreferenced buffers of every pool, then apply their trim policy.
+    pub fn clear_unused(&self) {
+        self.pools().for_each(BufferPool::clear_unused);
+    }
+
+    /// Free recycled buffers of every pool; returns the bytes freed.
+    pub fn trim(&self) -> u64 {
+        self.pools().map(|p| p.trim(0)).sum()
+    }
+
+    /// Device memory held by each pool: main, uniform, upload staging, download staging, scratch.
+    pub fn pool_stats(&self) -> [PoolStats; 5] {
+        [self.main_pool.stats(), self.uniform_pool.stats(), self.staging_upload.stats(),
+         self.staging_download.stats(), self.scratch_pool.stats()]
+    }
+
+    fn pools(&self) -> impl Iterator<Item = &BufferPool> {
+        [&self.main_pool, &self.uniform_pool, &self.staging_upload, &self.staging_download, &self.scratch_pool].into_iter()
+    }
+
+    /// The ring small uploads are written to
+    pub fn upload_belt(&self) -> &StagingBelt {
+        &self.upload_belt
+    }
+
+    /// Record the queued uploads into `batch`: anything it records afterwards sees them.
+    pub fn flush_uploads_into(&self, batch: &mut CommandBatch) {
+        self.upload_belt.flush_into(batch);
+    }
+
+    /// Submit the queued uploads now.
+    pub fn flush_uploads(&self) -> Result<()> {
+        self.upload_belt.flush()
+    }
+
+    /// Raw upload: CPU → GPU. Small uploads are queued on the staging belt and land
+    /// with the next submission (dispatches through the engine, downloads, `flush_uploads`).
     pub fn write_to_buffer<T: Pod>(&self, dest_id: BufferId, data: &[T]) -> Result<()> {
-        let bytes = cast_slice(data);
+        self.write_range(dest_id, 0, data)
+    }
 
-        // 1) staging_upload: write via GpuContext
-        let (sid, _) = self.staging_upload.alloc_buffer(bytes.len())?;
-        let staging_buf = self.staging_upload.get(sid).expect("staging buf");
-        self.ctx.write_buffer(staging_buf.as_raw(), bytes);
+    /// Upload `data` at `byte_offset` into a buffer, leaving the other bytes untouched.
+    /// Offsets and lengths need not be 4-byte aligned: the partial words at either
+    /// end are read back and merged first.
+    pub fn write_range<T: Pod>(&self, dest_id: BufferId, byte_offset: usize, data: &[T]) -> Result<()> {
+        self.ctx.ensure_alive()?;
+        let bytes: &[u8] = cast_slice(data);
+        self.check_range(dest_id, byte_offset, bytes.len())?;
+        let dst = self.resident(dest_id)?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
 
-        // 2) copy staging_upload → main_pool[dest_id]
-        let dst = self.main_pool.get(dest_id).expect("dest buf");
-        self.ctx.copy_buffer_to_buffer(staging_buf.as_raw(), dst.as_raw(), bytes.len() as u64);
+        // widen to whole words, keeping the current content of the bytes around the range
+        let (lo, hi) = word_range(byte_offset, bytes.len());
+        let mut window = bytes.to_vec();
+        if lo < byte_offset || hi > byte_offset + bytes.len() {
+            let (head, tail) = (byte_offset - lo, hi - byte_offset - bytes.len());
+            let first = if head > 0 { Some(self.read_bytes(dest_id, lo, WORD)?) } else { None };
+            let last = match &first {
+                // both ends in the same word
+                Some(word) if hi - lo == WORD => Some(word.clone()),
+                _ if tail > 0 => Some(self.read_bytes(dest_id, hi - WORD, WORD)?),
+                _ => None,
+            };
+            let mut merged = Vec::with_capacity(hi - lo);
+            merged.extend_from_slice(first.as_ref().map_or(&[][..], |w| &w[..head]));
+            merged.extend_from_slice(bytes);
+            merged.extend_from_slice(last.as_ref().map_or(&[][..], |w| &w[WORD - tail..]));
+            window = merged;
+        }
 
-        // 3) cleanup staging
-        self.staging_upload.release_buffer(sid);
+        self.uploaded.fetch_add(window.len() as u64, Ordering::Relaxed);
+        if self.upload_belt.write(&dst, lo as u64, &window)? {
+            return Ok(());
+        }
 
-        Ok(())
+        // oversized: dedicated staging, submitted after the queued uploads so writes land in order
+        self.flush_uploads()?;
+
+        // 1) staging_upload: write via GpuContext
+        let (sid, _staging) = self.alloc_in(&self.staging_upload, window.len(), None)?;
+        let staging_buf = self.staging_upload.get(sid).ok_or(VknpError::MissingBuffer(sid))?;
+        self.ctx.write_buffer(staging_buf.as_raw(), &window)?;
+
+        // 2) copy staging_upload → main_pool[dest_id]; staging is released with its token
+        let size = (hi - lo) as u64;
+        self.ctx.copy_buffer_to_buffer(staging_buf.as_raw(), &dst.as_raw().sub_range(lo as u64, size)?, size)
     }
 
     /// Raw download: GPU → CPU into a `Vec<T>`
     pub fn download_raw<T: Pod>(&self, id: BufferId) -> Result<Vec<T>> {
+        let size = self.main_pool.get_buffer_size(id).ok_or(VknpError::MissingBuffer(id))?;
+        Ok(bytemuck::pod_collect_to_vec(&self.read_bytes(id, 0, size)?))
+    }
+
+    /// Download `len` elements of `T` starting `byte_offset` bytes into a buffer;
+    /// only the words spanned by the range are copied.
+    pub fn read_range<T: Pod>(&self, id: BufferId, byte_offset: usize, len: usize) -> Result<Vec<T>> {
+        self.check_range(id, byte_offset, len * std::mem::size_of::<T>())?;
+        let bytes = self.read_bytes(id, byte_offset, len * std::mem::size_of::<T>())?;
+        Ok(bytemuck::pod_collect_to_vec(&bytes))
+    }
+
+    /// Non-blocking download: GPU → CPU, resolved once the staging buffer is mapped.
+    /// The device must be polled meanwhile (`GpuContext::poll` or a `DevicePoller`).
+    pub async fn download_async<T: Pod>(&self, id: BufferId) -> Result<Vec<T>> {
+        let size = self.main_pool.get_buffer_size(id).ok_or(VknpError::MissingBuffer(id))?;
+        self.read_range_async(id, 0, size / std::mem::size_of::<T>()).await
+    }
+
+    /// Non-blocking `read_range`.
+    pub async fn read_range_async<T: Pod>(&self, id: BufferId, byte_offset: usize, len: usize) -> Result<Vec<T>> {
+        let len = len * std::mem::size_of::<T>();
+        self.check_range(id, byte_offset, len)?;
         // 1) Copy main → staging_download
-        let src_buf = self.main_pool.get(id).expect("src buf");
-        let size = src_buf.as_raw().size();
-        let (sid, _) = self.staging_download.alloc_buffer(size as usize)?;
-        let dst_buf = self.staging_download.get(sid).expect("dst buf");
-        self.ctx.copy_buffer_to_buffer(src_buf.as_raw(), dst_buf.as_raw(), size);
+        let (staging, dst_buf, skip) = self.copy_to_staging(id, byte_offset, len)?;
+
+        // 2) wait for the mapping without blocking the thread
+        let bytes = self.ctx.read_buffer_async(dst_buf).await;
+
+        // 3) cleanup staging
+        drop(staging);
+
+        // 4) cast to Vec<T>, dropping the word padding around the range
+        Ok(bytemuck::pod_collect_to_vec(&bytes?[skip..skip + len]))
+    }
+
+    /// Blocking read of `len` bytes at `byte_offset`, possibly reaching into the
+    /// padding of the last word (not checked against the requested size).
+    fn read_bytes(&self, id: BufferId, byte_offset: usize, len: usize) -> Result<Vec<u8>> {
+        // 1) Copy main → staging_download
+        let (staging, dst_buf, skip) = self.copy_to_staging(id, byte_offset, len)?;
 
         // 2) read entire staging buffer via GpuContext
         let bytes = self.ctx.read_buffer(dst_buf.as_raw());
 
         // 3) cleanup staging
-        self.staging_download.release_buffer(sid);
+        drop(staging);
+
+        // 4) drop the word padding around the range and the padding of the size class
+        Ok(bytes?[skip..skip + len].to_vec())
+    }
 
-        // 4) cast to Vec<T>
-        let vec = cast_slice::<u8, T>(&bytes).to_vec();
-        Ok(vec)
+    /// Copy the words spanning `len` bytes at `byte_offset` of a main-pool buffer into a
+    /// download staging buffer, in the same submission as the queued uploads.
+    /// Returns the staging token / handle and where the range starts in it.
+    fn copy_to_staging(&self, id: BufferId, byte_offset: usize, len: usize) -> Result<(BufferToken, BufferHandle, usize)> {
+        self.ctx.ensure_alive()?;
+        let src_buf = self.resident(id)?;
+        let (lo, hi) = word_range(byte_offset, len);
+        // transient, and needed to spill: not held against the budget
+        let (sid, staging) = self.staging_download.alloc_buffer((hi - lo).max(WORD))?;
+        self.note_peak();
+        let dst_buf = self.staging_download.get(sid).ok_or(VknpError::MissingBuffer(sid))?;
+        let mut batch = self.ctx.begin_batch("vknp-download");
+        self.flush_uploads_into(&mut batch);
+        if hi > lo {
+            let size = (hi - lo) as u64;
+            self.downloaded.fetch_add(size, Ordering::Relaxed);
+            batch.copy_buffer_to_buffer(&src_buf.as_raw().sub_range(lo as u64, size)?, dst_buf.as_raw(), size);
+        }
+        batch.submit()?;
+        Ok((staging, dst_buf, byte_offset - lo))
     }
 
-    /// Get a clonable handle to a buffer in the main pool.
+    /// Public ranges must lie within the requested size of the buffer.
+    fn check_range(&self, id: BufferId, byte_offset: usize, len: usize) -> Result<()> {
+        let size = self.main_pool.get_buffer_size(id).ok_or(VknpError::MissingBuffer(id))?;
+        if byte_offset + len > size {
+            return Err(VknpError::Validation(format!(
+                "byte range {}..{} outside the {size} bytes of {id}", byte_offset, byte_offset + len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get a clonable handle to a buffer in the main pool: a whole device buffer, or
+    /// the sub-range of a slab block it names. Spilled buffers are paged in; see `resident`.
     pub fn get_ref(&self, id: BufferId) -> Option<BufferHandle> {
-        self.main_pool.get(id)
+        self.resident(id).ok()
     }
 }
 
+impl Drop for MemoryManager {
+    fn drop(&mut self) {
+        if !self.assert_no_leaks.load(Ordering::Relaxed) || std::thread::panicking() {
+            return;
+        }
+        let leaks = self.report_leaks();
+        if !leaks.is_empty() {
+            let list: Vec<String> = leaks.iter().map(Leak::to_string).collect();
+            panic!("{} buffer(s) leaked:\n{}", leaks.len(), list.join("\n"));
+        }
+    }
+}
+
+/// Buffer copies move whole words (`COPY_BUFFER_ALIGNMENT`)
+const WORD: usize = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+
+/// Whole words spanning `len` bytes at `byte_offset`; size classes leave room for the last one.
+fn word_range(byte_offset: usize, len: usize) -> (usize, usize) {
+    (byte_offset / WORD * WORD, (byte_offset + len).next_multiple_of(WORD))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,22 +707,327 @@ mod tests {
     fn test_allocate_and_free() {
         let ctx  = block_on(GpuContext::new()).unwrap();
         let mm = MemoryManager::new(ctx);
-        let (id, _) = mm.allocate_raw(256).unwrap();
+        let (id, _token) = mm.allocate_raw(256).unwrap();
         assert!(mm.get_ref(id).is_some());
         mm.release(id);
         assert!(mm.get_ref(id).is_none());
     }
 
+    #[test]
+    fn test_kernels_follow_the_tuner() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let (id, _token) = mm.allocate_filled(300 * 4, 7u32).unwrap();
+        assert_eq!(mm.download_raw::<u32>(id).unwrap(), vec![7; 300]);
+        assert_eq!(mm.fill_kernel().unwrap().workgroup_size, ctx.tuner().preferred());
+
+        // pinning a size rebuilds the kernel with it
+        ctx.tuner().set(FILL_ENTRY, 16).unwrap();
+        let (id, _token) = mm.allocate_filled(300 * 4, 9u32).unwrap();
+        assert_eq!(mm.download_raw::<u32>(id).unwrap(), vec![9; 300]);
+        assert_eq!(mm.fill_kernel().unwrap().workgroup_size, 16);
+
+        // a pin for one dtype variant leaves the others alone
+        ctx.tuner().set_variant(copy::COPY_ENTRY, &[DataType::F32], &[DataType::I32], 32).unwrap();
+        assert_eq!(mm.copy_kernel(DataType::F32, DataType::I32).unwrap().workgroup_size, 32);
+        assert_eq!(mm.copy_kernel(DataType::F32, DataType::F32).unwrap().workgroup_size, ctx.tuner().preferred());
+    }
+
     #[test]
     fn test_upload_download_roundtrip() {
         let ctx  = block_on(GpuContext::new()).unwrap();
         let mm = MemoryManager::new(ctx);
         let data  = vec![10u32, 20, 30, 40];
 
-        let (id, _) = mm.allocate_raw(data.len() * std::mem::size_of::<u32>()).unwrap();
+        let (id, _token) = mm.allocate_raw(data.len() * std::mem::size_of::<u32>()).unwrap();
         mm.write_to_buffer(id, &data).unwrap();
         let back: Vec<u32> = mm.download_raw(id).unwrap();
         assert_eq!(data, back);
         mm.release(id);
     }
+
+    #[test]
+    fn test_missing_buffer_is_an_error() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (id, _token) = mm.allocate_raw(16).unwrap();
+        mm.release(id);
+
+        assert!(matches!(mm.write_to_buffer(id, &[1u32; 4]), Err(VknpError::MissingBuffer(b)) if b == id));
+        assert!(matches!(mm.download_raw::<u32>(id), Err(VknpError::MissingBuffer(b)) if b == id));
+    }
+
+    #[test]
+    fn test_download_async_with_explicit_polls() {
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let data  = vec![1.5f32, -2.0, 3.25];
+
+        let (id, _token) = mm.allocate_raw(data.len() * std::mem::size_of::<f32>()).unwrap();
+        mm.write_to_buffer(id, &data).unwrap();
+
+        // Drive the future by hand: poll the device, then the future, until it resolves
+        let mut fut = std::pin::pin!(mm.download_async::<f32>(id));
+        let mut cx = Context::from_waker(Waker::noop());
+        let back = loop {
+            if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) {
+                break v.unwrap();
+            }
+            ctx.poll().unwrap();
+        };
+        assert_eq!(data, back);
+    }
+
+    #[test]
+    fn test_ranged_reads_and_writes_of_any_alignment() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (id, _token) = mm.allocate_raw(11).unwrap();
+        let mut host: Vec<u8> = (0..11).collect();
+        mm.write_to_buffer(id, &host).unwrap();
+
+        // unaligned offsets and lengths keep the neighbouring bytes
+        mm.write_range(id, 1, &[100u8, 101]).unwrap();
+        mm.write_range(id, 6, &[200u8, 201, 202, 203, 204]).unwrap();
+        host[1..3].copy_from_slice(&[100, 101]);
+        host[6..11].copy_from_slice(&[200, 201, 202, 203, 204]);
+        assert_eq!(mm.download_raw::<u8>(id).unwrap(), host);
+
+        assert_eq!(mm.read_range::<u8>(id, 5, 3).unwrap(), &host[5..8]);
+        assert_eq!(mm.read_range::<u8>(id, 9, 2).unwrap(), &host[9..]);
+        assert!(mm.read_range::<u8>(id, 0, 0).unwrap().is_empty());
+
+        assert!(matches!(mm.read_range::<u8>(id, 8, 4), Err(VknpError::Validation(_))));
+        assert!(matches!(mm.write_range(id, 10, &[1u8, 2]), Err(VknpError::Validation(_))));
+    }
+
+    #[test]
+    fn test_budget_reclaims_then_fails_with_out_of_memory() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        mm.set_budget(Some(4096));
+
+        let (_, first) = mm.allocate_raw(2048).unwrap();
+        let (second, _second) = mm.allocate_raw(1024).unwrap();
+        drop(first);
+        // 2048 + 1024 (pending / recycled) + 2048 > 4096: the released buffer is trimmed first
+        let (third, _third) = mm.allocate_raw(2048).unwrap();
+        // pinned tensors cannot be spilled to make room either
+        mm.pin(second).unwrap();
+        mm.pin(third).unwrap();
+        let err = mm.allocate_raw(2048).err().unwrap();
+        assert!(matches!(err, VknpError::OutOfMemory { requested: 2048 }), "got {err}");
+
+        let stats = mm.stats();
+        assert_eq!(stats.live_bytes, 3072);
+        assert!(stats.peak_bytes <= 4096);
+        assert_eq!((stats.allocs, stats.frees), (3, 1));
+        assert_eq!(stats.budget, Some(4096));
+
+        mm.set_budget(None);
+        assert!(mm.allocate_raw(2048).is_ok());
+    }
+
+    #[test]
+    fn test_slab_blocks_count_against_the_budget() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::with_config(ctx, PoolConfig::slab());
+        let (_a, _token_a) = mm.allocate_raw(100).unwrap();
+        let block = mm.main_pool.config().slab.unwrap().block_size;
+        assert_eq!(mm.stats().reserved_bytes, block);
+
+        // more small tensors fit in the block already held
+        mm.set_budget(Some(block));
+        let (_b, _token_b) = mm.allocate_raw(100).unwrap();
+        assert_eq!(mm.stats().reserved_bytes, block);
+        // released sub-ranges do not free the block while another one is live
+        let (_c, token_c) = mm.allocate_raw(100).unwrap();
+        drop(token_c);
+        assert_eq!(mm.trim(), 0);
+        assert_eq!(mm.stats().reserved_bytes, block);
+    }
+
+    #[test]
+    fn test_budget_spills_cold_tensors_and_pages_them_back_in() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (a, _ta) = mm.allocate_raw(4096).unwrap();
+        let (b, _tb) = mm.allocate_raw(4096).unwrap();
+        mm.write_to_buffer(a, &[1u32; 1024]).unwrap();
+        mm.write_to_buffer(b, &[2u32; 1024]).unwrap();
+        mm.pin(b).unwrap();
+        mm.flush_uploads().unwrap();
+
+        // room for one more 4 KiB buffer only once something is spilled
+        mm.trim();
+        mm.set_budget(Some(mm.stats().reserved_bytes + 2048));
+        let (c, _tc) = mm.allocate_raw(4096).unwrap();
+        assert!(mm.is_spilled(a), "`a` is the least recently used unpinned tensor");
+        assert!(!mm.is_spilled(b));
+        assert_eq!(mm.stats().spilled_bytes, 4096);
+
+        // reading `a` pages it back in, spilling `c` in turn
+        assert_eq!(mm.download_raw::<u32>(a).unwrap(), vec![1; 1024]);
+        assert!(!mm.is_spilled(a) && mm.is_spilled(c));
+        assert_eq!(mm.download_raw::<u32>(b).unwrap(), vec![2; 1024]);
+        assert!(mm.main_pool.is_evictable(a));
+        assert!(!mm.main_pool.is_evictable(b) && !mm.main_pool.is_evictable(c), "pinned / already spilled");
+        let held = mm.resident(a).unwrap();
+        assert!(!mm.evict(a).unwrap(), "still referenced outside the pool");
+        drop(held);
+
+        // nothing left to spill: everything live is pinned or in use
+        mm.pin(a).unwrap();
+        assert!(matches!(mm.allocate_raw(4096), Err(VknpError::OutOfMemory { .. })));
+        let [main, ..] = mm.pool_stats();
+        assert_eq!((main.spills, main.page_ins), (2, 1));
+    }
+
+    #[test]
+    fn test_stats_count_staging_traffic() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (id, _token) = mm.allocate_raw(40).unwrap();
+        mm.write_to_buffer(id, &[1u32; 10]).unwrap();
+        mm.write_range(id, 1, &[9u8]).unwrap();
+        let _: Vec<u32> = mm.read_range(id, 8, 2).unwrap();
+
+        let stats = mm.stats();
+        // the unaligned byte is sent as a whole word, after reading that word back
+        assert_eq!(stats.uploaded_bytes, 40 + 4);
+        assert_eq!(stats.downloaded_bytes, 4 + 8);
+        assert_eq!(stats.reserved_bytes, stats.pools.iter().map(|p| p.live_bytes + p.free_bytes + p.pending_bytes).sum::<u64>()
+            + mm.upload_belt().chunk_size());
+    }
+
+    #[test]
+    fn test_report_leaks_lists_live_buffers() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (id, token) = mm.allocate_raw(100).unwrap();
+        let (_, _released) = mm.allocate_raw(8).unwrap();
+        drop(_released);
+
+        let leaks = mm.report_leaks();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].pool, leaks[0].allocation.id, leaks[0].allocation.size), ("main", id, 100));
+        #[cfg(feature = "track-allocations")]
+        assert!(leaks[0].allocation.site.as_ref().is_some_and(|s| s.backtrace.contains("allocate_raw")));
+
+        drop(token);
+        assert!(mm.report_leaks().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "1 buffer(s) leaked")]
+    fn test_leak_assertion_on_drop() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        mm.assert_no_leaks_on_drop(true);
+        let (_, token) = mm.allocate_raw(64).unwrap();
+        std::mem::forget(token);
+    }
+
+    #[test]
+    fn test_staging_buffers_are_recycled() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let (id, _token) = mm.allocate_raw(3 * std::mem::size_of::<u32>()).unwrap();
+
+        for i in 0..4u32 {
+            mm.write_to_buffer(id, &[i, i + 1, i + 2]).unwrap();
+            assert_eq!(mm.download_raw::<u32>(id).unwrap(), vec![i, i + 1, i + 2]);
+        }
+        let [main, _, upload, download, _] = mm.pool_stats();
+        assert_eq!(main.device_allocs, 1);
+        // small uploads go through the belt, whose chunk is mapped again after each use
+        assert_eq!(upload.device_allocs, 0);
+        assert_eq!(mm.upload_belt().chunks(), 1);
+        assert_eq!((download.device_allocs, download.reuses), (1, 3));
+
+        assert!(mm.trim() > 0);
+        assert_eq!(mm.pool_stats()[3].free_bytes, 0);
+    }
+
+    #[test]
+    fn test_small_uploads_share_the_belt_until_the_next_submission() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let ids: Vec<_> = (0..16u32).map(|i| {
+            let (id, token) = mm.allocate_raw(3 * std::mem::size_of::<u32>()).unwrap();
+            mm.write_to_buffer(id, &[i, 2 * i, 3 * i]).unwrap();
+            (id, token)
+        }).collect();
+        assert_eq!(mm.upload_belt().pending(), 16);
+
+        // the first download flushes every queued upload in its own submission
+        for (i, (id, _)) in ids.iter().enumerate() {
+            let i = i as u32;
+            assert_eq!(mm.download_raw::<u32>(*id).unwrap(), vec![i, 2 * i, 3 * i]);
+        }
+        assert_eq!(mm.upload_belt().pending(), 0);
+        assert_eq!(mm.upload_belt().chunks(), 1);
+
+        // larger than a chunk: dedicated staging buffer, still ordered after the queued writes
+        let n = (mm.upload_belt().chunk_size() as usize) / 4 + 16;
+        let big: Vec<u32> = (0..n as u32).collect();
+        let (id, _token) = mm.allocate_raw(n * 4).unwrap();
+        mm.write_to_buffer(id, &[7u32; 4]).unwrap();
+        mm.write_to_buffer(id, &big).unwrap();
+        assert_eq!(mm.download_raw::<u32>(id).unwrap(), big);
+        assert_eq!(mm.pool_stats()[2].device_allocs, 1);
+    }
+
+    #[test]
+    fn test_uploads_survive_an_abandoned_batch() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let (a, _ta) = mm.allocate_raw(16).unwrap();
+        mm.write_to_buffer(a, &[1u32, 2, 3, 4]).unwrap();
+
+        // the batch takes the queued upload over, then is dropped without being submitted
+        let mut batch = ctx.begin_batch("abandoned");
+        mm.flush_uploads_into(&mut batch);
+        assert_eq!(mm.upload_belt().pending(), 0);
+        drop(batch);
+        assert_eq!(mm.upload_belt().pending(), 1);
+
+        // newer uploads land after the ones handed back, and the chunks are reused
+        let (b, _tb) = mm.allocate_raw(8).unwrap();
+        mm.write_to_buffer(b, &[5u32, 6]).unwrap();
+        mm.write_to_buffer(a, &[9u32]).unwrap();
+        assert_eq!(mm.download_raw::<u32>(a).unwrap(), vec![9, 2, 3, 4]);
+        assert_eq!(mm.download_raw::<u32>(b).unwrap(), vec![5, 6]);
+        mm.write_to_buffer(b, &[7u32, 8]).unwrap();
+        assert_eq!(mm.download_raw::<u32>(b).unwrap(), vec![7, 8]);
+        assert_eq!(mm.upload_belt().chunks(), 2);
+    }
+
+    #[test]
+    fn test_uploads_go_with_a_profiled_dispatch() {
+        let ctx  = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+        let (a, _ta) = mm.allocate_raw(16).unwrap();
+        let (b, _tb) = mm.allocate_raw(4).unwrap();
+        mm.write_to_buffer(a, &[1u32, 2, 3, 4]).unwrap();
+
+        // with a host-clock profiler the dispatch is submitted on its own, uploads included
+        let mut batch = ctx.begin_batch("profiled").with_profiler(vknp_core::Profiler::cpu(&ctx));
+        mm.flush_uploads_into(&mut batch);
+        let (params, _params_token) = mm.alloc_param(&fill::fill_params(1, 7), BufferKind::Uniform).unwrap();
+        mm.fill_kernel().unwrap().record(&mut batch, &[], params, mm.resident(b).unwrap(), 1, "fill").unwrap();
+        drop(batch);
+        assert_eq!(mm.upload_belt().pending(), 0, "nothing is handed back");
+        assert_eq!(mm.download_raw::<u32>(a).unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(mm.download_raw::<u32>(b).unwrap(), vec![7]);
+
+        // and its chunk was recalled for the next uploads
+        mm.write_to_buffer(a, &[5u32]).unwrap();
+        assert_eq!(mm.download_raw::<u32>(a).unwrap(), vec![5, 2, 3, 4]);
+        assert_eq!(mm.upload_belt().chunks(), 1);
+    }
 }
@@ -0,0 +1,303 @@
+//! Counter-based random numbers (Philox-4x32-10): element `i` of a draw is a pure
+//! function of the seed, stream, offset and `i`, so results do not depend on the
+//! workgroup size or the layout of the output, and streams never overlap.
+
+use bytemuck::{Pod, Zeroable};
+
+use vknp_core::{Result, VknpError};
+use core_types::{DataType, ViewDescriptor};
+
+use crate::copy::{wgsl_type, ViewU, VIEW_WGSL};
+
+const PHILOX_M0: u32 = 0xD251_1F53;
+const PHILOX_M1: u32 = 0xCD9E_8D57;
+const PHILOX_W0: u32 = 0x9E37_79B9;
+const PHILOX_W1: u32 = 0xBB67_AE85;
+
+/// Philox-4x32-10 block `counter` under `key`, as in Random123
+pub fn philox4x32(counter: [u32; 4], key: [u32; 2]) -> [u32; 4] {
+    let mulhilo = |a: u32, b: u32| {
+        let p = a as u64 * b as u64;
+        ((p >> 32) as u32, p as u32)
+    };
+    let (mut c, mut k) = (counter, key);
+    for _ in 0..10 {
+        let (hi0, lo0) = mulhilo(PHILOX_M0, c[0]);
+        let (hi1, lo1) = mulhilo(PHILOX_M1, c[2]);
+        c = [hi1 ^ c[1] ^ k[0], lo1, hi0 ^ c[3] ^ k[1], lo0];
+        k = [k[0].wrapping_add(PHILOX_W0), k[1].wrapping_add(PHILOX_W1)];
+    }
+    c
+}
+
+/// Position in the Philox sequence: draws of `seed` on `stream` start at block `offset`
+/// (one block is four 32-bit words).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RandomState {
+    pub seed:   u64,
+    pub stream: u64,
+    pub offset: u64,
+}
+
+impl RandomState {
+    /// Block `block` of this stream
+    pub fn block(&self, block: u64) -> [u32; 4] {
+        let ctr = self.offset.wrapping_add(block);
+        philox4x32(
+            [ctr as u32, (ctr >> 32) as u32, self.stream as u32, (self.stream >> 32) as u32],
+            [self.seed as u32, (self.seed >> 32) as u32],
+        )
+    }
+}
+
+/// What a draw writes at each row-major index of its output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// Uniform over `[low, high)` (float types)
+    Uniform { low: f64, high: f64 },
+    /// Gaussian (Box-Muller) of mean `mean` and standard deviation `std` (float types)
+    Normal { mean: f64, std: f64 },
+    /// Integers uniform over `[low, high)`, at most 2³² of them (integer types). The
+    /// bias of the multiply-shift reduction is at most `(high - low) / 2³²`.
+    Randint { low: i64, high: i64 },
+    /// 1 with probability `p`, 0 otherwise
+    Bernoulli { p: f64 },
+    /// A random permutation of `0..n` (the output holds `n` elements), drawn from a
+    /// Feistel network keyed by the state: not every one of the `n!` orders is reachable
+    Permutation { n: u32 },
+    /// Indices in `0..n`, with or without `replace`ment (a prefix of a `Permutation`);
+    /// with a source, the elements of the source at those indices
+    Choice { n: u32, replace: bool },
+}
+
+impl Distribution {
+    /// Philox blocks a draw of `n` elements consumes: the offset of the next draw
+    pub fn blocks(&self, n: u64) -> u64 {
+        match self {
+            Distribution::Normal { .. } => n.div_ceil(2),
+            Distribution::Permutation { .. } | Distribution::Choice { replace: false, .. } => 1,
+            _ => n.div_ceil(4),
+        }
+    }
+
+    /// Check the parameters of a draw of `n` elements.
+    pub fn validate(&self, n: u64) -> Result<()> {
+        match *self {
+            Distribution::Randint { low, high } if low >= high =>
+                Err(VknpError::Op(format!("randint: empty range [{low}, {high})"))),
+            Distribution::Randint { low, high } if high - low > 1 << 32 =>
+                Err(VknpError::Op(format!("randint: range [{low}, {high}) holds more than 2³² values"))),
+            Distribution::Bernoulli { p } if !(0.0..=1.0).contains(&p) =>
+                Err(VknpError::Op(format!("bernoulli: probability {p} outside [0, 1]"))),
+            Distribution::Permutation { n: len } if n != len as u64 =>
+                Err(VknpError::Shape(format!("permutation: {n} elements for a permutation of {len}"))),
+            Distribution::Choice { n: len, replace: false } if n > len as u64 =>
+                Err(VknpError::Shape(format!("choice: cannot take {n} of {len} elements without replacement"))),
+            Distribution::Choice { n: 0, replace: true } if n > 0 =>
+                Err(VknpError::Shape("choice: cannot take elements of an empty source".into())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct RandMeta {
+    dst:     ViewU,
+    src:     ViewU,
+    kind:    u32,
+    total:   u32,
+    seed:    [u32; 2],
+    offset:  [u32; 2],
+    stream:  [u32; 2],
+    a:       f32,
+    b:       f32,
+    int_low: u32,
+    range:   u32,
+    domain:  u32,
+    _pad1:   [u32; 3],
+}
+
+/// Uniform block drawing `dist` from `state` into the `dst` view; `src` is the view
+/// sampled by a `Choice` with a source.
+pub fn rand_params(dist: &Distribution, state: &RandomState, dst: &ViewDescriptor, src: Option<&ViewDescriptor>) -> Vec<u8> {
+    let split = |x: u64| [x as u32, (x >> 32) as u32];
+    let mut meta = RandMeta {
+        dst: dst.into(),
+        src: src.map(ViewU::from).unwrap_or_else(Zeroable::zeroed),
+        total: dst.shape[..dst.ndim as usize].iter().product(),
+        seed: split(state.seed),
+        offset: split(state.offset),
+        stream: split(state.stream),
+        ..Zeroable::zeroed()
+    };
+    match *dist {
+        Distribution::Uniform { low, high } => (meta.kind, meta.a, meta.b) = (0, low as f32, high as f32),
+        Distribution::Normal { mean, std } => (meta.kind, meta.a, meta.b) = (1, mean as f32, std as f32),
+        Distribution::Randint { low, high } => {
+            // a range of 2³² wraps to 0: every word is kept as is
+            meta.kind = 2;
+            meta.int_low = low as u32;
+            meta.range = (high - low) as u32;
+        }
+        Distribution::Bernoulli { p } => (meta.kind, meta.a) = (3, p as f32),
+        Distribution::Permutation { n } => (meta.kind, meta.domain) = (4, n),
+        Distribution::Choice { n, replace } => (meta.kind, meta.domain) = (if replace { 5 } else { 4 }, n),
+    }
+    bytemuck::bytes_of(&meta).to_vec()
+}
+
+/// Entry point of the `rand_wgsl` kernels
+pub const RAND_ENTRY: &str = "random";
+
+/// Kernel drawing the `Distribution` encoded by `rand_params` into the `D` view of `dtype`
+/// elements. Without `src`, bindings are uniform params 0 (`var<param>` for the op
+/// registry) and output 1; with it, the sampled input 0, params 1 and output 2.
+pub fn rand_wgsl(dtype: DataType, src: Option<DataType>) -> String {
+    let (source, out_binding, pick) = match src {
+        Some(s) => (
+            format!("@group(0) @binding(0) var<storage, read>       S : array<{}>;", wgsl_type(s)),
+            2,
+            "T(S[view_offset(j, M.src)])",
+        ),
+        None => (String::new(), 1, "T(j)"),
+    };
+    format!(r#"{VIEW_WGSL}
+override WG_SIZE : u32 = 64u;
+alias T = {t};
+
+struct Meta {{
+  dst     : View,
+  src     : View,
+  kind    : u32,
+  total   : u32,
+  seed    : vec2<u32>,
+  offset  : vec2<u32>,
+  stream  : vec2<u32>,
+  a       : f32,
+  b       : f32,
+  int_low : u32,
+  range   : u32,
+  domain  : u32,
+  _pad1   : u32,
+  _pad2   : u32,
+  _pad3   : u32,
+}};
+
+{source}
+var<param>                                     M : Meta;
+@group(0) @binding({out_binding}) var<storage, read_write> D : array<T>;
+
+// (hi, lo) words of a * b
+fn mulhilo(a: u32, b: u32) -> vec2<u32> {{
+  let ll = (a & 0xffffu) * (b & 0xffffu);
+  let lh = (a & 0xffffu) * (b >> 16u);
+  let hl = (a >> 16u) * (b & 0xffffu);
+  let hh = (a >> 16u) * (b >> 16u);
+  let mid = (ll >> 16u) + (lh & 0xffffu) + (hl & 0xffffu);
+  return vec2<u32>(hh + (lh >> 16u) + (hl >> 16u) + (mid >> 16u), a * b);
+}}
+
+fn philox(block: u32) -> vec4<u32> {{
+  let lo = M.offset.x + block;
+  var c = vec4<u32>(lo, M.offset.y + select(0u, 1u, lo < block), M.stream.x, M.stream.y);
+  var k = M.seed;
+  for (var r = 0u; r < 10u; r = r + 1u) {{
+    let p0 = mulhilo(0xD2511F53u, c.x);
+    let p1 = mulhilo(0xCD9E8D57u, c.z);
+    c = vec4<u32>(p1.x ^ c.y ^ k.x, p1.y, p0.x ^ c.w ^ k.y, p0.y);
+    k = k + vec2<u32>(0x9E3779B9u, 0xBB67AE85u);
+  }}
+  return c;
+}}
+
+// word i of the stream
+fn word(i: u32) -> u32 {{
+  return philox(i / 4u)[i % 4u];
+}}
+
+// [0, 1) with 24 random bits
+fn unit(w: u32) -> f32 {{
+  return f32(w >> 8u) * 5.9604645e-8;
+}}
+
+fn fmix(x: u32) -> u32 {{
+  var h = x;
+  h = (h ^ (h >> 16u)) * 0x85ebca6bu;
+  h = (h ^ (h >> 13u)) * 0xc2b2ae35u;
+  return h ^ (h >> 16u);
+}}
+
+// Cycle-walking Feistel network: a bijection of [0, domain) keyed by block 0
+fn permute(i: u32) -> u32 {{
+  // the walk only ends for indices of the domain (`Distribution::validate`)
+  if (i >= M.domain) {{ return i; }}
+  let keys = philox(0u);
+  let bits = 32u - countLeadingZeros(max(M.domain, 1u) - 1u);
+  let half = (bits + 1u) / 2u;
+  let mask = (1u << half) - 1u;
+  var x = i;
+  loop {{
+    var l = x >> half;
+    var r = x & mask;
+    for (var n = 0u; n < 4u; n = n + 1u) {{
+      let t = l ^ (fmix(r ^ keys[n]) & mask);
+      l = r;
+      r = t;
+    }}
+    x = (l << half) | r;
+    if (x < M.domain) {{ break; }}
+  }}
+  return x;
+}}
+
+@compute @workgroup_size(WG_SIZE)
+fn {RAND_ENTRY}(
+  @builtin(workgroup_id) wid: vec3<u32>,
+  @builtin(num_workgroups) nwg: vec3<u32>,
+  @builtin(local_invocation_index) lid: u32,
+) {{
+  let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
+  if (i >= M.total) {{ return; }}
+  var v : T;
+  switch M.kind {{
+    case 0u: {{ v = T(M.a + (M.b - M.a) * unit(word(i))); }}
+    case 1u: {{
+      let w = philox(i / 2u);
+      let pair = select(w.xy, w.zw, i % 2u == 1u);
+      let u1 = f32((pair.x >> 8u) + 1u) * 5.9604645e-8;
+      let u2 = unit(pair.y);
+      v = T(M.a + M.b * sqrt(-2.0 * log(u1)) * cos(6.2831855 * u2));
+    }}
+    case 2u: {{
+      let w = word(i);
+      v = bitcast<T>(M.int_low + select(mulhilo(w, M.range).x, w, M.range == 0u));
+    }}
+    case 3u: {{ v = select(T(0), T(1), unit(word(i)) < M.a); }}
+    default: {{
+      var j : u32;
+      if (M.kind == 4u) {{ j = permute(i); }} else {{ j = mulhilo(word(i), M.domain).x; }}
+      v = {pick};
+    }}
+  }}
+  D[view_offset(i, M.dst)] = v;
+}}
+"#, t = wgsl_type(dtype))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn philox_matches_the_random123_known_answers() {
+        assert_eq!(philox4x32([0; 4], [0; 2]), [0x6627e8d5, 0xe169c58d, 0xbc57ac4c, 0x9b00dbd8]);
+        assert_eq!(philox4x32([u32::MAX; 4], [u32::MAX; 2]), [0x408f276d, 0x41c83b0e, 0xa20bc7c6, 0x6d5451fd]);
+        assert_eq!(
+            philox4x32([0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344], [0xa4093822, 0x299f31d0]),
+            [0xd16cfe09, 0x94fdcceb, 0x5001e420, 0x24126ea1],
+        );
+    }
+}
@@ -1,71 +1,630 @@
-use std::collections::HashMap;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Weak, atomic::{AtomicBool, AtomicU64, Ordering}};
 use parking_lot::Mutex;
-use anyhow::Result;
+use wgpu::Queue;
 
-use vknp_core::GpuContext;
-use vknp_core::types::{BufferKind, BufferHandle, BufferToken};
+use vknp_core::{GpuContext, Result};
+use vknp_core::types::{AbstractBuffer, BufferKind, BufferHandle, BufferToken, TokenGuard};
 use core_types::BufferId;
 
 
+/// Where the content of an allocation currently lives
+enum Residency {
+    Device(BufferHandle),
+    /// Spilled to host memory; paged back in with `restore`
+    Host(Vec<u8>),
+}
+
 struct BufferEntry {
-    buffer: BufferHandle,
+    residency: Residency,
     size: usize,
+    class: u64,
+    /// Tick of the last `touch`, for LRU eviction
+    last_use: u64,
+    /// Never spilled
+    pinned: bool,
+    /// Name given at allocation or with `BufferToken::set_label`
+    label: Option<String>,
+    /// Recorded with the `track-allocations` feature
+    site: Option<Arc<AllocSite>>,
+}
+
+impl BufferEntry {
+    /// Resident, not pinned, and not referenced outside the pool (e.g. by a batch not yet submitted)
+    fn evictable(&self) -> bool {
+        !self.pinned && matches!(&self.residency, Residency::Device(b) if b.strong_count() == 1)
+    }
+}
+
+/// Where an allocation was made (recorded with the `track-allocations` feature)
+#[derive(Debug)]
+pub struct AllocSite {
+    pub label:     String,
+    pub backtrace: String,
+}
+
+impl AllocSite {
+    #[cfg(feature = "track-allocations")]
+    fn capture(label: &str) -> Option<Arc<Self>> {
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        Some(Arc::new(Self { label: label.to_string(), backtrace }))
+    }
+
+    #[cfg(not(feature = "track-allocations"))]
+    fn capture(_label: &str) -> Option<Arc<Self>> {
+        None
+    }
+}
+
+/// A live allocation of a pool, as listed by `BufferPool::live`
+#[derive(Debug, Clone)]
+pub struct LiveAllocation {
+    pub id:      BufferId,
+    /// Requested size, in bytes
+    pub size:    usize,
+    /// Size class held on the device
+    pub class:   u64,
+    pub spilled: bool,
+    pub label:   Option<String>,
+    pub site:    Option<Arc<AllocSite>>,
+}
+
+impl fmt::Display for LiveAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(label) = &self.label {
+            write!(f, " \"{}\"", label)?;
+        }
+        write!(f, ": {} bytes (class {}){}", self.size, self.class, if self.spilled { ", spilled" } else { "" })?;
+        match &self.site {
+            Some(site) => write!(f, ", `{}` allocated at:\n{}", site.label, site.backtrace),
+            None => write!(f, " (enable `track-allocations` for the allocation site)"),
+        }
+    }
+}
+
+/// How requested sizes map to recycled size classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Smallest size class, in bytes
+    pub min_class: u64,
+    /// Round requests up to a power of two; otherwise up to a multiple of `min_class`
+    pub power_of_two: bool,
+    /// Free bytes kept for reuse; releasing beyond this trims the free lists
+    pub max_free_bytes: u64,
+    /// Carve small size classes out of shared backing buffers (see `SlabConfig`)
+    pub slab: Option<SlabConfig>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self { min_class: 256, power_of_two: true, max_free_bytes: 256 << 20, slab: None }
+    }
+}
+
+impl PoolConfig {
+    /// Default classes, with slab sub-allocation of the small ones.
+    pub fn slab() -> Self {
+        Self { slab: Some(SlabConfig::default()), ..Default::default() }
+    }
+}
+
+/// Slab mode: size classes up to `max_alloc` are aligned sub-ranges of `block_size`
+/// backing buffers instead of buffers of their own. Each sub-range has its own id and
+/// is recycled through the free lists like any buffer; a block goes back to the device
+/// once the pool has let go of all its sub-ranges (and nothing else references them).
+///
+/// Only for pools whose buffers are never mapped (`Main`, `Uniform`): two ranges of one
+/// block cannot be mapped at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabConfig {
+    /// Size of each backing buffer, in bytes
+    pub block_size: u64,
+    /// Largest size class carved out of a block
+    pub max_alloc: u64,
+}
+
+impl Default for SlabConfig {
+    fn default() -> Self {
+        Self { block_size: 4 << 20, max_alloc: 64 << 10 }
+    }
+}
+
+/// Counters of one pool, in bytes of device memory (size classes, not requested sizes)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Held by live allocations
+    pub live_bytes: u64,
+    /// Held by the free lists, ready for reuse
+    pub free_bytes: u64,
+    /// Released, waiting for the GPU work submitted before the release to complete
+    pub pending_bytes: u64,
+    /// Allocated on the device: buffers of their own, and slab blocks as a whole. Without
+    /// slab mode, `live_bytes + free_bytes + pending_bytes`; with it, the sub-ranges counted
+    /// there are only a part of their blocks.
+    pub reserved_bytes: u64,
+    /// Peak of `reserved_bytes`
+    pub high_water: u64,
+    /// Buffers actually created on the device (slab blocks included)
+    pub device_allocs: u64,
+    /// Allocations carved out of a slab block
+    pub slab_allocs: u64,
+    /// Allocations served from a free list
+    pub reuses: u64,
+    /// Allocations handed out (new, carved or reused)
+    pub allocs: u64,
+    /// Allocations released
+    pub frees: u64,
+    /// Host bytes held by spilled allocations (not counted in the device bytes above)
+    pub spilled_bytes: u64,
+    /// Allocations spilled to host / paged back in
+    pub spills: u64,
+    pub page_ins: u64,
+}
+
+/// A released buffer, recyclable once `done` is set
+struct PendingRelease {
+    buffer: BufferHandle,
+    class:  u64,
+    done:   Arc<AtomicBool>,
+}
+
+/// Slab block, bump-allocated
+struct SlabBlock {
+    buffer: AbstractBuffer,
+    used:   u64,
+    /// Sub-ranges the pool still holds (live, pending or free); the block is dropped with the last one
+    ranges: usize,
+}
+
+#[derive(Default)]
+struct PoolState {
+    entries: HashMap<BufferId, BufferEntry>,
+    /// size class → released buffers
+    free: BTreeMap<u64, Vec<BufferHandle>>,
+    /// released buffers possibly still used by in-flight GPU work
+    pending: Vec<PendingRelease>,
+    /// slab blocks the pool still holds sub-ranges of; new ones are carved from the last
+    slabs: Vec<SlabBlock>,
+    /// use counter behind `BufferEntry::last_use`
+    tick: u64,
+    stats: PoolStats,
+}
+
+impl PoolState {
+    fn bump_high_water(&mut self) {
+        self.stats.high_water = self.stats.high_water.max(self.stats.reserved_bytes);
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Remove `id` from the live entries; its buffer becomes recyclable once everything
+    /// submitted so far has completed.
+    fn release(&mut self, id: BufferId, queue: &Queue) {
+        let Some(entry) = self.entries.remove(&id) else { return };
+        self.stats.frees += 1;
+        match entry.residency {
+            Residency::Device(buffer) => {
+                self.stats.live_bytes -= entry.class;
+                self.retire(buffer, entry.class, queue);
+            }
+            Residency::Host(_) => self.stats.spilled_bytes -= entry.size as u64,
+        }
+    }
+
+    /// Hand a buffer no longer used by its entry to the free lists, once in-flight work is done.
+    fn retire(&mut self, buffer: BufferHandle, class: u64, queue: &Queue) {
+        let done = Arc::new(AtomicBool::new(false));
+        let flag = done.clone();
+        queue.on_submitted_work_done(move || flag.store(true, Ordering::Release));
+        self.stats.pending_bytes += class;
+        self.pending.push(PendingRelease { buffer, class, done });
+    }
+
+    /// Move completed releases to the free lists, then trim them to `max_free_bytes`.
+    fn collect(&mut self, max_free_bytes: u64) {
+        let (done, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| p.done.load(Ordering::Acquire));
+        self.pending = pending;
+        for p in done {
+            self.stats.pending_bytes -= p.class;
+            self.stats.free_bytes += p.class;
+            self.free.entry(p.class).or_default().push(p.buffer);
+        }
+        self.trim(max_free_bytes);
+    }
+
+    /// Take a free buffer of `class` nobody else still references
+    /// (a batch may hold one until it is submitted).
+    fn take_free(&mut self, class: u64) -> Option<BufferHandle> {
+        let list = self.free.get_mut(&class)?;
+        let pos = list.iter().position(|b| b.strong_count() == 1)?;
+        let buffer = list.swap_remove(pos);
+        self.stats.free_bytes -= class;
+        Some(buffer)
+    }
+
+    /// Drop free buffers, largest classes first, until at most `target` free bytes remain.
+    /// Returns the device bytes actually freed.
+    fn trim(&mut self, target: u64) -> u64 {
+        let mut freed = 0;
+        while self.stats.free_bytes > target {
+            let Some(mut last) = self.free.last_entry() else { break };
+            let class = *last.key();
+            let buffer = last.get_mut().pop();
+            if last.get().is_empty() {
+                last.remove();
+            }
+            if let Some(buffer) = buffer {
+                self.stats.free_bytes -= class;
+                freed += self.drop_buffer(buffer, class);
+            }
+        }
+        freed
+    }
+
+    /// Let go of a recycled buffer of `class`; returns the device bytes freed: its own
+    /// size, or for a slab sub-range, its block's once no other sub-range of it is held.
+    fn drop_buffer(&mut self, buffer: BufferHandle, class: u64) -> u64 {
+        let freed = match self.slabs.iter().position(|b| b.buffer.same_buffer(buffer.as_raw())) {
+            None => class,
+            Some(i) => {
+                self.slabs[i].ranges -= 1;
+                if self.slabs[i].ranges > 0 { 0 } else { self.slabs.remove(i).buffer.size() }
+            }
+        };
+        self.stats.reserved_bytes -= freed;
+        freed
+    }
+}
+
+
+/// Returns its buffer to the pool when the last `BufferToken` clone is dropped
+struct Lease {
+    id:    BufferId,
+    state: Weak<Mutex<PoolState>>,
+    queue: Arc<Queue>,
 }
 
+impl TokenGuard for Lease {
+    fn set_label(&self, label: &str) {
+        if let Some(state) = self.state.upgrade()
+            && let Some(entry) = state.lock().entries.get_mut(&self.id) {
+            entry.label = Some(label.to_string());
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            state.lock().release(self.id, &self.queue);
+        }
+    }
+}
 
-/// thread-safe pool of GPU buffers
+
+/// thread-safe pool of GPU buffers, recycled through per-size-class free lists.
+///
+/// A buffer is released when the last token returned by `alloc_buffer` is dropped (or
+/// explicitly with `release_buffer`), and reused only once the GPU work submitted
+/// before its release has completed.
 pub struct BufferPool {
     ctx: GpuContext,
     usage: BufferKind,
+    config: PoolConfig,
     next_id: AtomicU64,
-    entries: Mutex<HashMap<BufferId, BufferEntry>>,
+    state: Arc<Mutex<PoolState>>,
 }
 
 impl BufferPool {
     pub fn new(ctx: GpuContext, usage: BufferKind) -> Self {
+        Self::with_config(ctx, usage, PoolConfig::default())
+    }
+
+    /// Pool with custom size classes. `config.slab` is ignored for staging pools.
+    pub fn with_config(ctx: GpuContext, usage: BufferKind, mut config: PoolConfig) -> Self {
+        if matches!(usage, BufferKind::Upload | BufferKind::Download) {
+            config.slab = None;
+        }
         Self {
             ctx,
             usage,
+            config,
             next_id: AtomicU64::new(0),
-            entries: Mutex::new(HashMap::new()),
+            state: Arc::new(Mutex::new(PoolState::default())),
         }
     }
 
-    /// Allocate (or recycle) a buffer of `size_bytes`, returning a unique ID and a BufferToken
+    /// Allocate (or recycle) a buffer of `size_bytes`, returning a unique ID and a BufferToken.
+    /// The buffer stays allocated as long as a clone of the token is alive.
     pub fn alloc_buffer(&self, size_bytes: usize) -> Result<(BufferId, BufferToken)> {
-        // MVP: alloc à chaque demande (recyclage à venir)
-        let raw = self.ctx.create_buffer(size_bytes as u64, self.usage);
+        self.alloc_buffer_labeled(size_bytes, None)
+    }
+
+    /// `alloc_buffer`, naming the allocation. A device buffer created for it carries the
+    /// label too; a recycled one (or a slab range) keeps the label it was created with.
+    pub fn alloc_buffer_labeled(&self, size_bytes: usize, label: Option<&str>) -> Result<(BufferId, BufferToken)> {
+        let class = self.class_of(size_bytes as u64);
+        let handle = self.obtain(class, label)?;
         let id = BufferId(self.next_id.fetch_add(1, Ordering::Relaxed));
-        let handle = Arc::new(raw);
+        let token = BufferToken::with_guard(Arc::new(Lease {
+            id,
+            state: Arc::downgrade(&self.state),
+            queue: self.ctx.queue.clone(),
+        }));
 
-        self.entries.lock().insert(id, BufferEntry {
-            buffer: BufferHandle::new(handle.clone()),
+        let mut st = self.state.lock();
+        let last_use = st.next_tick();
+        let site = AllocSite::capture(&label.map_or_else(|| format!("{:?}", self.usage), str::to_string));
+        let entry = BufferEntry {
+            residency: Residency::Device(handle),
             size: size_bytes,
-        });
-        Ok((id, BufferToken::new(handle)))
+            class,
+            last_use,
+            pinned: false,
+            label: label.map(str::to_string),
+            site,
+        };
+        st.entries.insert(id, entry);
+        st.stats.live_bytes += class;
+        st.stats.allocs += 1;
+        st.bump_high_water();
+        Ok((id, token))
+    }
+
+    /// A buffer of `class`: recycled, carved out of a slab block or created.
+    fn obtain(&self, class: u64, label: Option<&str>) -> Result<BufferHandle> {
+        self.collect_pending();
+        let recycled = self.state.lock().take_free(class);
+        if let Some(buffer) = recycled {
+            self.state.lock().stats.reuses += 1;
+            return Ok(buffer);
+        }
+        if self.config.slab.is_some_and(|s| class <= s.max_alloc) {
+            return self.carve(class);
+        }
+        let buffer = match label {
+            Some(label) => self.ctx.create_buffer_labeled(class, self.usage, Some(label))?,
+            None => self.ctx.create_buffer_labeled(class, self.usage, Some(&self.default_label()))?,
+        };
+        let mut st = self.state.lock();
+        st.stats.device_allocs += 1;
+        st.stats.reserved_bytes += class;
+        Ok(BufferHandle::new(Arc::new(buffer)))
+    }
+
+    /// Carve `class` bytes out of the current slab block, opening a new block when it is full.
+    /// Offsets are aligned for binding the range on its own.
+    fn carve(&self, class: u64) -> Result<BufferHandle> {
+        let slab = self.config.slab.expect("carve is only used in slab mode");
+        let mut st = self.state.lock();
+        let offset = match self.slab_offset(&st, class) {
+            Some(offset) => offset,
+            None => {
+                let label = format!("{}-slab", self.default_label());
+                let buffer = self.ctx.create_buffer_labeled(slab.block_size.max(class), self.usage, Some(&label))?;
+                st.stats.device_allocs += 1;
+                st.stats.reserved_bytes += buffer.size();
+                st.slabs.push(SlabBlock { buffer, used: 0, ranges: 0 });
+                0
+            }
+        };
+        let block = st.slabs.last_mut().expect("a block was just opened");
+        block.used = offset + class;
+        block.ranges += 1;
+        let range = block.buffer.sub_range(offset, class)?;
+        st.stats.slab_allocs += 1;
+        Ok(BufferHandle::new(Arc::new(range)))
+    }
+
+    /// Offset of the next `class` sub-range in the last slab block, if it still fits there
+    fn slab_offset(&self, st: &PoolState, class: u64) -> Option<u64> {
+        let slab = self.config.slab?;
+        let limits = self.ctx.device.limits();
+        let align = match self.usage {
+            BufferKind::Uniform => limits.min_uniform_buffer_offset_alignment,
+            _ => limits.min_storage_buffer_offset_alignment,
+        } as u64;
+        st.slabs.last()
+            .map(|b| b.used.next_multiple_of(align))
+            .filter(|off| off + class <= slab.block_size)
+    }
+
+    /// Device bytes a new allocation of `size` bytes may add: its size class, or in slab
+    /// mode nothing if it fits in the current block and a whole block otherwise.
+    pub fn growth_of(&self, size: u64) -> u64 {
+        let class = self.class_of(size);
+        match self.config.slab {
+            Some(slab) if class <= slab.max_alloc => {
+                if self.slab_offset(&self.state.lock(), class).is_some() { 0 } else { slab.block_size.max(class) }
+            }
+            _ => class,
+        }
+    }
+
+    /// Label of the device buffers created without a name, e.g. `vknp-storage`
+    fn default_label(&self) -> String {
+        format!("vknp-{:?}", self.usage).to_lowercase()
+    }
+
+    /// Name of an allocation, if it was given one
+    pub fn label(&self, id: BufferId) -> Option<String> {
+        self.state.lock().entries.get(&id)?.label.clone()
     }
 
-    /// Retrieve a clonable handle to the buffer for a given ID
+    /// Retrieve a clonable handle to the buffer for a given ID (`None` while it is spilled)
     pub fn get(&self, id: BufferId) -> Option<BufferHandle> {
-        self.entries.lock().get(&id).map(|e| e.buffer.clone())
+        match &self.state.lock().entries.get(&id)?.residency {
+            Residency::Device(buffer) => Some(buffer.clone()),
+            Residency::Host(_) => None,
+        }
+    }
+
+    /// Every allocation not released yet, oldest first
+    pub fn live(&self) -> Vec<LiveAllocation> {
+        let st = self.state.lock();
+        let mut live: Vec<LiveAllocation> = st.entries.iter().map(|(&id, e)| LiveAllocation {
+            id,
+            size: e.size,
+            class: e.class,
+            spilled: matches!(e.residency, Residency::Host(_)),
+            label: e.label.clone(),
+            site: e.site.clone(),
+        }).collect();
+        live.sort_unstable_by_key(|a| a.id.0);
+        live
+    }
+
+    /// Whether `id` is currently spilled to host memory
+    pub fn is_spilled(&self, id: BufferId) -> bool {
+        matches!(self.state.lock().entries.get(&id), Some(BufferEntry { residency: Residency::Host(_), .. }))
+    }
+
+    /// Mark `id` as just used (eviction picks the least recently used first).
+    pub fn touch(&self, id: BufferId) {
+        let mut st = self.state.lock();
+        let tick = st.next_tick();
+        if let Some(entry) = st.entries.get_mut(&id) {
+            entry.last_use = tick;
+        }
     }
 
+    /// Keep `id` from being spilled (or allow it again); `false` if there is no such allocation.
+    pub fn set_pinned(&self, id: BufferId, pinned: bool) -> bool {
+        self.state.lock().entries.get_mut(&id).map(|e| e.pinned = pinned).is_some()
+    }
+
+    /// Allocations that may be spilled, least recently used first: resident, not pinned,
+    /// and not referenced outside the pool (e.g. by a batch not yet submitted).
+    pub fn eviction_order(&self) -> Vec<BufferId> {
+        let st = self.state.lock();
+        let mut ids: Vec<(u64, BufferId)> = st.entries.iter()
+            .filter(|(_, e)| e.evictable())
+            .map(|(&id, e)| (e.last_use, id))
+            .collect();
+        ids.sort_unstable_by_key(|&(last_use, _)| last_use);
+        ids.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Whether `id` may be spilled now: resident, not pinned and not referenced outside the pool.
+    pub fn is_evictable(&self, id: BufferId) -> bool {
+        self.state.lock().entries.get(&id).is_some_and(BufferEntry::evictable)
+    }
+
+    /// Swap the device buffer of `id` for `host`, a copy of its content; the buffer is
+    /// released like a dropped one. `false` (and nothing done) if `id` may not be spilled anymore.
+    pub fn spill(&self, id: BufferId, host: Vec<u8>) -> bool {
+        let mut st = self.state.lock();
+        let Some(entry) = st.entries.get_mut(&id).filter(|e| e.evictable()) else { return false };
+        let (class, size) = (entry.class, entry.size as u64);
+        let Residency::Device(buffer) = std::mem::replace(&mut entry.residency, Residency::Host(host)) else {
+            unreachable!("checked above")
+        };
+        st.stats.live_bytes -= class;
+        st.stats.spilled_bytes += size;
+        st.stats.spills += 1;
+        st.retire(buffer, class, &self.ctx.queue);
+        true
+    }
+
+    /// Give a spilled `id` a device buffer again. Returns it with the host copy to upload,
+    /// or `None` if `id` is not spilled.
+    pub fn restore(&self, id: BufferId) -> Result<Option<(BufferHandle, Vec<u8>)>> {
+        let class = match self.state.lock().entries.get(&id) {
+            Some(BufferEntry { residency: Residency::Host(_), class, .. }) => *class,
+            _ => return Ok(None),
+        };
+        let label = self.label(id);
+        let handle = self.obtain(class, label.as_deref())?;
+
+        let mut st = self.state.lock();
+        let tick = st.next_tick();
+        let Some(entry) = st.entries.get_mut(&id).filter(|e| matches!(e.residency, Residency::Host(_))) else {
+            // released (or restored) meanwhile
+            st.free.entry(class).or_default().push(handle);
+            st.stats.free_bytes += class;
+            return Ok(None);
+        };
+        entry.last_use = tick;
+        let size = entry.size as u64;
+        let Residency::Host(host) = std::mem::replace(&mut entry.residency, Residency::Device(handle.clone())) else {
+            unreachable!("checked above")
+        };
+        st.stats.live_bytes += class;
+        st.stats.spilled_bytes -= size;
+        st.stats.page_ins += 1;
+        st.bump_high_water();
+        Ok(Some((handle, host)))
+    }
+
+    /// Requested size of a buffer (its device buffer may be larger)
     pub fn get_buffer_size(&self, id: BufferId) -> Option<usize> {
-        self.entries.lock().get(&id).map(|e| e.size)
+        self.state.lock().entries.get(&id).map(|e| e.size)
     }
 
-    /// Explicitly release a buffer by its ID
+    /// Explicitly release a buffer by its ID, before its tokens are dropped.
+    /// It goes back to its size class's free list once in-flight GPU work has completed.
     pub fn release_buffer(&self, id: BufferId) {
-        self.entries.lock().remove(&id);
+        self.state.lock().release(id, &self.ctx.queue);
     }
 
-    /// Clear entries with only one reference (the one in the pool)
+    /// Recycle released buffers whose GPU work is done, then apply the trim policy.
+    /// (Allocations are released as soon as their last token is dropped.)
     pub fn clear_unused(&self) {
-        self.entries.lock().retain(|_, entry| entry.buffer.strong_count() > 1);
+        self.collect_pending();
+    }
+
+    /// Free recycled buffers until at most `max_free_bytes` remain; returns the bytes freed.
+    pub fn trim(&self, max_free_bytes: u64) -> u64 {
+        self.collect_pending();
+        self.state.lock().trim(max_free_bytes)
+    }
+
+    /// Poll the device (non-blocking) so completed releases become recyclable.
+    fn collect_pending(&self) {
+        if self.state.lock().pending.is_empty() {
+            return;
+        }
+        // a lost device never completes anything: pending releases are simply kept
+        let _ = self.ctx.poll();
+        self.state.lock().collect(self.config.max_free_bytes);
     }
 
+    pub fn stats(&self) -> PoolStats {
+        self.state.lock().stats
+    }
+
+    pub fn config(&self) -> PoolConfig { self.config }
+
     pub fn usage(&self) -> BufferKind { self.usage }
+
+    /// Size class serving a request of `size` bytes. Classes the device cannot allocate
+    /// (or bind) fall back to the exact size, rounded to the copy alignment.
+    pub fn class_of(&self, size: u64) -> u64 {
+        let min = self.config.min_class.max(wgpu::COPY_BUFFER_ALIGNMENT);
+        let class = if self.config.power_of_two {
+            size.max(min).next_power_of_two()
+        } else {
+            size.max(1).next_multiple_of(min)
+        };
+        let limits = self.ctx.device.limits();
+        let cap = match self.usage {
+            BufferKind::Main => limits.max_buffer_size.min(limits.max_storage_buffer_binding_size as u64),
+            BufferKind::Uniform => limits.max_buffer_size.min(limits.max_uniform_buffer_binding_size as u64),
+            BufferKind::Upload | BufferKind::Download => limits.max_buffer_size,
+        };
+        if class > cap && size <= cap {
+            size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        } else {
+            class
+        }
+    }
 }
 
 
@@ -82,11 +641,12 @@ mod tests {
         let usage = BufferKind::Main;
         let pool = BufferPool::new(ctx, usage);
 
-        let (id, _) = pool.alloc_buffer(1024).expect("Failed to allocate buffer");
+        let (id, _token) = pool.alloc_buffer(1024).expect("Failed to allocate buffer");
         assert!(pool.get(id).is_some(), "Buffer should be allocated");
 
         let size = pool.get_buffer_size(id).expect("Buffer size should be available");
         assert_eq!(size, 1024, "Expected buffer size to be 1024 bytes");
+        assert_eq!(pool.get(id).unwrap().as_raw().size(), 1024, "1024 is its own size class");
 
         assert_eq!(pool.usage(), usage, "Usage bitmap should match");
 
@@ -97,4 +657,107 @@ mod tests {
         pool.release_buffer(id);
         assert!(pool.get(id).is_none(), "Buffer should be released");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_released_buffers_are_recycled_by_size_class() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let config = PoolConfig { max_free_bytes: 4096, ..Default::default() };
+        let pool = BufferPool::with_config(ctx, BufferKind::Main, config);
+
+        // 300 and 500 bytes share the 512-byte class
+        let (a, tok_a) = pool.alloc_buffer(300).unwrap();
+        let raw_a = pool.get(a).unwrap();
+        assert_eq!(raw_a.as_raw().size(), 512);
+        drop(tok_a);
+        assert_eq!(pool.stats().free_bytes + pool.stats().pending_bytes, 512);
+
+        // a buffer still referenced elsewhere is not handed out again
+        let (b, _tok_b) = pool.alloc_buffer(500).unwrap();
+        assert_eq!(pool.stats().device_allocs, 2);
+        pool.release_buffer(b);
+        drop(raw_a);
+        let (c, _tok_c) = pool.alloc_buffer(500).unwrap();
+        assert_eq!(pool.stats().reuses, 1);
+        assert_eq!(pool.get_buffer_size(c), Some(500));
+
+        // beyond max_free_bytes, releases trim the free lists
+        let big: Vec<_> = (0..3).map(|_| pool.alloc_buffer(2048).unwrap()).collect();
+        let stats = pool.stats();
+        assert_eq!(stats.high_water, stats.live_bytes + stats.free_bytes + stats.pending_bytes);
+        drop(big);
+        pool.clear_unused();
+        assert!(pool.stats().free_bytes <= 4096);
+        assert!(pool.trim(0) > 0);
+        assert_eq!(pool.stats().free_bytes, 0);
+
+        // clear_unused collects completed releases
+        let (d, tok_d) = pool.alloc_buffer(100).unwrap();
+        drop(tok_d);
+        pool.ctx.device_poll(wgpu::PollType::Wait).unwrap();
+        pool.clear_unused();
+        assert!(pool.get(d).is_none());
+        assert_eq!(pool.stats().free_bytes, 256);
+    }
+
+    #[test]
+    fn test_slab_mode_carves_aligned_sub_ranges() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let slab = SlabConfig { block_size: 4096, max_alloc: 1024 };
+        let pool = BufferPool::with_config(ctx.clone(), BufferKind::Main, PoolConfig { slab: Some(slab), ..Default::default() });
+        let align = ctx.device.limits().min_storage_buffer_offset_alignment as u64;
+
+        // many small allocations share one backing buffer, each at an aligned offset
+        let small: Vec<_> = (0..4).map(|_| pool.alloc_buffer(100).unwrap()).collect();
+        let ranges: Vec<_> = small.iter().map(|(id, _)| pool.get(*id).unwrap()).collect();
+        assert!(ranges.iter().all(|r| r.as_raw().same_buffer(ranges[0].as_raw())));
+        assert!(ranges.iter().all(|r| r.as_raw().offset() % align == 0 && r.as_raw().size() == 256));
+        assert_eq!((pool.stats().device_allocs, pool.stats().slab_allocs), (1, 4));
+
+        // large classes get buffers of their own; a full block opens the next one
+        let (big, _big) = pool.alloc_buffer(2048).unwrap();
+        assert_eq!(pool.get(big).unwrap().as_raw().offset(), 0);
+        let more: Vec<_> = (0..16).map(|_| pool.alloc_buffer(256).unwrap()).collect();
+        assert_eq!(pool.stats().device_allocs, 3);
+
+        // released sub-ranges are recycled like any buffer
+        drop(ranges);
+        drop(small);
+        ctx.device_poll(wgpu::PollType::Wait).unwrap();
+        let (id, token) = pool.alloc_buffer(200).unwrap();
+        assert_eq!(pool.stats().reuses, 1);
+        assert_eq!(pool.get(id).unwrap().as_raw().size(), 256);
+
+        // blocks are counted whole, and freed with the last sub-range the pool holds
+        assert_eq!(pool.stats().reserved_bytes, 2 * slab.block_size + 2048);
+        drop(more);
+        ctx.device_poll(wgpu::PollType::Wait).unwrap();
+        pool.clear_unused();
+        assert_eq!(pool.trim(0), slab.block_size, "the first block still holds `id`");
+        assert_eq!(pool.stats().reserved_bytes, slab.block_size + 2048);
+        drop(token);
+        ctx.device_poll(wgpu::PollType::Wait).unwrap();
+        assert_eq!(pool.trim(0), slab.block_size);
+        assert_eq!(pool.stats().reserved_bytes, 2048);
+        assert_eq!(pool.stats().high_water, 2 * slab.block_size + 2048);
+    }
+
+    #[test]
+    fn test_dropping_the_last_token_releases_the_buffer() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let pool = BufferPool::new(ctx.clone(), BufferKind::Main);
+
+        let (id, token) = pool.alloc_buffer(64).unwrap();
+        let clone = token.clone();
+        drop(token);
+        assert!(pool.get(id).is_some(), "a clone is still alive");
+        drop(clone);
+        assert!(pool.get(id).is_none());
+        assert_eq!(pool.stats().live_bytes, 0);
+
+        // recycled once the work submitted before the release is done
+        ctx.device_poll(wgpu::PollType::Wait).unwrap();
+        let (_, _token) = pool.alloc_buffer(64).unwrap();
+        assert_eq!(pool.stats().reuses, 1);
+        assert_eq!(pool.stats().pending_bytes, 0);
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+use parking_lot::Mutex;
+
+use vknp_core::{CommandBatch, GpuContext, Result};
+use vknp_core::types::{AbstractBuffer, BufferHandle, BufferKind};
+
+/// Offsets inside a chunk: valid both for mapped writes and as copy sources
+const ALIGN: u64 = if wgpu::MAP_ALIGNMENT > wgpu::COPY_BUFFER_ALIGNMENT {
+    wgpu::MAP_ALIGNMENT
+} else {
+    wgpu::COPY_BUFFER_ALIGNMENT
+};
+
+/// One persistent upload buffer, mapped while it is being filled
+struct Chunk {
+    buffer: Arc<AbstractBuffer>,
+    used:   u64,
+}
+
+/// A queued copy from a chunk to its destination range
+struct PendingCopy {
+    src: AbstractBuffer,
+    dst: AbstractBuffer,
+}
+
+#[derive(Default)]
+struct BeltState {
+    /// mapped chunks being filled
+    active: Vec<Chunk>,
+    /// copies waiting for the next submission
+    copies: Vec<PendingCopy>,
+    /// unmapped chunks holding copies handed back by an abandoned batch
+    sealed: Vec<Chunk>,
+    /// chunks created so far
+    chunks: usize,
+}
+
+/// Persistent, pre-mapped upload ring (the equivalent of wgpu's `StagingBelt`).
+///
+/// Small uploads are written straight into mapped chunks and their copies queued;
+/// `flush_into` records them at the current point of a batch and unmaps the chunks,
+/// which are mapped again once that batch is submitted and reused when the GPU is
+/// done with them. A batch dropped without being submitted hands its copies back.
+/// Transfers larger than a chunk are refused: the caller falls back
+/// to a dedicated staging buffer.
+pub struct StagingBelt {
+    ctx:        GpuContext,
+    chunk_size: u64,
+    state:      Arc<Mutex<BeltState>>,
+    /// chunks mapped again, ready to be filled
+    recalled:   Arc<Mutex<Vec<Chunk>>>,
+}
+
+impl StagingBelt {
+    pub fn new(ctx: GpuContext, chunk_size: u64) -> Self {
+        Self {
+            ctx,
+            chunk_size: chunk_size.next_multiple_of(ALIGN),
+            state: Arc::new(Mutex::new(BeltState::default())),
+            recalled: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Largest upload the belt takes
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// Chunks created so far (each `chunk_size` bytes of device memory)
+    pub fn chunks(&self) -> usize {
+        self.state.lock().chunks
+    }
+
+    /// Uploads waiting for the next submission
+    pub fn pending(&self) -> usize {
+        self.state.lock().copies.len()
+    }
+
+    /// Queue the upload of `data` to `dst_offset` bytes into `dst`, padded to 4 bytes.
+    /// Returns `false` without doing anything if `data` does not fit in a chunk.
+    pub fn write(&self, dst: &BufferHandle, dst_offset: u64, data: &[u8]) -> Result<bool> {
+        let size = (data.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        if size > self.chunk_size {
+            return Ok(false);
+        }
+        let dst = dst.as_raw().sub_range(dst_offset, size)?;
+        let mut bytes = data.to_vec();
+        bytes.resize(size as usize, 0);
+
+        let mut st = self.state.lock();
+        let fits = |c: &Chunk| c.used.next_multiple_of(ALIGN) + size <= self.chunk_size;
+        let idx = match st.active.iter().position(fits) {
+            Some(idx) => idx,
+            None => {
+                let chunk = self.next_chunk(&mut st)?;
+                st.active.push(chunk);
+                st.active.len() - 1
+            }
+        };
+        let chunk = &mut st.active[idx];
+        let offset = chunk.used.next_multiple_of(ALIGN);
+        chunk.buffer.write_mapped(offset, &bytes);
+        chunk.used = offset + size;
+        let src = chunk.buffer.sub_range(offset, size)?;
+        st.copies.push(PendingCopy { src, dst });
+        Ok(true)
+    }
+
+    /// A recalled chunk if one is mapped again, a new one otherwise.
+    fn next_chunk(&self, st: &mut BeltState) -> Result<Chunk> {
+        if self.recalled.lock().is_empty() {
+            // remapping only progresses when the device is polled
+            let _ = self.ctx.poll();
+        }
+        if let Some(chunk) = self.recalled.lock().pop() {
+            return Ok(chunk);
+        }
+        let buffer = self.ctx.create_buffer_mapped(self.chunk_size, BufferKind::Upload, Some("vknp-staging-belt"))?;
+        st.chunks += 1;
+        Ok(Chunk { buffer: Arc::new(buffer), used: 0 })
+    }
+
+    /// Record every queued copy into `batch`, which must be submitted before the
+    /// destinations are used elsewhere. The chunks are mapped again after submission;
+    /// if `batch` is dropped instead, the copies are queued again (before newer ones).
+    pub fn flush_into(&self, batch: &mut CommandBatch) {
+        let (chunks, copies) = {
+            let mut st = self.state.lock();
+            if st.copies.is_empty() {
+                return;
+            }
+            let active = std::mem::take(&mut st.active);
+            for chunk in &active {
+                chunk.buffer.unmap();
+            }
+            let mut chunks = std::mem::take(&mut st.sealed);
+            chunks.extend(active);
+            (chunks, std::mem::take(&mut st.copies))
+        };
+        for copy in &copies {
+            batch.copy_buffer_to_buffer(&copy.src, &copy.dst, copy.src.size());
+        }
+
+        // whichever of submission and abandonment comes first gets the chunks and copies
+        let flushed = Arc::new(Mutex::new(Some((chunks, copies))));
+        let (state, taken) = (self.state.clone(), flushed.clone());
+        batch.if_abandoned(move || {
+            if let Some((chunks, mut copies)) = taken.lock().take() {
+                let mut st = state.lock();
+                copies.append(&mut st.copies);
+                st.copies = copies;
+                st.sealed.extend(chunks);
+            }
+        });
+        let recalled = self.recalled.clone();
+        batch.after_submit(move || {
+            let Some((active, copies)) = flushed.lock().take() else { return };
+            drop(copies);
+            for chunk in active {
+                let recalled = recalled.clone();
+                let buffer = chunk.buffer.clone();
+                // a failed mapping (device lost) simply retires the chunk
+                chunk.buffer.map_async(wgpu::MapMode::Write, move |res| {
+                    if res.is_ok() {
+                        recalled.lock().push(Chunk { buffer, used: 0 });
+                    }
+                });
+            }
+        });
+    }
+
+    /// Submit the queued copies on their own.
+    pub fn flush(&self) -> Result<()> {
+        if self.pending() == 0 {
+            return Ok(());
+        }
+        let mut batch = self.ctx.begin_batch("vknp-upload");
+        self.flush_into(&mut batch);
+        batch.submit()?;
+        Ok(())
+    }
+}
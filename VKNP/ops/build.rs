@@ -37,5 +37,5 @@ fn main() {
 
     // Tell cargo to rerun if files change
     println!("cargo:rerun-if-changed=../supported_types.yaml");
-    println!("cargo:rerun-if-changed=templates/data_types.jinja");
+    println!("cargo:rerun-if-changed=templates/tensor_any.jinja");
 }
@@ -3,7 +3,7 @@ use core_types::{DataType, ViewDescriptor, MAX_DIMS};
 
 use crate::op::Op;
 use crate::register_op;
-use crate::types::{OpSignature, ParamBuffer, GpuTask, PreparedOp, TensorAnyRef, RegistrationInfo};
+use crate::types::{OpSignature, ParamBuffer, GpuTask, LaunchConfig, PreparedOp, TensorAnyRef, RegistrationInfo};
 
 
 #[repr(C)]
@@ -13,7 +13,7 @@ struct ViewU {
     ndim:    u32,
     _pad0:   [u32; 2],
     shape:   [u32; MAX_DIMS],
-    strides: [u32; MAX_DIMS],
+    strides: [i32; MAX_DIMS],
 }
 
 #[repr(C)]
@@ -31,7 +31,7 @@ fn descriptor_to_uniform(v: &ViewDescriptor) -> ViewU {
     ViewU { offset: v.offset, ndim: v.ndim, _pad0: [0;2], shape: v.shape, strides: v.strides }
 }
 
-/// “add” f32+f32 → f32 (1 output)
+/// “add” f32+f32 → f32 (1 output), inputs broadcast to the output shape
 pub struct AddOp {
     sig: OpSignature,
 }
@@ -46,11 +46,19 @@ impl AddOp {
                 num_outputs:   1,
                 input_dtypes:  vec![ vec![dt], vec![dt] ],
                 output_dtypes: vec![ vec![dt] ],
+                broadcast:     true,
+                scalars:       vec![],
             },
         }
     }
 }
 
+impl Default for AddOp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RegistrationInfo for AddOp {
     const NAME: &'static str = "add";
 }
@@ -72,7 +80,7 @@ impl Op for AddOp {
         let mut total = 1u32;
         for d in 0..(c.view().ndim as usize) { total *= c.view().shape[d]; }
 
-        // param buffer (storage RO) : MetaU
+        // param buffer (uniform) : MetaU
         let meta = MetaU {
             a: descriptor_to_uniform(a.view()),
             b: descriptor_to_uniform(b.view()),
@@ -81,21 +89,24 @@ impl Op for AddOp {
             _pad1: [0;3],
             _tail_pad: [0;4],
         };
-        let param = ParamBuffer { bytes: bytemuck::bytes_of(&meta).to_vec() };
+        let param = ParamBuffer::uniform(bytemuck::bytes_of(&meta).to_vec());
 
         let (src, entry) = self.shader_template();
         let task = GpuTask {
+            op_name:         self.sig.name.to_string(),
+            device_id:       c.device_id(),
             pipeline_source: src.to_string(),
             entry_point:     entry.to_string(),
-            input_descs:     vec![ a.view().clone(), b.view().clone() ],
-            output_descs:    vec![ c.view().clone() ],
+            input_descs:     vec![ *a.view(), *b.view() ],
+            output_descs:    vec![ *c.view() ],
             input_types:     vec![ a.dtype(), b.dtype() ],
             output_types:    vec![ c.dtype() ],
             input_ids:       vec![ a.buffer_id(), b.buffer_id() ],
             output_ids:      vec![ c.buffer_id() ],
             params:          vec![param],
+            launch:          LaunchConfig::Auto,
         };
-        PreparedOp::Gpu(task)
+        PreparedOp::Gpu(Box::new(task))
     }
 
     fn shader_template(&self) -> (&'static str, &'static str) {
@@ -107,13 +118,15 @@ register_op!(AddOp);
 
 const ADD_WGSL: &str = r#"
 const MAX_DIMS : u32 = 8u;
+override WG_SIZE : u32 = 64u;
 
+// shape / strides are packed 4 per vec4 to satisfy uniform layout rules
 struct View {
   offset  : u32,
   ndim    : u32,
   _pad0   : vec2<u32>,
-  shape   : array<u32, MAX_DIMS>,
-  strides : array<u32, MAX_DIMS>,
+  shape   : array<vec4<u32>, MAX_DIMS / 4u>,
+  strides : array<vec4<i32>, MAX_DIMS / 4u>,
 };
 
 struct Meta {
@@ -126,28 +139,33 @@ struct Meta {
 
 @group(0) @binding(0) var<storage, read>  A         : array<f32>;
 @group(0) @binding(1) var<storage, read>  B         : array<f32>;
-@group(0) @binding(2) var<storage, read>  M         : Meta;
+var<param>                                M         : Meta;
 @group(0) @binding(3) var<storage, read_write> C    : array<f32>;
 
 fn linear_to_offsets(i: u32, v: View) -> u32 {
   var idx = i;
-  var off = v.offset;
+  var off = i32(v.offset);
   var d: i32 = i32(v.ndim) - 1;
   loop {
     if (d < 0) { break; }
     let du : u32 = u32(d);
-    let dim = v.shape[du];
+    let dim = v.shape[du / 4u][du % 4u];
     let coord = idx % dim;
     idx = idx / dim;
-    off = off + coord * v.strides[du]; // stride 0 -> broadcast
+    off = off + i32(coord) * v.strides[du / 4u][du % 4u]; // stride 0 -> broadcast, < 0 -> flipped
     d = d - 1;
   }
-  return off;
+  return u32(off);
 }
 
-@compute @workgroup_size(64)
-fn add_strided(@builtin(global_invocation_id) gid: vec3<u32>) {
-  let i = gid.x;
+@compute @workgroup_size(WG_SIZE)
+fn add_strided(
+  @builtin(workgroup_id) wid: vec3<u32>,
+  @builtin(num_workgroups) nwg: vec3<u32>,
+  @builtin(local_invocation_index) lid: u32,
+) {
+  // large tensors are dispatched on a 2-D / 3-D grid
+  let i = vknp_linear_index(wid, nwg, lid, WG_SIZE);
   if (i >= M.total_elems) { return; }
 
   let ai = linear_to_offsets(i, M.a);
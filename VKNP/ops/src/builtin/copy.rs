@@ -0,0 +1,81 @@
+use std::sync::LazyLock;
+use core_types::DataType;
+use memory::copy::{copy_params, copy_wgsl, COPY_ENTRY};
+
+use crate::op::Op;
+use crate::register_op;
+use crate::types::{OpSignature, ParamBuffer, GpuTask, LaunchConfig, PreparedOp, TensorAnyRef, RegistrationInfo};
+
+
+const ALL_DTYPES: [DataType; 3] = [DataType::F32, DataType::I32, DataType::U32];
+
+/// f32 → f32 instance of the kernel (the one `shader_template` reports)
+static COPY_F32_WGSL: LazyLock<String> = LazyLock::new(|| copy_wgsl(DataType::F32, DataType::F32));
+
+/// “copy”: strided copy of any view into any view of the same (broadcast) shape,
+/// converting between any two dtypes (1 output)
+pub struct CopyOp {
+    sig: OpSignature,
+}
+
+impl CopyOp {
+    pub fn new() -> Self {
+        Self {
+            sig: OpSignature {
+                name:          "copy",
+                num_inputs:    1,
+                num_outputs:   1,
+                input_dtypes:  vec![ ALL_DTYPES.to_vec() ],
+                output_dtypes: vec![ ALL_DTYPES.to_vec() ],
+                broadcast:     true,
+                scalars:       vec![],
+            },
+        }
+    }
+}
+
+impl Default for CopyOp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegistrationInfo for CopyOp {
+    const NAME: &'static str = "copy";
+}
+
+impl Op for CopyOp {
+    fn signature(&self) -> &OpSignature { &self.sig }
+
+    fn prepare(
+        &self,
+        inputs:  &[TensorAnyRef],
+        outputs: &[TensorAnyRef],
+    ) -> PreparedOp {
+        let (src, dst) = (&inputs[0], &outputs[0]);
+        let param = ParamBuffer::uniform(copy_params(src.view(), dst.view()));
+
+        // one kernel per pair of dtypes
+        let task = GpuTask {
+            op_name:         self.sig.name.to_string(),
+            device_id:       dst.device_id(),
+            pipeline_source: copy_wgsl(src.dtype(), dst.dtype()),
+            entry_point:     COPY_ENTRY.to_string(),
+            input_descs:     vec![ *src.view() ],
+            output_descs:    vec![ *dst.view() ],
+            input_types:     vec![ src.dtype() ],
+            output_types:    vec![ dst.dtype() ],
+            input_ids:       vec![ src.buffer_id() ],
+            output_ids:      vec![ dst.buffer_id() ],
+            params:          vec![param],
+            launch:          LaunchConfig::Auto,
+        };
+        PreparedOp::Gpu(Box::new(task))
+    }
+
+    fn shader_template(&self) -> (&'static str, &'static str) {
+        (COPY_F32_WGSL.as_str(), COPY_ENTRY)
+    }
+}
+
+register_op!(CopyOp);
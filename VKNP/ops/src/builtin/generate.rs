@@ -0,0 +1,171 @@
+use std::sync::LazyLock;
+use core_types::DataType;
+use memory::generate::{gen_params, gen_wgsl, Sequence, GEN_ENTRY};
+use vknp_core::VknpError;
+
+use crate::op::Op;
+use crate::register_op;
+use crate::types::{OpError, OpSignature, ParamBuffer, GpuTask, LaunchConfig, PreparedOp, TensorAnyRef, RegistrationInfo};
+
+
+const ALL_DTYPES: [DataType; 3] = [DataType::F32, DataType::I32, DataType::U32];
+
+/// f32 instance of the kernel (the one `shader_template` reports)
+static GEN_F32_WGSL: LazyLock<String> = LazyLock::new(|| gen_wgsl(DataType::F32));
+
+/// Task writing `seq` into the (only) output
+fn generate_task(name: &str, outputs: &[TensorAnyRef], seq: Sequence) -> PreparedOp {
+    let dst = &outputs[0];
+    let param = ParamBuffer::uniform(gen_params(&seq, dst.dtype(), dst.view()));
+    let task = GpuTask {
+        op_name:         name.to_string(),
+        device_id:       dst.device_id(),
+        pipeline_source: gen_wgsl(dst.dtype()),
+        entry_point:     GEN_ENTRY.to_string(),
+        input_descs:     vec![],
+        output_descs:    vec![ *dst.view() ],
+        input_types:     vec![],
+        output_types:    vec![ dst.dtype() ],
+        input_ids:       vec![],
+        output_ids:      vec![ dst.buffer_id() ],
+        params:          vec![param],
+        launch:          LaunchConfig::Auto,
+    };
+    PreparedOp::Gpu(Box::new(task))
+}
+
+/// Reject a scalar among `names` that is not a whole number: the kernel would
+/// silently truncate it
+fn check_whole<'a>(sig: &OpSignature, names: impl IntoIterator<Item = &'a str>, scalars: &[f64]) -> Result<(), OpError> {
+    for name in names {
+        let i = sig.scalars.iter().position(|&(n, _)| n == name).expect("a scalar of the op");
+        if scalars[i].fract() != 0.0 {
+            return Err(OpError::Invalid(VknpError::Op(format!(
+                "{}: `{name}` must be a whole number, found {}", sig.name, scalars[i]
+            ))));
+        }
+    }
+    Ok(())
+}
+
+/// A generator op: no input, one output of any dtype and shape, filled with the
+/// `Sequence` built from its scalars. The scalars listed in `whole` must be whole
+/// numbers, those in `whole_for_ints` only when the output has an integer dtype.
+macro_rules! generator_op {
+    (
+        $(#[$doc:meta])* $op:ident, $name:literal, [$(($scalar:literal, $default:expr)),*],
+        whole: [$($whole:literal),*], whole_for_ints: [$($whole_int:literal),*],
+        |$s:ident| $seq:expr
+    ) => {
+        $(#[$doc])*
+        pub struct $op {
+            sig: OpSignature,
+        }
+
+        impl $op {
+            pub fn new() -> Self {
+                Self {
+                    sig: OpSignature {
+                        name:          $name,
+                        num_inputs:    0,
+                        num_outputs:   1,
+                        input_dtypes:  vec![],
+                        output_dtypes: vec![ ALL_DTYPES.to_vec() ],
+                        broadcast:     false,
+                        scalars:       vec![ $(($scalar, $default)),* ],
+                    },
+                }
+            }
+        }
+
+        impl Default for $op {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl RegistrationInfo for $op {
+            const NAME: &'static str = $name;
+        }
+
+        impl Op for $op {
+            fn signature(&self) -> &OpSignature { &self.sig }
+
+            fn prepare(
+                &self,
+                inputs:  &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+            ) -> PreparedOp {
+                let defaults: Vec<f64> = self.sig.scalars.iter().map(|&(_, d)| d).collect();
+                self.prepare_with(inputs, outputs, &defaults)
+            }
+
+            fn prepare_with(
+                &self,
+                _inputs: &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+                $s:      &[f64],
+            ) -> PreparedOp {
+                generate_task(self.sig.name, outputs, $seq)
+            }
+
+            fn check_scalars(
+                &self,
+                _inputs: &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+                scalars: &[f64],
+            ) -> Result<(), OpError> {
+                let whole_for_ints: &[&str] = if outputs[0].dtype().is_float() { &[] } else { &[$($whole_int),*] };
+                check_whole(&self.sig, [$($whole),*].into_iter().chain(whole_for_ints.iter().copied()), scalars)
+            }
+
+            fn shader_template(&self) -> (&'static str, &'static str) {
+                (GEN_F32_WGSL.as_str(), GEN_ENTRY)
+            }
+        }
+
+        register_op!($op);
+    };
+}
+
+generator_op!(
+    /// “arange”: `start + i * step` at each row-major index `i`
+    ArangeOp, "arange", [("start", 0.0), ("step", 1.0)],
+    whole: [], whole_for_ints: ["start", "step"],
+    |s| Sequence::Arange { start: s[0], step: s[1] }
+);
+
+generator_op!(
+    /// “linspace”: evenly spaced from `start` to `stop`, the last value with `endpoint != 0`
+    LinspaceOp, "linspace", [("start", 0.0), ("stop", 1.0), ("endpoint", 1.0)],
+    whole: [], whole_for_ints: [],
+    |s| Sequence::Linspace { start: s[0], stop: s[1], endpoint: s[2] != 0.0 }
+);
+
+generator_op!(
+    /// “logspace”: `base` raised to the values of the matching “linspace”
+    LogspaceOp, "logspace", [("start", 0.0), ("stop", 1.0), ("endpoint", 1.0), ("base", 10.0)],
+    whole: [], whole_for_ints: [],
+    |s| Sequence::Logspace { start: s[0], stop: s[1], endpoint: s[2] != 0.0, base: s[3] }
+);
+
+generator_op!(
+    /// “eye”: ones on diagonal `k` of the last two dimensions
+    EyeOp, "eye", [("k", 0.0)],
+    whole: ["k"], whole_for_ints: [],
+    |s| Sequence::Eye { k: s[0] as i64 }
+);
+
+generator_op!(
+    /// “tril”: mask of ones on and below diagonal `k` of the last two dimensions
+    TrilOp, "tril", [("k", 0.0)],
+    whole: ["k"], whole_for_ints: [],
+    |s| Sequence::Tril { k: s[0] as i64 }
+);
+
+generator_op!(
+    /// “triu”: mask of ones on and above diagonal `k` of the last two dimensions
+    TriuOp, "triu", [("k", 0.0)],
+    whole: ["k"], whole_for_ints: [],
+    |s| Sequence::Triu { k: s[0] as i64 }
+);
@@ -1 +1,4 @@
-pub mod add;
\ No newline at end of file
+pub mod add;
+pub mod copy;
+pub mod generate;
+pub mod random;
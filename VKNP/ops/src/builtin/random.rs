@@ -0,0 +1,166 @@
+use std::sync::LazyLock;
+use core_types::DataType;
+use memory::philox::{rand_params, rand_wgsl, Distribution, RandomState, RAND_ENTRY};
+
+use crate::op::Op;
+use crate::register_op;
+use crate::types::{OpError, OpSignature, ParamBuffer, GpuTask, LaunchConfig, PreparedOp, TensorAnyRef, RegistrationInfo};
+
+
+/// f32 instance of the kernel (the one `shader_template` reports)
+static RAND_F32_WGSL: LazyLock<String> = LazyLock::new(|| rand_wgsl(DataType::F32, None));
+
+/// Scalars every random op ends with: where its draw starts in the Philox sequence.
+/// Callers move `offset` past `Distribution::blocks` between draws.
+const STATE_SCALARS: [(&str, f64); 3] = [("seed", 0.0), ("stream", 0.0), ("offset", 0.0)];
+
+/// Signature of a random op over `dtypes`, with the distribution's scalars first
+fn random_signature(name: &'static str, num_inputs: usize, dtypes: &[DataType], scalars: &[(&'static str, f64)]) -> OpSignature {
+    OpSignature {
+        name,
+        num_inputs,
+        num_outputs:   1,
+        input_dtypes:  vec![ DataType::ALL.to_vec(); num_inputs ],
+        output_dtypes: vec![ dtypes.to_vec() ],
+        broadcast:     false,
+        scalars:       scalars.iter().chain(&STATE_SCALARS).copied().collect(),
+    }
+}
+
+/// Task drawing `dist` into the (only) output, sampling the (optional) input; the last
+/// three `scalars` are the state
+fn random_task(name: &str, inputs: &[TensorAnyRef], outputs: &[TensorAnyRef], dist: Distribution, scalars: &[f64]) -> PreparedOp {
+    let dst = &outputs[0];
+    let src = inputs.first();
+    let [seed, stream, offset] = scalars[scalars.len() - 3..] else { unreachable!() };
+    let state = RandomState { seed: seed as u64, stream: stream as u64, offset: offset as u64 };
+    let param = ParamBuffer::uniform(rand_params(&dist, &state, dst.view(), src.map(|s| s.view())));
+    let task = GpuTask {
+        op_name:         name.to_string(),
+        device_id:       dst.device_id(),
+        pipeline_source: rand_wgsl(dst.dtype(), src.map(|s| s.dtype())),
+        entry_point:     RAND_ENTRY.to_string(),
+        input_descs:     src.iter().map(|s| *s.view()).collect(),
+        output_descs:    vec![ *dst.view() ],
+        input_types:     src.iter().map(|s| s.dtype()).collect(),
+        output_types:    vec![ dst.dtype() ],
+        input_ids:       src.iter().map(|s| s.buffer_id()).collect(),
+        output_ids:      vec![ dst.buffer_id() ],
+        params:          vec![param],
+        launch:          LaunchConfig::Auto,
+    };
+    PreparedOp::Gpu(Box::new(task))
+}
+
+/// Number of elements of a tensor
+fn numel(t: &TensorAnyRef) -> u32 {
+    t.shape().iter().product::<usize>() as u32
+}
+
+/// A random op: `num_inputs` inputs of any dtype, one output of `dtypes`, filled with
+/// the `Distribution` built from its scalars (and its tensors)
+macro_rules! random_op {
+    ($(#[$doc:meta])* $op:ident, $name:literal, $num_inputs:literal, $dtypes:expr,
+     [$(($scalar:literal, $default:expr)),*], |$i:ident, $o:ident, $s:ident| $dist:expr) => {
+        $(#[$doc])*
+        pub struct $op {
+            sig: OpSignature,
+        }
+
+        impl $op {
+            pub fn new() -> Self {
+                Self { sig: random_signature($name, $num_inputs, &$dtypes, &[ $(($scalar, $default)),* ]) }
+            }
+
+            fn distribution(&self, $i: &[TensorAnyRef], $o: &[TensorAnyRef], $s: &[f64]) -> Distribution {
+                $dist
+            }
+        }
+
+        impl Default for $op {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl RegistrationInfo for $op {
+            const NAME: &'static str = $name;
+        }
+
+        impl Op for $op {
+            fn signature(&self) -> &OpSignature { &self.sig }
+
+            fn prepare(
+                &self,
+                inputs:  &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+            ) -> PreparedOp {
+                let defaults: Vec<f64> = self.sig.scalars.iter().map(|&(_, d)| d).collect();
+                self.prepare_with(inputs, outputs, &defaults)
+            }
+
+            fn prepare_with(
+                &self,
+                inputs:  &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+                scalars: &[f64],
+            ) -> PreparedOp {
+                let dist = self.distribution(inputs, outputs, scalars);
+                random_task(self.sig.name, inputs, outputs, dist, scalars)
+            }
+
+            fn check_scalars(
+                &self,
+                inputs:  &[TensorAnyRef],
+                outputs: &[TensorAnyRef],
+                scalars: &[f64],
+            ) -> Result<(), OpError> {
+                let dist = self.distribution(inputs, outputs, scalars);
+                dist.validate(numel(&outputs[0]) as u64).map_err(OpError::Invalid)
+            }
+
+            fn shader_template(&self) -> (&'static str, &'static str) {
+                (RAND_F32_WGSL.as_str(), RAND_ENTRY)
+            }
+        }
+
+        register_op!($op);
+    };
+}
+
+random_op!(
+    /// “uniform”: values uniform over `[low, high)`
+    UniformOp, "uniform", 0, [DataType::F32], [("low", 0.0), ("high", 1.0)],
+    |_i, _o, s| Distribution::Uniform { low: s[0], high: s[1] }
+);
+
+random_op!(
+    /// “normal”: Gaussian values of mean `mean` and standard deviation `std`
+    NormalOp, "normal", 0, [DataType::F32], [("mean", 0.0), ("std", 1.0)],
+    |_i, _o, s| Distribution::Normal { mean: s[0], std: s[1] }
+);
+
+random_op!(
+    /// “randint”: integers uniform over `[low, high)`
+    RandintOp, "randint", 0, [DataType::I32, DataType::U32], [("low", 0.0), ("high", 2.0)],
+    |_i, _o, s| Distribution::Randint { low: s[0] as i64, high: s[1] as i64 }
+);
+
+random_op!(
+    /// “bernoulli”: 1 with probability `p`, 0 otherwise
+    BernoulliOp, "bernoulli", 0, DataType::ALL, [("p", 0.5)],
+    |_i, _o, s| Distribution::Bernoulli { p: s[0] }
+);
+
+random_op!(
+    /// “permutation”: the integers `0..n` in a random order, `n` being the size of the output
+    PermutationOp, "permutation", 0, DataType::ALL, [],
+    |_i, o, _s| Distribution::Permutation { n: numel(&o[0]) }
+);
+
+random_op!(
+    /// “choice”: elements of the input (in row-major order) picked at random, with or
+    /// without `replace`ment; without, the output must not outnumber the input
+    ChoiceOp, "choice", 1, DataType::ALL, [("replace", 1.0)],
+    |i, _o, s| Distribution::Choice { n: numel(&i[0]), replace: s[0] != 0.0 }
+);
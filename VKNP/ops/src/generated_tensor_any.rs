@@ -21,6 +21,137 @@ impl<'a> TensorAnyRef<'a> {
             TensorAnyRef::U32(t) => t.view(),
         }
     }
+
+    pub fn device_id(&self) -> usize {
+        match self {
+            TensorAnyRef::F32(t) => t.device_id(),
+            TensorAnyRef::I32(t) => t.device_id(),
+            TensorAnyRef::U32(t) => t.device_id(),
+        }
+    }
+
+    pub fn buffer_id(&self) -> BufferId {
+        match self {
+            TensorAnyRef::F32(t) => t.buffer_id(),
+            TensorAnyRef::I32(t) => t.buffer_id(),
+            TensorAnyRef::U32(t) => t.buffer_id(),
+        }
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        match self {
+            TensorAnyRef::F32(t) => t.shape(),
+            TensorAnyRef::I32(t) => t.shape(),
+            TensorAnyRef::U32(t) => t.shape(),
+        }
+    }
+
+    /// Zero-stride view of the tensor stretched to `shape`
+    pub fn broadcast_to(&self, shape: &[usize]) -> Result<TensorAny, VknpError> {
+        Ok(match self {
+            TensorAnyRef::F32(t) => TensorAny::F32(t.broadcast_to(shape)?),
+            TensorAnyRef::I32(t) => TensorAny::I32(t.broadcast_to(shape)?),
+            TensorAnyRef::U32(t) => TensorAny::U32(t.broadcast_to(shape)?),
+        })
+    }
+}
+
+/// Owned counterpart of `TensorAnyRef`, e.g. outputs allocated by `OpRegistry::prepare_alloc`
+pub enum TensorAny {
+    F32(Tensor<f32>),
+    I32(Tensor<i32>),
+    U32(Tensor<u32>),
+}
+
+impl TensorAny {
+    /// Uninitialised tensor of a runtime `dtype`
+    pub fn empty(mgr: &MemoryManager, dtype: DataType, shape: &[usize], device_id: usize) -> Result<Self, VknpError> {
+        Ok(match dtype {
+            DataType::F32 => TensorAny::F32(Tensor::try_empty(mgr, shape, device_id)?),
+            DataType::I32 => TensorAny::I32(Tensor::try_empty(mgr, shape, device_id)?),
+            DataType::U32 => TensorAny::U32(Tensor::try_empty(mgr, shape, device_id)?),
+        })
+    }
+
+    pub fn as_tensor_ref(&self) -> TensorAnyRef<'_> {
+        match self {
+            TensorAny::F32(t) => TensorAnyRef::F32(t),
+            TensorAny::I32(t) => TensorAnyRef::I32(t),
+            TensorAny::U32(t) => TensorAnyRef::U32(t),
+        }
+    }
+
+    pub fn dtype(&self) -> DataType {
+        self.as_tensor_ref().dtype()
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.as_tensor_ref().shape()
+    }
+}
+
+
+impl From<Tensor<f32>> for TensorAny {
+    fn from(t: Tensor<f32>) -> Self {
+        TensorAny::F32(t)
+    }
+}
+
+impl TryFrom<TensorAny> for Tensor<f32> {
+    type Error = VknpError;
+
+    fn try_from(t: TensorAny) -> Result<Self, VknpError> {
+        match t {
+            TensorAny::F32(t) => Ok(t),
+            other => Err(VknpError::Dtype {
+                context: "TensorAny conversion".to_string(),
+                expected: vec![DataType::F32],
+                found: other.dtype(),
+            }),
+        }
+    }
+}
+
+impl From<Tensor<i32>> for TensorAny {
+    fn from(t: Tensor<i32>) -> Self {
+        TensorAny::I32(t)
+    }
+}
+
+impl TryFrom<TensorAny> for Tensor<i32> {
+    type Error = VknpError;
+
+    fn try_from(t: TensorAny) -> Result<Self, VknpError> {
+        match t {
+            TensorAny::I32(t) => Ok(t),
+            other => Err(VknpError::Dtype {
+                context: "TensorAny conversion".to_string(),
+                expected: vec![DataType::I32],
+                found: other.dtype(),
+            }),
+        }
+    }
+}
+
+impl From<Tensor<u32>> for TensorAny {
+    fn from(t: Tensor<u32>) -> Self {
+        TensorAny::U32(t)
+    }
+}
+
+impl TryFrom<TensorAny> for Tensor<u32> {
+    type Error = VknpError;
+
+    fn try_from(t: TensorAny) -> Result<Self, VknpError> {
+        match t {
+            TensorAny::U32(t) => Ok(t),
+            other => Err(VknpError::Dtype {
+                context: "TensorAny conversion".to_string(),
+                expected: vec![DataType::U32],
+                found: other.dtype(),
+            }),
+        }
+    }
 }
 
 
@@ -3,7 +3,9 @@ pub mod types;
 pub mod builtin;
 
 use std::collections::HashMap;
-use types::{PreparedOp, TensorAnyRef, OpError, RegistrationInfo};
+use core_types::DataType;
+use memory::MemoryManager;
+use types::{PreparedOp, TensorAny, TensorAnyRef, OpError, RegistrationInfo};
 use op::{Op, OpFactory};
 
 
@@ -26,6 +28,12 @@ pub struct OpRegistry {
     map: HashMap<&'static str, Box<dyn Op>>,
 }
 
+impl Default for OpRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OpRegistry {
     pub fn new() -> Self {
         Self { map: HashMap::new() }
@@ -49,59 +57,119 @@ impl OpRegistry {
         self.map.insert(name, op);
     }
 
-    /// Lookup + validate arity & dtypes + prepare in one call
+    /// Lookup + validate arity, dtypes, devices & shapes + prepare in one call.
+    /// The inputs of a broadcasting op are passed to `prepare` as views stretched to the
+    /// output shape.
     pub fn check_and_prepare<'a>(
         &self,
         name:    &str,
         inputs:  &[TensorAnyRef<'a>],
         outputs: &[TensorAnyRef<'a>],
     ) -> Result<PreparedOp, OpError> {
-        let op = self.map.get(name)
-            .ok_or(OpError::UnknownOp(name.to_string()))?;
+        self.check_and_prepare_with(name, inputs, outputs, &[])
+    }
+
+    /// `check_and_prepare` passing leading scalar arguments to the op; the ones left out
+    /// take the default of its signature.
+    pub fn check_and_prepare_with<'a>(
+        &self,
+        name:    &str,
+        inputs:  &[TensorAnyRef<'a>],
+        outputs: &[TensorAnyRef<'a>],
+        scalars: &[f64],
+    ) -> Result<PreparedOp, OpError> {
+        let op = self.lookup(name)?;
         let sig = op.signature();
+        check_tensors(name, inputs, sig.num_inputs, &sig.input_dtypes)?;
+        check_tensors(name, outputs, sig.num_outputs, &sig.output_dtypes)?;
 
-        // inputs
-        if inputs.len() != sig.num_inputs {
-            return Err(OpError::ArityMismatch {
+        if scalars.len() > sig.scalars.len() {
+            return Err(OpError::ScalarArity {
                 op: name.to_string(),
-                expected: sig.num_inputs,
-                found: inputs.len(),
+                expected: sig.scalars.len(),
+                found: scalars.len(),
             });
         }
-        for (i, t) in inputs.iter().enumerate() {
-            let dt = t.dtype();
-            if !sig.input_dtypes[i].contains(&dt) {
-                return Err(OpError::DtypeMismatch {
-                    op: name.to_string(),
-                    index: i,
-                    expected: sig.input_dtypes[i].clone(),
-                    found: dt,
-                });
+        let scalars: Vec<f64> = sig.scalars.iter().enumerate()
+            .map(|(i, &(_, default))| scalars.get(i).copied().unwrap_or(default))
+            .collect();
+        op.check_scalars(inputs, outputs, &scalars)?;
+
+        // every tensor must live on the same device
+        if let Some(first) = inputs.iter().chain(outputs.iter()).next() {
+            let device = first.device_id();
+            for t in inputs.iter().chain(outputs.iter()) {
+                if t.device_id() != device {
+                    return Err(OpError::DeviceMismatch {
+                        op: name.to_string(),
+                        expected: device,
+                        found: t.device_id(),
+                    });
+                }
             }
         }
 
-        // outputs
-        if outputs.len() != sig.num_outputs {
-            return Err(OpError::ArityMismatch {
-                op: name.to_string(),
-                expected: sig.num_outputs,
-                found: outputs.len(),
-            });
-        }
-        for (i, t) in outputs.iter().enumerate() {
-            let dt = t.dtype();
-            if !sig.output_dtypes[i].contains(&dt) {
-                return Err(OpError::DtypeMismatch {
-                    op: name.to_string(),
-                    index: i,
-                    expected: sig.output_dtypes[i].clone(),
-                    found: dt,
-                });
+        // broadcasting ops: the inputs must broadcast together, to the shape of the outputs
+        if sig.broadcast {
+            let shapes = op.output_shapes(inputs)?;
+            for (t, expected) in outputs.iter().zip(&shapes) {
+                if t.shape() != *expected {
+                    return Err(OpError::ShapeMismatch {
+                        op: name.to_string(),
+                        shapes: outputs.iter().map(|t| t.shape()).collect(),
+                        expected: Some(expected.clone()),
+                    });
+                }
+            }
+            if let Some(target) = shapes.first() {
+                let expanded = inputs.iter()
+                    .map(|t| t.broadcast_to(target))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| OpError::ShapeMismatch {
+                        op: name.to_string(),
+                        shapes: inputs.iter().map(|t| t.shape()).collect(),
+                        expected: Some(target.clone()),
+                    })?;
+                let expanded: Vec<TensorAnyRef> = expanded.iter().map(TensorAny::as_tensor_ref).collect();
+                return Ok(op.prepare_with(&expanded, outputs, &scalars));
             }
         }
 
         // prepare the operation
-        Ok(op.prepare(&inputs, &outputs))
+        Ok(op.prepare_with(inputs, outputs, &scalars))
+    }
+
+    /// `check_and_prepare`, with outputs allocated in `mm` (the memory of the inputs'
+    /// device) with the shapes the op infers. An output takes the dtype of the first
+    /// input when its signature allows it, its first allowed dtype otherwise.
+    pub fn prepare_alloc(
+        &self,
+        name:   &str,
+        inputs: &[TensorAnyRef],
+        mm:     &MemoryManager,
+    ) -> vknp_core::Result<(PreparedOp, Vec<TensorAny>)> {
+        let op = self.lookup(name)?;
+        let sig = op.signature();
+        check_tensors(name, inputs, sig.num_inputs, &sig.input_dtypes)?;
+
+        let device = inputs.first().map_or(0, |t| t.device_id());
+        let outputs = op.output_shapes(inputs)?.iter().zip(&sig.output_dtypes)
+            .map(|(shape, dtypes)| {
+                let dtype = inputs.first().map(|t| t.dtype()).filter(|dt| dtypes.contains(dt)).unwrap_or(dtypes[0]);
+                TensorAny::empty(mm, dtype, shape, device)
+            })
+            .collect::<vknp_core::Result<Vec<_>>>()?;
+        let prepared = {
+            let refs: Vec<TensorAnyRef> = outputs.iter().map(TensorAny::as_tensor_ref).collect();
+            self.check_and_prepare(name, inputs, &refs)?
+        };
+        Ok((prepared, outputs))
+    }
+
+    fn lookup(&self, name: &str) -> Result<&dyn Op, OpError> {
+        self.map.get(name)
+            .map(|b| b.as_ref())
+            .ok_or(OpError::UnknownOp(name.to_string()))
     }
 
     /// lookup sans validation
@@ -110,6 +178,29 @@ impl OpRegistry {
     }
 }
 
+/// Arity of `tensors`, then the dtype of each against `dtypes`.
+fn check_tensors(name: &str, tensors: &[TensorAnyRef], expected: usize, dtypes: &[Vec<DataType>]) -> Result<(), OpError> {
+    if tensors.len() != expected {
+        return Err(OpError::ArityMismatch {
+            op: name.to_string(),
+            expected,
+            found: tensors.len(),
+        });
+    }
+    for (i, t) in tensors.iter().enumerate() {
+        let dt = t.dtype();
+        if !dtypes[i].contains(&dt) {
+            return Err(OpError::DtypeMismatch {
+                op: name.to_string(),
+                index: i,
+                expected: dtypes[i].clone(),
+                found: dt,
+            });
+        }
+    }
+    Ok(())
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -123,7 +214,7 @@ mod tests {
     fn registry_and_addop() {
         // setup GPU & memory manager
         let ctx = block_on(GpuContext::new()).unwrap();
-        let mut mm = MemoryManager::new(ctx);
+        let mm = MemoryManager::new(ctx);
 
         // register all available ops
         let mut reg = OpRegistry::new();
@@ -137,9 +228,9 @@ mod tests {
 
         // prepare with three f32 tensors
         let shape = [4];
-        let t1 = Tensor::<f32>::empty(&mut mm, &shape, 0);
-        let t2 = Tensor::<f32>::empty(&mut mm, &shape, 0);
-        let t3 = Tensor::<f32>::empty(&mut mm, &shape, 0);
+        let t1 = Tensor::<f32>::empty(&mm, &shape, 0);
+        let t2 = Tensor::<f32>::empty(&mm, &shape, 0);
+        let t3 = Tensor::<f32>::empty(&mm, &shape, 0);
 
         let inputs = vec![ TensorAnyRef::F32(&t1), TensorAnyRef::F32(&t2) ];
         let outputs = vec![ TensorAnyRef::F32(&t3) ];
@@ -155,6 +246,14 @@ mod tests {
             _ => panic!("AddOp should produce a single GpuTask"),
         }
 
+        // [3] + [4] do not broadcast, and outputs must have the broadcast shape
+        let t4 = Tensor::<f32>::empty(&mm, &[3], 0);
+        let err = reg.check_and_prepare("add", &[(&t1).into(), (&t4).into()], &[(&t3).into()]).unwrap_err();
+        assert!(matches!(err, OpError::ShapeMismatch { expected: None, .. }), "{err}");
+        let row = Tensor::<f32>::empty(&mm, &[2, 4], 0);
+        let err = reg.check_and_prepare("add", &[(&t1).into(), (&row).into()], &[(&t3).into()]).unwrap_err();
+        assert!(matches!(err, OpError::ShapeMismatch { expected: Some(ref s), .. } if *s == vec![2, 4]), "{err}");
+
         // requesting unknown op errors
         let err = reg.check_and_prepare("extremely_strange_op", &[], &[]).unwrap_err();
         match err {
@@ -1,4 +1,6 @@
-use crate::types::{OpSignature, PreparedOp, TensorAnyRef};
+use tensor::broadcast::broadcast_shapes;
+
+use crate::types::{OpError, OpSignature, PreparedOp, TensorAnyRef};
 
 
 /// Trait to implement for each Op
@@ -13,8 +15,47 @@ pub trait Op: Send + Sync {
         outputs: &[TensorAnyRef]
     ) -> PreparedOp;
 
+    /// `prepare`, given the scalar arguments declared in the signature (defaults filled in).
+    /// Ops without scalars keep this default.
+    fn prepare_with(
+        &self,
+        inputs:   &[TensorAnyRef],
+        outputs:  &[TensorAnyRef],
+        _scalars: &[f64],
+    ) -> PreparedOp {
+        self.prepare(inputs, outputs)
+    }
+
+    /// Reject scalar arguments (defaults filled in) that are invalid for these tensors,
+    /// before `prepare_with` is called.
+    fn check_scalars(
+        &self,
+        _inputs:  &[TensorAnyRef],
+        _outputs: &[TensorAnyRef],
+        _scalars: &[f64],
+    ) -> Result<(), OpError> {
+        Ok(())
+    }
+
     /// For a simple GPU kernel, return WGSL source + entry point
     fn shader_template(&self) -> (&'static str, &'static str);
+
+    /// Shape of each output for these inputs. By default, for broadcasting ops, every
+    /// output has the broadcast shape of the inputs; other ops must override it to have
+    /// their outputs allocated by `OpRegistry::prepare_alloc`.
+    fn output_shapes(&self, inputs: &[TensorAnyRef]) -> Result<Vec<Vec<usize>>, OpError> {
+        let sig = self.signature();
+        if !sig.broadcast {
+            return Err(OpError::NoShapeInference(sig.name.to_string()));
+        }
+        let shapes: Vec<Vec<usize>> = inputs.iter().map(|t| t.shape()).collect();
+        let shape = broadcast_shapes(&shapes).ok_or_else(|| OpError::ShapeMismatch {
+            op: sig.name.to_string(),
+            shapes: shapes.clone(),
+            expected: None,
+        })?;
+        Ok(vec![shape; sig.num_outputs])
+    }
 }
 
 
@@ -1,5 +1,10 @@
+use std::fmt;
+
 use core_types::{BufferId, DataType, ViewDescriptor};
+use memory::MemoryManager;
 use tensor::Tensor;
+use vknp_core::VknpError;
+pub use vknp_core::types::ParamBinding;
 
 include!("generated_tensor_any.rs");
 
@@ -8,6 +13,7 @@ include!("generated_tensor_any.rs");
 /// - number of tensor inputs
 /// - allowed DataTypes per tensor input
 /// - expected output DataTypes
+/// - whether the inputs broadcast against each other (element-wise ops)
 #[derive(Debug, Clone)]
 pub struct OpSignature {
     pub name:           &'static str,
@@ -15,15 +21,45 @@ pub struct OpSignature {
     pub num_outputs:    usize,
     pub input_dtypes:   Vec<Vec<DataType>>,
     pub output_dtypes:  Vec<Vec<DataType>>,
+    /// Inputs are broadcast to the shape of the outputs, which is their broadcast shape
+    pub broadcast:      bool,
+    /// Named scalar arguments passed to `Op::prepare_with`, with their default value
+    pub scalars:        Vec<(&'static str, f64)>,
 }
 
-/// Simple abstraction for structures/constants that will be pushed before an operation
+/// Simple abstraction for structures/constants that will be pushed before an operation.
+/// The shader declares it as `var<param> NAME : TYPE;`, rewritten according to `binding`.
+/// A `PushConstant` param falls back to `Uniform` when the device can't push it, so its
+/// type must also respect uniform layout rules.
 #[derive(Debug, Clone)]
-pub struct ParamBuffer { pub bytes: Vec<u8> }
+pub struct ParamBuffer {
+    pub bytes:   Vec<u8>,
+    pub binding: ParamBinding,
+}
+
+impl ParamBuffer {
+    pub fn push_constant(bytes: Vec<u8>) -> Self { Self { bytes, binding: ParamBinding::PushConstant } }
+    pub fn uniform(bytes: Vec<u8>) -> Self { Self { bytes, binding: ParamBinding::Uniform } }
+    pub fn storage(bytes: Vec<u8>) -> Self { Self { bytes, binding: ParamBinding::Storage } }
+}
+
+/// How the workgroup size of a task is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LaunchConfig {
+    /// Picked by the device's tuner. The kernel must declare `override WG_SIZE: u32`
+    /// and use `@workgroup_size(WG_SIZE)`.
+    #[default]
+    Auto,
+    /// Always this size: either the literal of `@workgroup_size(..)`,
+    /// or the value given to `WG_SIZE` when the kernel declares it.
+    Fixed(u32),
+}
 
 /// A GPU “kernel” ready to bind & dispatch
 #[derive(Debug, Clone)]
 pub struct GpuTask {
+    pub op_name:            String,
+    pub device_id:          usize,
     pub pipeline_source:    String,
     pub entry_point:        String,
     pub input_descs:        Vec<ViewDescriptor>,
@@ -33,22 +69,81 @@ pub struct GpuTask {
     pub input_ids:          Vec<BufferId>,
     pub output_ids:         Vec<BufferId>,
     pub params:             Vec<ParamBuffer>,
+    pub launch:             LaunchConfig,
 }
 
 /// Result of preparing an Op: either a single GPU kernel
 /// or a sequence of sub-ops (for composites like FFT)
 #[derive(Debug, Clone)]
 pub enum PreparedOp {
-    Gpu(GpuTask),
+    Gpu(Box<GpuTask>),
     Composite(Vec<PreparedOp>),
 }
 
+impl PreparedOp {
+    /// Device of the first GPU task, if any
+    pub fn device_id(&self) -> Option<usize> {
+        match self {
+            PreparedOp::Gpu(task) => Some(task.device_id),
+            PreparedOp::Composite(ops) => ops.iter().find_map(|op| op.device_id()),
+        }
+    }
+}
+
 /// Errors during signature validation
 #[derive(Debug)]
 pub enum OpError {
     UnknownOp(String),
     ArityMismatch { op: String, expected: usize, found: usize },
     DtypeMismatch  { op: String, index: usize, expected: Vec<DataType>, found: DataType },
+    DeviceMismatch { op: String, expected: usize, found: usize },
+    /// Inputs that do not broadcast together, or an output of the wrong shape
+    ShapeMismatch  { op: String, shapes: Vec<Vec<usize>>, expected: Option<Vec<usize>> },
+    /// The op cannot allocate its outputs: they must be passed
+    NoShapeInference(String),
+    /// More scalar arguments than the op declares
+    ScalarArity    { op: String, expected: usize, found: usize },
+    /// Scalars the op rejects for these tensors (`Op::check_scalars`)
+    Invalid(VknpError),
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpError::UnknownOp(name) => write!(f, "unknown op `{}`", name),
+            OpError::ArityMismatch { op, expected, found } =>
+                write!(f, "`{}` expects {} tensors, found {}", op, expected, found),
+            OpError::DtypeMismatch { op, index, expected, found } =>
+                write!(f, "`{}` tensor {}: expected one of {:?}, found {:?}", op, index, expected, found),
+            OpError::DeviceMismatch { op, expected, found } =>
+                write!(f, "`{}` tensors live on devices {} and {}", op, expected, found),
+            OpError::ShapeMismatch { op, shapes, expected: None } =>
+                write!(f, "`{}` input shapes {:?} do not broadcast together", op, shapes),
+            OpError::ShapeMismatch { op, shapes, expected: Some(expected) } =>
+                write!(f, "`{}` outputs {:?} should have shape {:?}", op, shapes, expected),
+            OpError::NoShapeInference(op) =>
+                write!(f, "`{}` cannot infer the shapes of its outputs", op),
+            OpError::ScalarArity { op, expected, found } =>
+                write!(f, "`{}` takes at most {} scalars, found {}", op, expected, found),
+            OpError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OpError {}
+
+impl From<OpError> for VknpError {
+    fn from(e: OpError) -> Self {
+        match e {
+            OpError::DtypeMismatch { op, index, expected, found } =>
+                VknpError::Dtype { context: format!("`{}` tensor {}", op, index), expected, found },
+            OpError::DeviceMismatch { expected, found, .. } =>
+                VknpError::DeviceMismatch { expected, found },
+            OpError::ShapeMismatch { .. } => VknpError::Shape(e.to_string()),
+            OpError::Invalid(e) => e,
+            other => VknpError::Op(other.to_string()),
+        }
+    }
 }
 
 /// Trait to implement for each Op to work with inventory
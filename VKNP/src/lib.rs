@@ -1,6 +1,9 @@
 // Re-exports all submodules
+#[allow(ambiguous_glob_reexports)] // `core::random` clashes with `tensor::random`, named below
 pub use core::*;
 pub use memory::*;
 pub use tensor::*;
 pub use ops::*;
-pub use execution::*;
\ No newline at end of file
+pub use execution::*;
+
+pub use tensor::random;
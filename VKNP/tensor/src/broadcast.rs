@@ -0,0 +1,98 @@
+//! NumPy-style broadcasting: shapes are aligned on their last dimension, and each
+//! dimension must either match or be 1 (stretched with a zero stride).
+
+use vknp_core::Result;
+use core_types::{Element, MAX_DIMS};
+
+use crate::Tensor;
+use crate::view::shape_error;
+
+/// Shape every one of `shapes` broadcasts to, or `None` if two of them are incompatible.
+pub fn broadcast_shapes<S: AsRef<[usize]>>(shapes: &[S]) -> Option<Vec<usize>> {
+    let ndim = shapes.iter().map(|s| s.as_ref().len()).max().unwrap_or(0);
+    let mut out = vec![1usize; ndim];
+    for shape in shapes {
+        let shape = shape.as_ref();
+        for (o, &d) in out[ndim - shape.len()..].iter_mut().zip(shape) {
+            match (*o, d) {
+                (a, b) if a == b => {}
+                (1, b) => *o = b,
+                (_, 1) => {}
+                _ => return None,
+            }
+        }
+    }
+    Some(out)
+}
+
+impl<T: Element> Tensor<T> {
+    /// View of this tensor stretched to `shape`: leading dimensions are added and
+    /// dimensions of size 1 repeated, all with a zero stride (no copy).
+    pub fn broadcast_to(&self, shape: &[usize]) -> Result<Self> {
+        self.derive(|d| {
+            if shape.len() > MAX_DIMS {
+                return Err(shape_error(format!("{} dimensions given, at most {} supported", shape.len(), MAX_DIMS)));
+            }
+            let incompatible = || shape_error(format!("cannot broadcast {:?} to {shape:?}", d.shape));
+            let lead = shape.len().checked_sub(d.shape.len()).ok_or_else(incompatible)?;
+            let mut strides = vec![0i64; shape.len()];
+            for (i, (&from, &stride)) in d.shape.iter().zip(&d.strides).enumerate() {
+                match from {
+                    _ if from == shape[lead + i] => strides[lead + i] = stride,
+                    1 => {}
+                    _ => return Err(incompatible()),
+                }
+            }
+            d.shape = shape.to_vec();
+            d.strides = strides;
+            Ok(())
+        })
+    }
+
+    /// `broadcast_to`, where a size of `-1` keeps the size of the matching dimension.
+    pub fn expand(&self, sizes: &[isize]) -> Result<Self> {
+        let current = self.shape();
+        let lead = sizes.len().checked_sub(current.len())
+            .ok_or_else(|| shape_error(format!("cannot expand {current:?} to {sizes:?}")))?;
+        let shape = sizes.iter().enumerate().map(|(i, &s)| match s {
+            -1 if i >= lead => Ok(current[i - lead]),
+            s if s >= 0 => Ok(s as usize),
+            _ => Err(shape_error(format!("invalid size {s} at dimension {i} of {sizes:?}"))),
+        }).collect::<Result<Vec<_>>>()?;
+        self.broadcast_to(&shape)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pollster::block_on;
+    use memory::MemoryManager;
+    use vknp_core::GpuContext;
+
+    #[test]
+    fn test_broadcast_shapes() {
+        assert_eq!(broadcast_shapes(&[vec![4], vec![1, 4]]), Some(vec![1, 4]));
+        assert_eq!(broadcast_shapes(&[&[3, 1, 5][..], &[4, 1], &[]]), Some(vec![3, 4, 5]));
+        assert_eq!(broadcast_shapes(&[vec![2, 0], vec![1]]), Some(vec![2, 0]));
+        assert_eq!(broadcast_shapes(&[vec![3], vec![4]]), None);
+    }
+
+    #[test]
+    fn test_broadcast_to_and_expand() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let col = Tensor::from_vec(&mm, &[1u32, 2, 3], &[3, 1], 0);
+
+        let b = col.broadcast_to(&[2, 3, 2]).unwrap();
+        assert_eq!(b.buffer_id(), col.buffer_id());
+        assert_eq!(b.view().strides[..3], [0, 1, 0]);
+        assert_eq!(b.to_vec(&mm), vec![1, 1, 2, 2, 3, 3, 1, 1, 2, 2, 3, 3]);
+
+        assert_eq!(col.expand(&[-1, 4]).unwrap().shape(), vec![3, 4]);
+        assert!(col.broadcast_to(&[2, 2]).is_err());
+        assert!(col.broadcast_to(&[3]).is_err());
+        assert!(col.expand(&[-1, -1, 2]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! NumPy-style creation routines, generated on the device: ranges, evenly spaced
+//! values, identity and triangular masks, coordinate grids.
+
+use memory::MemoryManager;
+use memory::copy::StridedView;
+use memory::generate::Sequence;
+use vknp_core::Result;
+use core_types::Element;
+
+use crate::Tensor;
+use crate::view::shape_error;
+
+/// Layout of the outputs of `Tensor::meshgrid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Indexing {
+    /// Cartesian: the first two inputs index the columns then the rows (NumPy's default)
+    #[default]
+    Xy,
+    /// Matrix: input `i` indexes dimension `i`
+    Ij,
+}
+
+impl<T: Element> Tensor<T> {
+    /// Values from `start` (included) to `stop` (excluded) every `step`, as `numpy.arange`:
+    /// there are `ceil((stop - start) / step)` of them.
+    pub fn arange(mgr: &MemoryManager, start: T, stop: T, step: T, device_id: usize) -> Result<Self> {
+        let (start, stop, step) = (start.to_f64(), stop.to_f64(), step.to_f64());
+        if step == 0.0 {
+            return Err(shape_error("arange: step must not be zero".into()));
+        }
+        let len = ((stop - start) / step).ceil().max(0.0) as usize;
+        Self::generate(mgr, &[len], device_id, Sequence::Arange { start, step })
+    }
+
+    /// `num` values evenly spaced from `start` to `stop`, as `numpy.linspace`: `stop` is
+    /// the last one with `endpoint`, left out otherwise. Integer types round down.
+    pub fn linspace(
+        mgr:       &MemoryManager,
+        start:     f64,
+        stop:      f64,
+        num:       usize,
+        endpoint:  bool,
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::generate(mgr, &[num], device_id, Sequence::Linspace { start, stop, endpoint })
+    }
+
+    /// `base` raised to each value of the matching `linspace`, as `numpy.logspace`.
+    pub fn logspace(
+        mgr:       &MemoryManager,
+        start:     f64,
+        stop:      f64,
+        num:       usize,
+        endpoint:  bool,
+        base:      f64,
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::generate(mgr, &[num], device_id, Sequence::Logspace { start, stop, endpoint, base })
+    }
+
+    /// `n × m` matrix (`m` defaults to `n`) with ones on diagonal `k` (above the main one
+    /// for `k > 0`) and zeros elsewhere, as `numpy.eye`.
+    pub fn eye(mgr: &MemoryManager, n: usize, m: Option<usize>, k: isize, device_id: usize) -> Result<Self> {
+        Self::generate(mgr, &[n, m.unwrap_or(n)], device_id, Sequence::Eye { k: k as i64 })
+    }
+
+    /// `n × m` mask (`m` defaults to `n`) with ones on and below diagonal `k`, as `numpy.tri`.
+    pub fn tril(mgr: &MemoryManager, n: usize, m: Option<usize>, k: isize, device_id: usize) -> Result<Self> {
+        Self::generate(mgr, &[n, m.unwrap_or(n)], device_id, Sequence::Tril { k: k as i64 })
+    }
+
+    /// `n × m` mask (`m` defaults to `n`) with ones on and above diagonal `k`.
+    pub fn triu(mgr: &MemoryManager, n: usize, m: Option<usize>, k: isize, device_id: usize) -> Result<Self> {
+        Self::generate(mgr, &[n, m.unwrap_or(n)], device_id, Sequence::Triu { k: k as i64 })
+    }
+
+    /// Coordinate grids of 1-D `inputs`, as `numpy.meshgrid`: output `i` repeats input `i`
+    /// along every other dimension. With `Indexing::Xy`, the first two dimensions are
+    /// swapped, so two inputs of lengths `nx, ny` give grids of shape `[ny, nx]`.
+    pub fn meshgrid(mgr: &MemoryManager, inputs: &[&Self], indexing: Indexing) -> Result<Vec<Self>> {
+        let mut shape = inputs.iter().map(|t| match t.shape()[..] {
+            [len] => Ok(len),
+            ref s => Err(shape_error(format!("meshgrid: inputs must be 1-D, found {s:?}"))),
+        }).collect::<Result<Vec<_>>>()?;
+        let swap = indexing == Indexing::Xy && shape.len() >= 2;
+        if swap {
+            shape.swap(0, 1);
+        }
+        inputs.iter().enumerate().map(|(i, t)| {
+            let dim = match i {
+                0 | 1 if swap => 1 - i,
+                _ => i,
+            };
+            let mut along = vec![1; shape.len()];
+            along[dim] = shape[dim];
+            let out = Self::try_empty(mgr, &shape, t.device_id)?;
+            out.assign(mgr, &t.reshape(&along)?)?;
+            Ok(out)
+        }).collect()
+    }
+
+    /// Contiguous tensor of `shape` holding `seq`, written on the device
+    fn generate(mgr: &MemoryManager, shape: &[usize], device_id: usize, seq: Sequence) -> Result<Self> {
+        let out = Self::try_empty(mgr, shape, device_id)?;
+        mgr.generate(&StridedView { id: out.buffer_id, view: out.view, dtype: out.dtype }, &seq)?;
+        Ok(out)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pollster::block_on;
+    use vknp_core::GpuContext;
+
+    #[test]
+    fn test_arange_linspace_and_logspace_follow_numpy() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        assert_eq!(Tensor::arange(&mm, 0i32, 5, 1, 0).unwrap().to_vec(&mm), vec![0, 1, 2, 3, 4]);
+        assert_eq!(Tensor::arange(&mm, 10i32, -3, -4, 0).unwrap().to_vec(&mm), vec![10, 6, 2, -2]);
+        assert_eq!(Tensor::arange(&mm, 3u32, 4_000_000_003, 1_000_000_000, 0).unwrap().to_vec(&mm),
+                   vec![3, 1_000_000_003, 2_000_000_003, 3_000_000_003]);
+        assert_eq!(Tensor::arange(&mm, 0.0f32, 1.0, 0.25, 0).unwrap().to_vec(&mm), vec![0.0, 0.25, 0.5, 0.75]);
+        assert_eq!(Tensor::arange(&mm, 1.0f32, 0.0, 1.0, 0).unwrap().shape(), vec![0]);
+        assert!(Tensor::arange(&mm, 0i32, 5, 0, 0).is_err());
+
+        assert_eq!(Tensor::<f32>::linspace(&mm, 2.0, 3.0, 5, true, 0).unwrap().to_vec(&mm), vec![2.0, 2.25, 2.5, 2.75, 3.0]);
+        assert_eq!(Tensor::<f32>::linspace(&mm, 2.0, 3.0, 4, false, 0).unwrap().to_vec(&mm), vec![2.0, 2.25, 2.5, 2.75]);
+        assert_eq!(Tensor::<f32>::linspace(&mm, 2.0, 3.0, 1, true, 0).unwrap().to_vec(&mm), vec![2.0]);
+        // integer types round down, as NumPy 2 does
+        assert_eq!(Tensor::<i32>::linspace(&mm, -1.0, 1.0, 5, true, 0).unwrap().to_vec(&mm), vec![-1, -1, 0, 0, 1]);
+
+        let logs = Tensor::<f32>::logspace(&mm, 0.0, 3.0, 4, true, 10.0, 0).unwrap().to_vec(&mm);
+        for (got, want) in logs.iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((got - want).abs() <= want * 1e-5, "{logs:?}");
+        }
+        assert_eq!(Tensor::<u32>::logspace(&mm, 0.0, 10.0, 11, true, 2.0, 0).unwrap().to_vec(&mm),
+                   (0..=10).map(|e| 1 << e).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_eye_triangles_and_meshgrid() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        assert_eq!(Tensor::<f32>::eye(&mm, 2, None, 0, 0).unwrap().to_vec(&mm), vec![1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(Tensor::<i32>::eye(&mm, 2, Some(3), 1, 0).unwrap().to_vec(&mm), vec![0, 1, 0, 0, 0, 1]);
+        assert_eq!(Tensor::<u32>::tril(&mm, 3, None, 0, 0).unwrap().to_vec(&mm), vec![1, 0, 0, 1, 1, 0, 1, 1, 1]);
+        assert_eq!(Tensor::<u32>::tril(&mm, 2, Some(3), -1, 0).unwrap().to_vec(&mm), vec![0, 0, 0, 1, 0, 0]);
+        assert_eq!(Tensor::<u32>::triu(&mm, 3, None, 1, 0).unwrap().to_vec(&mm), vec![0, 1, 1, 0, 0, 1, 0, 0, 0]);
+
+        let x = Tensor::from_vec(&mm, &[1i32, 2, 3], &[3], 0);
+        let y = Tensor::from_vec(&mm, &[10i32, 20], &[2], 0);
+        let xy = Tensor::meshgrid(&mm, &[&x, &y], Indexing::Xy).unwrap();
+        assert_eq!(xy[0].shape(), vec![2, 3]);
+        assert_eq!(xy[0].to_vec(&mm), vec![1, 2, 3, 1, 2, 3]);
+        assert_eq!(xy[1].to_vec(&mm), vec![10, 10, 10, 20, 20, 20]);
+        let ij = Tensor::meshgrid(&mm, &[&x, &y], Indexing::Ij).unwrap();
+        assert_eq!(ij[0].shape(), vec![3, 2]);
+        assert_eq!(ij[0].to_vec(&mm), vec![1, 1, 2, 2, 3, 3]);
+        assert_eq!(ij[1].to_vec(&mm), vec![10, 20, 10, 20, 10, 20]);
+        assert!(Tensor::meshgrid(&mm, &[&xy[0]], Indexing::Ij).is_err());
+    }
+}
@@ -1,13 +1,40 @@
+pub mod broadcast;
+pub mod creation;
+pub mod random;
 mod utils;
+mod view;
 
 use bytemuck::Zeroable;
 use std::marker::PhantomData;
 
-use memory::MemoryManager;
+use memory::{DeviceMemory, MemoryManager};
+use memory::copy::StridedView;
+use vknp_core::{Result, VknpError};
 use vknp_core::types::BufferToken;
-use core_types::{BufferId, DataType, Element, ViewDescriptor};
+use core_types::{BufferId, DataType, Element, ViewDescriptor, MAX_DIMS};
 
-use utils::compute_strides;
+use utils::{compute_strides, view_span};
+
+pub use creation::Indexing;
+pub use random::Generator;
+pub use view::Slice;
+
+/// Row-major view over a whole buffer, rejecting more than `MAX_DIMS` dimensions.
+fn contiguous_view(shape: &[usize]) -> Result<ViewDescriptor> {
+    if shape.len() > MAX_DIMS {
+        return Err(VknpError::Shape(format!(
+            "{} dimensions given, at most {} supported", shape.len(), MAX_DIMS
+        )));
+    }
+    let mut vd = ViewDescriptor::zeroed();
+    vd.ndim = shape.len() as u32;
+    let strides = compute_strides(shape);
+    for (i, &d) in shape.iter().enumerate() {
+        vd.shape[i]   = d as u32;
+        vd.strides[i] = strides[i] as i32;
+    }
+    Ok(vd)
+}
 
 /// Lightweight handle: (BufferId, ViewDescriptor, device_id, dtype)
 pub struct Tensor<T: Element> {
@@ -25,68 +52,245 @@ impl<T: Element> Tensor<T> {
     /* --------------------------------------------------------------------- */
 
     /// Allocate an uninitialised tensor on the given device.
+    /// Panics on failure; see [`Tensor::try_empty`].
     pub fn empty(
         mgr:       &MemoryManager,
         shape:     &[usize],
         device_id: usize,
     ) -> Self {
+        Self::try_empty(mgr, shape, device_id).expect("Tensor::empty failed")
+    }
+
+    /// Allocate an uninitialised tensor on the given device.
+    pub fn try_empty(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::allocate_with(shape, device_id, |bytes| mgr.allocate_raw(bytes))
+    }
+
+    /// Allocate a tensor of zeros, cleared on the device.
+    /// Panics on failure; see [`Tensor::try_zeros`].
+    pub fn zeros(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Self {
+        Self::try_zeros(mgr, shape, device_id).expect("Tensor::zeros failed")
+    }
+
+    /// Allocate a tensor of zeros, cleared on the device.
+    pub fn try_zeros(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::allocate_with(shape, device_id, |bytes| mgr.allocate_zeroed(bytes))
+    }
+
+    /// Allocate a tensor of ones, filled on the device.
+    /// Panics on failure; see [`Tensor::try_ones`].
+    pub fn ones(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Self {
+        Self::try_ones(mgr, shape, device_id).expect("Tensor::ones failed")
+    }
+
+    /// Allocate a tensor of ones, filled on the device.
+    pub fn try_ones(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::try_full(mgr, shape, T::ONE, device_id)
+    }
+
+    /// Allocate a tensor with every element set to `value`, filled on the device.
+    /// Panics on failure; see [`Tensor::try_full`].
+    pub fn full(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        value:     T,
+        device_id: usize,
+    ) -> Self {
+        Self::try_full(mgr, shape, value, device_id).expect("Tensor::full failed")
+    }
+
+    /// Allocate a tensor with every element set to `value`, filled on the device.
+    pub fn try_full(
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        value:     T,
+        device_id: usize,
+    ) -> Result<Self> {
+        Self::allocate_with(shape, device_id, |bytes| mgr.allocate_filled(bytes, value))
+    }
+
+    /// Contiguous tensor over a buffer obtained from `alloc` (given the size in bytes).
+    fn allocate_with(
+        shape:     &[usize],
+        device_id: usize,
+        alloc:     impl FnOnce(usize) -> Result<(BufferId, BufferToken)>,
+    ) -> Result<Self> {
+        let vd = contiguous_view(shape)?;
         let elem_count = shape.iter().product::<usize>();
         let bytes      = elem_count * T::DTYPE.size_in_bytes();
-        let (buf_id, token) = mgr.allocate_raw(bytes).unwrap();
-
-        let mut vd = ViewDescriptor::zeroed();
-        vd.ndim = shape.len() as u32;
-        let strides = compute_strides(shape);
-        for (i, &d) in shape.iter().enumerate() {
-            vd.shape[i]   = d as u32;
-            vd.strides[i] = strides[i] as u32;
-        }
+        let (buf_id, token) = alloc(bytes)?;
 
-        Tensor {
+        Ok(Tensor {
             buffer_id: buf_id,
             token,
             device_id,
             view:      vd,
             dtype:     T::DTYPE,
             _marker:   PhantomData,
-        }
+        })
     }
 
-    /// Construct a tensor by uploading a CPU slice into GPU
+    /// Construct a tensor by uploading a CPU slice into GPU.
+    /// Panics on failure; see [`Tensor::try_from_vec`].
     pub fn from_vec(
         mgr:       &MemoryManager,
         data:      &[T],
         shape:     &[usize],
         device_id: usize,
     ) -> Self {
-        // 1) allocate
+        Self::try_from_vec(mgr, data, shape, device_id).expect("Tensor::from_vec failed")
+    }
+
+    /// Construct a tensor by uploading a CPU slice into GPU
+    pub fn try_from_vec(
+        mgr:       &MemoryManager,
+        data:      &[T],
+        shape:     &[usize],
+        device_id: usize,
+    ) -> Result<Self> {
+        // 1) validate + build the view descriptor
+        let vd = contiguous_view(shape)?;
         let elem_count = shape.iter().product::<usize>();
-        let bytes      = elem_count * T::DTYPE.size_in_bytes();
-        let (buf_id, token) = mgr.allocate_raw(bytes).unwrap();
-        // 2) write
-        mgr.write_to_buffer(buf_id, data).unwrap();
-        // 3) build the view descriptor
-        let mut vd = ViewDescriptor::zeroed();
-        vd.ndim = shape.len() as u32;
-        let strides = compute_strides(shape);
-        for (i, &d) in shape.iter().enumerate() {
-            vd.shape[i]   = d as u32;
-            vd.strides[i] = strides[i] as u32;
+        if data.len() != elem_count {
+            return Err(VknpError::Shape(format!(
+                "{} elements given for shape {:?} ({} expected)", data.len(), shape, elem_count
+            )));
+        }
+        // 2) allocate
+        let bytes = elem_count * T::DTYPE.size_in_bytes();
+        let (buf_id, token) = mgr.allocate_raw(bytes)?;
+        // 3) write
+        if let Err(e) = mgr.write_to_buffer(buf_id, data) {
+            mgr.release(buf_id);
+            return Err(e);
         }
 
-        Tensor {
+        Ok(Tensor {
             buffer_id: buf_id,
             token,
             device_id,
             view:      vd,
             dtype:     T::DTYPE,
             _marker:   PhantomData,
-        }
+        })
     }
 
     /// Download a tensor from GPU to CPU into a `Vec<T>`.
+    /// Panics on failure; see [`Tensor::try_to_vec`].
     pub fn to_vec(&self, mgr: &MemoryManager) -> Vec<T> {
-        mgr.download_raw(self.buffer_id).unwrap()
+        self.try_to_vec(mgr).expect("Tensor::to_vec failed")
+    }
+
+    /// Download a tensor from GPU to CPU into a `Vec<T>`, in the row-major order of its view.
+    /// A non-contiguous view is first materialised on the device (`contiguous`).
+    pub fn try_to_vec(&self, mgr: &MemoryManager) -> Result<Vec<T>> {
+        let t = self.contiguous(mgr)?;
+        let (start, len) = view_span(&t.view);
+        mgr.read_range(t.buffer_id, start * T::DTYPE.size_in_bytes(), len)
+    }
+
+    /// Non-blocking download; the device must be polled meanwhile
+    /// (`GpuContext::poll` or a `DevicePoller`).
+    pub async fn to_vec_async(&self, mgr: &MemoryManager) -> Result<Vec<T>> {
+        let t = self.contiguous(mgr)?;
+        let (start, len) = view_span(&t.view);
+        mgr.read_range_async(t.buffer_id, start * T::DTYPE.size_in_bytes(), len).await
+    }
+
+    /// Copy this tensor to another device through host staging.
+    /// The copy is contiguous, with the shape of this view.
+    pub fn to_device<D: DeviceMemory + ?Sized>(&self, devices: &D, device_id: usize) -> Result<Self> {
+        let src = devices.memory(self.device_id).ok_or(VknpError::UnknownDevice(self.device_id))?;
+        let dst = devices.memory(device_id).ok_or(VknpError::UnknownDevice(device_id))?;
+        if device_id == self.device_id {
+            return Ok(self.clone());
+        }
+
+        // 1) GPU → host on the source device
+        let host: Vec<T> = self.try_to_vec(src)?;
+        // 2) host → GPU on the target device
+        Self::try_from_vec(dst, &host, &self.shape(), device_id)
+    }
+
+    /* --------------------------------------------------------------------- */
+    /* Copies                                                                */
+    /* --------------------------------------------------------------------- */
+
+    /// This tensor if its view is contiguous, a contiguous copy of it otherwise.
+    pub fn contiguous(&self, mgr: &MemoryManager) -> Result<Self> {
+        if self.is_contiguous() {
+            return Ok(self.clone());
+        }
+        let out = Self::try_empty(mgr, &self.shape(), self.device_id)?;
+        out.assign(mgr, self)?;
+        Ok(out)
+    }
+
+    /// Copy `src` into this view, converting its elements to `T`; `src` is broadcast
+    /// to the shape of this view. Runs on the device.
+    pub fn assign<U: Element>(&self, mgr: &MemoryManager, src: &Tensor<U>) -> Result<()> {
+        if src.device_id != self.device_id {
+            return Err(VknpError::DeviceMismatch { expected: self.device_id, found: src.device_id });
+        }
+        let src = src.broadcast_to(&self.shape())?;
+        mgr.copy_view(
+            &StridedView { id: src.buffer_id, view: src.view, dtype: src.dtype },
+            &StridedView { id: self.buffer_id, view: self.view, dtype: self.dtype },
+        )
+    }
+
+    /// Contiguous copy of this tensor with its elements converted to `U`.
+    pub fn cast<U: Element>(&self, mgr: &MemoryManager) -> Result<Tensor<U>> {
+        let out = Tensor::<U>::try_empty(mgr, &self.shape(), self.device_id)?;
+        out.assign(mgr, self)?;
+        Ok(out)
+    }
+
+    /// Name the buffer of this tensor, e.g. `Tensor::empty(..).with_name("weights")`.
+    /// The name shows in leak reports and in the labels of the dispatches using it;
+    /// it is shared by every view of the buffer.
+    pub fn with_name(self, name: &str) -> Self {
+        self.token.set_label(name);
+        self
+    }
+
+    /// Name given with `with_name`, if any
+    pub fn name(&self, mgr: &MemoryManager) -> Option<String> {
+        mgr.label_of(self.buffer_id)
+    }
+
+    /* --------------------------------------------------------------------- */
+    /* Residency                                                             */
+    /* --------------------------------------------------------------------- */
+
+    /// Keep this tensor in device memory: it is never spilled to host under memory
+    /// pressure until `unpin`. Shared by every clone of the tensor.
+    pub fn pin(&self, mgr: &MemoryManager) -> Result<()> {
+        mgr.pin(self.buffer_id)
+    }
+
+    pub fn unpin(&self, mgr: &MemoryManager) {
+        mgr.unpin(self.buffer_id);
     }
 
     /* --------------------------------------------------------------------- */
@@ -98,6 +302,11 @@ impl<T: Element> Tensor<T> {
         &self.view
     }
 
+    /// Size of each dimension
+    pub fn shape(&self) -> Vec<usize> {
+        self.view.shape[..self.view.ndim as usize].iter().map(|&d| d as usize).collect()
+    }
+
     /// The internal BufferId
     pub fn buffer_id(&self) -> BufferId {
         self.buffer_id
@@ -121,7 +330,7 @@ impl<T: Element> Clone for Tensor<T> {
             buffer_id: self.buffer_id,
             token: self.token.clone(),
             device_id: self.device_id,
-            view: self.view.clone(),
+            view: self.view,
             dtype: self.dtype,
             _marker: PhantomData,
         }
@@ -137,15 +346,14 @@ mod tests {
     use super::*;
     use pollster::block_on;
     use vknp_core::GpuContext;
-    use core_types::MAX_DIMS;
 
     #[test]
     fn test_empty_tensor_dtype_and_view() {
         let ctx = block_on(GpuContext::new()).unwrap();
-        let mut mm = MemoryManager::new(ctx);
+        let mm = MemoryManager::new(ctx);
 
         let shape = [2, 3, 4];
-        let t: Tensor<f32> = Tensor::empty(&mut mm, &shape, 0);
+        let t: Tensor<f32> = Tensor::empty(&mm, &shape, 0);
 
         // dtype must be f32
         assert_eq!(t.dtype(), DataType::F32);
@@ -158,7 +366,7 @@ mod tests {
         assert_eq!(t.view().shape, expect_shape);
 
         // strides for [2,3,4] row-major = [12,4,1]
-        let mut expect_strides = [0u32; MAX_DIMS];
+        let mut expect_strides = [0i32; MAX_DIMS];
         expect_strides[..3].copy_from_slice(&[12, 4, 1]);
         assert_eq!(t.view().strides, expect_strides);
 
@@ -168,14 +376,14 @@ mod tests {
     #[test]
     fn test_from_vec_and_to_vec_preserves_data_and_dtype() {
         let ctx = block_on(GpuContext::new()).unwrap();
-        let mut mm = MemoryManager::new(ctx);
+        let mm = MemoryManager::new(ctx);
 
         let shape = [2, 2];
         let data  = vec![1u32, 2, 3, 4];
-        let t     = Tensor::from_vec(&mut mm, &data, &shape, 0);
+        let t     = Tensor::from_vec(&mm, &data, &shape, 0);
 
         // data round-trip
-        assert_eq!(t.to_vec(&mut mm), data);
+        assert_eq!(t.to_vec(&mm), data);
         // dtype correct
         assert_eq!(t.dtype(), DataType::U32);
 
@@ -184,4 +392,126 @@ mod tests {
         for i in 0..shape.len() { expect_shape[i] = shape[i] as u32; }
         assert_eq!(t.view().shape, expect_shape);
     }
+
+    #[test]
+    fn test_try_constructors_report_shape_errors() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        // data length does not match the shape
+        let err = Tensor::try_from_vec(&mm, &[1.0f32, 2.0, 3.0], &[2, 2], 0).err().unwrap();
+        assert!(matches!(err, VknpError::Shape(_)), "got {err}");
+
+        // too many dimensions
+        let err = Tensor::<u32>::try_empty(&mm, &[1; MAX_DIMS + 1], 0).err().unwrap();
+        assert!(matches!(err, VknpError::Shape(_)), "got {err}");
+    }
+
+    #[test]
+    fn test_to_vec_async_with_poller() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx.clone());
+
+        let data = vec![7i32, -8, 9, 10, 11, 12];
+        let t    = Tensor::from_vec(&mm, &data, &[2, 3], 0);
+
+        let _poller = ctx.spawn_poller(std::time::Duration::from_millis(1)).unwrap();
+        assert_eq!(block_on(t.to_vec_async(&mm)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_to_vec_downloads_only_the_elements_of_its_view() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let data: Vec<u32> = (0..10).collect();
+        let t = Tensor::from_vec(&mm, &data, &[10], 0);
+
+        // elements 3, 5, 7 out of the middle of the buffer, gathered on the device
+        let mut strided = t.clone();
+        strided.view.offset = 3;
+        strided.view.shape[0] = 3;
+        strided.view.strides[0] = 2;
+        assert!(!strided.is_contiguous());
+        assert_eq!(strided.to_vec(&mm), vec![3, 5, 7]);
+        assert_eq!(mm.stats().downloaded_bytes, 12);
+        assert_eq!(t.to_vec(&mm), data);
+    }
+
+    #[test]
+    fn test_assign_and_cast_between_views_and_dtypes() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let t = Tensor::from_vec(&mm, &[1.5f32, -2.0, 3.0, 4.0, 5.0, 6.0], &[2, 3], 0);
+        let c = t.transpose(0, 1).unwrap().contiguous(&mm).unwrap();
+        assert!(c.is_contiguous());
+        assert_eq!(c.shape(), vec![3, 2]);
+        assert_eq!(c.to_vec(&mm), vec![1.5, 4.0, -2.0, 5.0, 3.0, 6.0]);
+        assert_eq!(t.cast::<i32>(&mm).unwrap().to_vec(&mm), vec![1, -2, 3, 4, 5, 6]);
+
+        // a row broadcast into a column of an integer tensor
+        let m = Tensor::<u32>::zeros(&mm, &[2, 3], 0);
+        let row = Tensor::from_vec(&mm, &[7.0f32, 8.0], &[2, 1], 0);
+        m.narrow(1, 1, 1).unwrap().assign(&mm, &row).unwrap();
+        assert_eq!(m.to_vec(&mm), vec![0, 7, 0, 0, 8, 0]);
+
+        // source and destination in the same buffer
+        t.narrow(1, 0, 1).unwrap().assign(&mm, &t.narrow(1, 2, 1).unwrap()).unwrap();
+        assert_eq!(t.to_vec(&mm), vec![3.0, -2.0, 3.0, 6.0, 5.0, 6.0]);
+        assert!(t.assign(&mm, &c).is_err(), "[3, 2] does not broadcast to [2, 3]");
+    }
+
+    #[test]
+    fn test_zeros_ones_and_full_are_initialised_on_the_device() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        // recycle a dirty buffer: zeros must not see its old content
+        drop(Tensor::from_vec(&mm, &[7.0f32; 6], &[2, 3], 0));
+        mm.clear_unused();
+        assert_eq!(Tensor::<f32>::zeros(&mm, &[2, 3], 0).to_vec(&mm), vec![0.0; 6]);
+        assert_eq!(Tensor::<i32>::ones(&mm, &[5], 0).to_vec(&mm), vec![1; 5]);
+        assert_eq!(Tensor::full(&mm, &[3], -2.5f32, 0).to_vec(&mm), vec![-2.5; 3]);
+        assert_eq!(Tensor::full(&mm, &[1000], u32::MAX, 0).to_vec(&mm), vec![u32::MAX; 1000]);
+
+        let stats = mm.stats();
+        assert_eq!(stats.uploaded_bytes, 24, "only the from_vec went through staging");
+        assert!(stats.pools[0].reuses >= 1);
+    }
+
+    #[test]
+    fn test_named_tensors_show_in_leak_reports() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let w = Tensor::<f32>::empty(&mm, &[8], 0).with_name("weights");
+        let unnamed = Tensor::<f32>::empty(&mm, &[8], 0);
+        assert_eq!(w.clone().name(&mm).as_deref(), Some("weights"));
+        assert_eq!(unnamed.name(&mm), None);
+
+        let leaks = mm.report_leaks();
+        let named = leaks.iter().find(|l| l.allocation.id == w.buffer_id()).unwrap();
+        assert!(named.to_string().contains("\"weights\""), "{named}");
+    }
+
+    #[test]
+    fn test_dropping_the_last_tensor_releases_its_buffer() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let t = Tensor::from_vec(&mm, &[1.0f32; 16], &[16], 0);
+        let id = t.buffer_id();
+        let alias = t.clone();
+        drop(t);
+        assert_eq!(alias.to_vec(&mm), vec![1.0; 16]);
+
+        drop(alias);
+        assert!(mm.get_ref(id).is_none());
+        assert_eq!(mm.pool_stats()[0].live_bytes, 0);
+
+        // the next tensor of the same size class reuses the buffer
+        let _t = Tensor::<f32>::empty(&mm, &[16], 0);
+        assert_eq!(mm.pool_stats()[0].device_allocs, 1);
+    }
 }
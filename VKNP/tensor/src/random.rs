@@ -0,0 +1,233 @@
+//! Random tensors drawn on the device by a counter-based generator (Philox-4x32-10).
+//! A draw depends only on the seed, stream and offset of the `Generator` that makes it,
+//! which then moves its offset past the numbers it used.
+
+use memory::MemoryManager;
+use memory::copy::StridedView;
+use memory::philox::{Distribution, RandomState};
+use vknp_core::{Result, VknpError};
+use core_types::{DataType, Element};
+
+use crate::Tensor;
+use crate::view::shape_error;
+
+/// Source of random tensors: a position in a Philox stream, moved forward by each draw
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generator {
+    state: RandomState,
+}
+
+impl Generator {
+    /// Generator of `seed`, at the start of stream 0
+    pub fn new(seed: u64) -> Self {
+        Self::from_state(RandomState { seed, ..Default::default() })
+    }
+
+    /// Generator at a given seed, stream and offset, e.g. saved with `state`
+    pub fn from_state(state: RandomState) -> Self {
+        Self { state }
+    }
+
+    /// Current seed, stream and offset: a generator restored from it repeats the next draws
+    pub fn state(&self) -> RandomState {
+        self.state
+    }
+
+    /// Independent generator: same seed, on a stream drawn from this one
+    pub fn split(&mut self) -> Self {
+        let [lo, hi, ..] = self.state.block(0);
+        self.state.offset += 1;
+        Self::from_state(RandomState {
+            seed:   self.state.seed,
+            stream: (hi as u64) << 32 | lo as u64,
+            offset: 0,
+        })
+    }
+
+    /// Values uniform over `[low, high)`, for float types.
+    pub fn uniform<T: Element>(
+        &mut self,
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        low:       f64,
+        high:      f64,
+        device_id: usize,
+    ) -> Result<Tensor<T>> {
+        expect_float::<T>("uniform", true)?;
+        self.draw(mgr, shape, device_id, Distribution::Uniform { low, high }, None)
+    }
+
+    /// Gaussian values of mean `mean` and standard deviation `std`, for float types.
+    pub fn normal<T: Element>(
+        &mut self,
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        mean:      f64,
+        std:       f64,
+        device_id: usize,
+    ) -> Result<Tensor<T>> {
+        expect_float::<T>("normal", true)?;
+        self.draw(mgr, shape, device_id, Distribution::Normal { mean, std }, None)
+    }
+
+    /// Integers uniform over `[low, high)`, for integer types; at most 2³² values.
+    pub fn randint<T: Element>(
+        &mut self,
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        low:       T,
+        high:      T,
+        device_id: usize,
+    ) -> Result<Tensor<T>> {
+        expect_float::<T>("randint", false)?;
+        let (low, high) = (low.to_f64() as i64, high.to_f64() as i64);
+        self.draw(mgr, shape, device_id, Distribution::Randint { low, high }, None)
+    }
+
+    /// 1 with probability `p`, 0 otherwise.
+    pub fn bernoulli<T: Element>(
+        &mut self,
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        p:         f64,
+        device_id: usize,
+    ) -> Result<Tensor<T>> {
+        self.draw(mgr, shape, device_id, Distribution::Bernoulli { p }, None)
+    }
+
+    /// The integers `0..n` in a random order.
+    pub fn permutation<T: Element>(&mut self, mgr: &MemoryManager, n: usize, device_id: usize) -> Result<Tensor<T>> {
+        let n = domain("permutation", n)?;
+        self.draw(mgr, &[n as usize], device_id, Distribution::Permutation { n }, None)
+    }
+
+    /// Elements of the 1-D tensor `a` picked at random, with or without `replace`ment,
+    /// as `numpy.random.choice`.
+    pub fn choice<T: Element>(
+        &mut self,
+        mgr:     &MemoryManager,
+        a:       &Tensor<T>,
+        size:    &[usize],
+        replace: bool,
+    ) -> Result<Tensor<T>> {
+        let [len] = a.shape()[..] else {
+            return Err(shape_error(format!("choice: the source must be 1-D, found {:?}", a.shape())));
+        };
+        let n = domain("choice", len)?;
+        let src = StridedView { id: a.buffer_id, view: a.view, dtype: a.dtype };
+        self.draw(mgr, size, a.device_id, Distribution::Choice { n, replace }, Some(&src))
+    }
+
+    /// Tensor of `shape` drawn from `dist` (checked first), then move past the blocks it used
+    fn draw<T: Element>(
+        &mut self,
+        mgr:       &MemoryManager,
+        shape:     &[usize],
+        device_id: usize,
+        dist:      Distribution,
+        src:       Option<&StridedView>,
+    ) -> Result<Tensor<T>> {
+        let total = shape.iter().product::<usize>() as u64;
+        dist.validate(total)?;
+        let out = Tensor::<T>::try_empty(mgr, shape, device_id)?;
+        mgr.random(&StridedView { id: out.buffer_id, view: out.view, dtype: out.dtype }, &dist, &self.state, src)?;
+        self.state.offset += dist.blocks(total);
+        Ok(out)
+    }
+}
+
+/// `T` must be a float type (`float`) or an integer one (`!float`)
+fn expect_float<T: Element>(op: &str, float: bool) -> Result<()> {
+    if T::DTYPE.is_float() == float {
+        return Ok(());
+    }
+    Err(VknpError::Dtype {
+        context:  op.to_string(),
+        expected: DataType::ALL.into_iter().filter(|dt| dt.is_float() == float).collect(),
+        found:    T::DTYPE,
+    })
+}
+
+/// `n` as the size of a permutation, which must be addressable with 32 bits
+fn domain(op: &str, n: usize) -> Result<u32> {
+    u32::try_from(n).map_err(|_| shape_error(format!("{op}: {n} elements, at most {} supported", u32::MAX)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pollster::block_on;
+    use vknp_core::GpuContext;
+
+    #[test]
+    fn test_draws_are_reproducible_and_move_the_generator() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+
+        let mut g = Generator::new(42);
+        let saved = g.state();
+        let a = g.uniform::<f32>(&mm, &[1000], -1.0, 1.0, 0).unwrap().to_vec(&mm);
+        assert_eq!(g.state().offset, 250);
+        assert!(a.iter().all(|x| (-1.0..1.0).contains(x)));
+        let mean = a.iter().sum::<f32>() / a.len() as f32;
+        assert!(mean.abs() < 0.1, "mean {mean}");
+
+        // the same state gives the same numbers, whatever the shape of the output
+        let b = Generator::from_state(saved).uniform::<f32>(&mm, &[10, 100], -1.0, 1.0, 0).unwrap().to_vec(&mm);
+        assert_eq!(a, b);
+        // and the device agrees with the host Philox: the 24 high bits of each word
+        let unit = Generator::from_state(saved).uniform::<f32>(&mm, &[4], 0.0, 1.0, 0).unwrap().to_vec(&mm);
+        let words = saved.block(0).map(|w| (w >> 8) as f32 * 2f32.powi(-24));
+        assert_eq!(unit, words);
+
+        let next = g.uniform::<f32>(&mm, &[1000], -1.0, 1.0, 0).unwrap().to_vec(&mm);
+        assert_ne!(a, next);
+        let mut child = g.split();
+        assert_ne!(child.state().stream, g.state().stream);
+        assert_ne!(child.uniform::<f32>(&mm, &[8], 0.0, 1.0, 0).unwrap().to_vec(&mm),
+                   g.uniform::<f32>(&mm, &[8], 0.0, 1.0, 0).unwrap().to_vec(&mm));
+
+        let z = g.normal::<f32>(&mm, &[4000], 3.0, 2.0, 0).unwrap().to_vec(&mm);
+        let mean = z.iter().sum::<f32>() / z.len() as f32;
+        let var = z.iter().map(|x| (x - mean) * (x - mean)).sum::<f32>() / z.len() as f32;
+        assert!((mean - 3.0).abs() < 0.15 && (var.sqrt() - 2.0).abs() < 0.15, "mean {mean}, std {}", var.sqrt());
+
+        assert!(g.uniform::<i32>(&mm, &[4], 0.0, 1.0, 0).is_err());
+        assert!(g.randint::<f32>(&mm, &[4], 0.0, 1.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_randint_bernoulli_permutation_and_choice() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let mut g = Generator::new(7);
+
+        let r = g.randint(&mm, &[2000], -3i32, 4, 0).unwrap().to_vec(&mm);
+        assert!(r.iter().all(|x| (-3..4).contains(x)));
+        assert!((-3..4).all(|v| r.contains(&v)));
+        let full = g.randint(&mm, &[64], 0u32, u32::MAX, 0).unwrap().to_vec(&mm);
+        assert!(full.iter().any(|&x| x > 1 << 31));
+
+        let coins = g.bernoulli::<u32>(&mm, &[4000], 0.25, 0).unwrap().to_vec(&mm);
+        let heads = coins.iter().filter(|&&c| c == 1).count();
+        assert!(coins.iter().all(|&c| c <= 1) && (800..1200).contains(&heads), "{heads} heads");
+        assert!(g.bernoulli::<f32>(&mm, &[1], 1.5, 0).is_err());
+
+        for n in [0, 1, 2, 5, 1000] {
+            let mut p = g.permutation::<u32>(&mm, n, 0).unwrap().to_vec(&mm);
+            p.sort();
+            assert_eq!(p, (0..n as u32).collect::<Vec<_>>());
+        }
+
+        let a = Tensor::from_vec(&mm, &[10.0f32, 20.0, 30.0, 40.0, 50.0], &[5], 0);
+        let mut picked = g.choice(&mm, &a, &[5], false).unwrap().to_vec(&mm);
+        picked.sort_by(f32::total_cmp);
+        assert_eq!(picked, vec![10.0, 20.0, 30.0, 40.0, 50.0]);
+        let reversed = a.flip(&[0]).unwrap();
+        let drawn = g.choice(&mm, &reversed, &[4, 50], true).unwrap();
+        assert_eq!(drawn.shape(), vec![4, 50]);
+        assert!(drawn.to_vec(&mm).iter().all(|x| [10.0, 20.0, 30.0, 40.0, 50.0].contains(x)));
+        assert!(g.choice(&mm, &a, &[6], false).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use core_types::ViewDescriptor;
+
 /// Computes the strides for an empty tensor given its shape.
 pub fn compute_strides(shape: &[usize]) -> Vec<usize> {
     let n = shape.len();
@@ -14,10 +16,41 @@ pub fn compute_strides(shape: &[usize]) -> Vec<usize> {
     strides
 }
 
+/// Whether a view covers its elements in row-major order with no gap
+/// (dimensions of size 1 may have any stride).
+pub fn is_contiguous(vd: &ViewDescriptor) -> bool {
+    let mut expected = 1i64;
+    for i in (0..vd.ndim as usize).rev() {
+        let dim = vd.shape[i] as i64;
+        if dim == 0 {
+            return true;
+        }
+        if dim != 1 && vd.strides[i] as i64 != expected {
+            return false;
+        }
+        expected *= dim;
+    }
+    true
+}
+
+/// Elements of the buffer a view spans: index of the lowest one and count up to the highest one.
+pub fn view_span(vd: &ViewDescriptor) -> (usize, usize) {
+    let ndim = vd.ndim as usize;
+    if vd.shape[..ndim].contains(&0) {
+        return (vd.offset as usize, 0);
+    }
+    let (mut lo, mut hi) = (vd.offset as i64, vd.offset as i64);
+    for i in 0..ndim {
+        let reach = (vd.shape[i] as i64 - 1) * vd.strides[i] as i64;
+        if reach < 0 { lo += reach } else { hi += reach }
+    }
+    (lo as usize, (hi - lo + 1) as usize)
+}
+
 
 #[cfg(test)]
 mod tests {
-    use super::compute_strides;
+    use super::*;
 
     #[test]
     fn test_compute_strides_simple() {
@@ -27,4 +60,28 @@ mod tests {
         assert_eq!(compute_strides(&[2, 3, 4]), vec![12, 4, 1]);
         assert_eq!(compute_strides(&[4, 1, 5]), vec![5, 5, 1]);
     }
+
+    #[test]
+    fn test_view_span_and_contiguity() {
+        use bytemuck::Zeroable;
+        // every other element of a 2×4 block, starting at element 3
+        let mut vd = ViewDescriptor::zeroed();
+        vd.offset = 3;
+        vd.ndim = 2;
+        vd.shape[..2].copy_from_slice(&[2, 2]);
+        vd.strides[..2].copy_from_slice(&[4, 2]);
+        assert_eq!(view_span(&vd), (3, 7));
+        assert!(!is_contiguous(&vd));
+
+        // the same elements, flipped along both dimensions
+        vd.offset = 9;
+        vd.strides[..2].copy_from_slice(&[-4, -2]);
+        assert_eq!(view_span(&vd), (3, 7));
+
+        // dimensions of size 1 may have any stride
+        vd.shape[..2].copy_from_slice(&[1, 3]);
+        vd.strides[..2].copy_from_slice(&[0, 1]);
+        assert!(is_contiguous(&vd));
+        assert_eq!(view_span(&vd), (9, 3));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,371 @@
+use bytemuck::Zeroable;
+use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
+
+use vknp_core::{Result, VknpError};
+use core_types::{Element, ViewDescriptor, MAX_DIMS};
+
+use crate::Tensor;
+use crate::utils::{compute_strides, is_contiguous};
+
+/// Range of one dimension taken by `Tensor::slice`: `start..end`, every `step`-th element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slice {
+    pub start: usize,
+    /// `None`: up to the end of the dimension
+    pub end:   Option<usize>,
+    pub step:  usize,
+}
+
+impl Slice {
+    /// The whole dimension
+    pub fn all() -> Self {
+        Self { start: 0, end: None, step: 1 }
+    }
+
+    /// Keep every `step`-th element of the range
+    pub fn step(self, step: usize) -> Self {
+        Self { step, ..self }
+    }
+}
+
+impl From<Range<usize>> for Slice {
+    fn from(r: Range<usize>) -> Self {
+        Self { start: r.start, end: Some(r.end), step: 1 }
+    }
+}
+
+impl From<RangeFrom<usize>> for Slice {
+    fn from(r: RangeFrom<usize>) -> Self {
+        Self { start: r.start, end: None, step: 1 }
+    }
+}
+
+impl From<RangeTo<usize>> for Slice {
+    fn from(r: RangeTo<usize>) -> Self {
+        Self { start: 0, end: Some(r.end), step: 1 }
+    }
+}
+
+impl From<RangeFull> for Slice {
+    fn from(_: RangeFull) -> Self {
+        Self::all()
+    }
+}
+
+pub(crate) fn shape_error(msg: String) -> VknpError {
+    VknpError::Shape(msg)
+}
+
+/// Shape and strides of a view as signed integers, for the arithmetic below
+pub(crate) struct Dims {
+    pub(crate) offset:  i64,
+    pub(crate) shape:   Vec<usize>,
+    pub(crate) strides: Vec<i64>,
+}
+
+impl Dims {
+    fn of(vd: &ViewDescriptor) -> Self {
+        let ndim = vd.ndim as usize;
+        Self {
+            offset:  vd.offset as i64,
+            shape:   vd.shape[..ndim].iter().map(|&d| d as usize).collect(),
+            strides: vd.strides[..ndim].iter().map(|&s| s as i64).collect(),
+        }
+    }
+
+    fn check_dim(&self, dim: usize) -> Result<()> {
+        if dim >= self.shape.len() {
+            return Err(shape_error(format!("dimension {dim} out of range for {} dimensions", self.shape.len())));
+        }
+        Ok(())
+    }
+
+    fn into_view(self) -> Result<ViewDescriptor> {
+        if self.shape.len() > MAX_DIMS {
+            return Err(shape_error(format!(
+                "{} dimensions given, at most {} supported", self.shape.len(), MAX_DIMS
+            )));
+        }
+        let mut vd = ViewDescriptor::zeroed();
+        vd.offset = self.offset as u32;
+        vd.ndim = self.shape.len() as u32;
+        for (i, (&d, &s)) in self.shape.iter().zip(&self.strides).enumerate() {
+            vd.shape[i] = d as u32;
+            vd.strides[i] = s as i32;
+        }
+        Ok(vd)
+    }
+}
+
+/// Strides giving `new_shape` the same elements as `shape` / `strides` in row-major order,
+/// if no copy is needed: dimensions are only split or merged where the layout allows it.
+fn reshape_strides(shape: &[usize], strides: &[i64], new_shape: &[usize]) -> Option<Vec<i64>> {
+    let numel: usize = shape.iter().product();
+    if numel == 0 || shape.is_empty() {
+        return Some(compute_strides(new_shape).into_iter().map(|s| s as i64).collect());
+    }
+    let mut new_strides = vec![0i64; new_shape.len()];
+    let mut view_d = new_shape.len() as isize - 1;
+    // stride of the innermost dimension of the current chunk of mergeable dimensions
+    let mut chunk_base = *strides.last()?;
+    let (mut tensor_numel, mut view_numel) = (1usize, 1usize);
+    for d in (0..shape.len()).rev() {
+        tensor_numel *= shape[d];
+        let chunk_ends = d == 0 || (shape[d - 1] != 1 && strides[d - 1] != tensor_numel as i64 * chunk_base);
+        if chunk_ends {
+            while view_d >= 0 && (view_numel < tensor_numel || new_shape[view_d as usize] == 1) {
+                new_strides[view_d as usize] = view_numel as i64 * chunk_base;
+                view_numel *= new_shape[view_d as usize];
+                view_d -= 1;
+            }
+            if view_numel != tensor_numel {
+                return None;
+            }
+            if d > 0 {
+                chunk_base = strides[d - 1];
+                tensor_numel = 1;
+                view_numel = 1;
+            }
+        }
+    }
+    (view_d == -1).then_some(new_strides)
+}
+
+/// Views sharing the buffer of the tensor they come from (metadata only, no copy)
+impl<T: Element> Tensor<T> {
+    /// Same buffer, another view of it
+    fn with_view(&self, view: ViewDescriptor) -> Self {
+        let mut t = self.clone();
+        t.view = view;
+        t
+    }
+
+    pub(crate) fn derive(&self, f: impl FnOnce(&mut Dims) -> Result<()>) -> Result<Self> {
+        let mut dims = Dims::of(&self.view);
+        f(&mut dims)?;
+        Ok(self.with_view(dims.into_view()?))
+    }
+
+    /// Same elements under another shape. Fails if the element counts differ, or if
+    /// the view's layout cannot be expressed with strides (copy it with `contiguous` first).
+    pub fn reshape(&self, shape: &[usize]) -> Result<Self> {
+        self.derive(|d| {
+            let (old, new) = (d.shape.iter().product::<usize>(), shape.iter().product::<usize>());
+            if old != new {
+                return Err(shape_error(format!("cannot reshape {:?} ({old} elements) into {shape:?} ({new} elements)", d.shape)));
+            }
+            d.strides = reshape_strides(&d.shape, &d.strides, shape).ok_or_else(|| shape_error(format!(
+                "cannot reshape this non-contiguous {:?} view into {shape:?} without a copy", d.shape
+            )))?;
+            d.shape = shape.to_vec();
+            Ok(())
+        })
+    }
+
+    /// Swap two dimensions.
+    pub fn transpose(&self, dim0: usize, dim1: usize) -> Result<Self> {
+        self.derive(|d| {
+            d.check_dim(dim0)?;
+            d.check_dim(dim1)?;
+            d.shape.swap(dim0, dim1);
+            d.strides.swap(dim0, dim1);
+            Ok(())
+        })
+    }
+
+    /// Reorder the dimensions: dimension `i` of the result is dimension `dims[i]` of `self`.
+    pub fn permute(&self, dims: &[usize]) -> Result<Self> {
+        self.derive(|d| {
+            let mut seen = vec![false; d.shape.len()];
+            if dims.len() != seen.len() || dims.iter().any(|&i| i >= seen.len() || std::mem::replace(&mut seen[i], true)) {
+                return Err(shape_error(format!("{dims:?} is not a permutation of {} dimensions", seen.len())));
+            }
+            d.shape = dims.iter().map(|&i| d.shape[i]).collect();
+            d.strides = dims.iter().map(|&i| d.strides[i]).collect();
+            Ok(())
+        })
+    }
+
+    /// Take a range of every leading dimension (one `Slice` each; missing trailing
+    /// dimensions are kept whole), e.g. `t.slice(&[(1..3).into(), Slice::all().step(2)])`.
+    pub fn slice(&self, ranges: &[Slice]) -> Result<Self> {
+        self.derive(|d| {
+            if ranges.len() > d.shape.len() {
+                return Err(shape_error(format!("{} ranges given for {} dimensions", ranges.len(), d.shape.len())));
+            }
+            for (i, r) in ranges.iter().enumerate() {
+                let end = r.end.unwrap_or(d.shape[i]);
+                if r.step == 0 || r.start > end || end > d.shape[i] {
+                    return Err(shape_error(format!(
+                        "invalid range {}..{end} step {} for dimension {i} of size {}", r.start, r.step, d.shape[i]
+                    )));
+                }
+                d.offset += r.start as i64 * d.strides[i];
+                d.shape[i] = (end - r.start).div_ceil(r.step);
+                d.strides[i] *= r.step as i64;
+            }
+            Ok(())
+        })
+    }
+
+    /// Elements `start..start + len` of dimension `dim`.
+    pub fn narrow(&self, dim: usize, start: usize, len: usize) -> Result<Self> {
+        let mut ranges = vec![Slice::all(); dim + 1];
+        Dims::of(&self.view).check_dim(dim)?;
+        ranges[dim] = (start..start + len).into();
+        self.slice(&ranges)
+    }
+
+    /// Remove dimension `dim`, which must have size 1, or every dimension of size 1 with `None`.
+    pub fn squeeze(&self, dim: Option<usize>) -> Result<Self> {
+        self.derive(|d| {
+            let keep: Vec<bool> = match dim {
+                Some(dim) => {
+                    d.check_dim(dim)?;
+                    if d.shape[dim] != 1 {
+                        return Err(shape_error(format!("cannot squeeze dimension {dim} of size {}", d.shape[dim])));
+                    }
+                    (0..d.shape.len()).map(|i| i != dim).collect()
+                }
+                None => d.shape.iter().map(|&n| n != 1).collect(),
+            };
+            let mut kept = keep.iter();
+            d.strides.retain(|_| *kept.next().unwrap());
+            let mut kept = keep.iter();
+            d.shape.retain(|_| *kept.next().unwrap());
+            Ok(())
+        })
+    }
+
+    /// Insert a dimension of size 1 at position `dim` (`0..=ndim`).
+    pub fn unsqueeze(&self, dim: usize) -> Result<Self> {
+        self.derive(|d| {
+            if dim > d.shape.len() {
+                return Err(shape_error(format!("cannot insert dimension {dim} in {} dimensions", d.shape.len())));
+            }
+            let stride = match dim {
+                _ if dim < d.shape.len() => d.shape[dim] as i64 * d.strides[dim],
+                _ => 1,
+            };
+            d.shape.insert(dim, 1);
+            d.strides.insert(dim, stride);
+            Ok(())
+        })
+    }
+
+    /// Reverse the order of the elements along each of `dims` (negative strides).
+    pub fn flip(&self, dims: &[usize]) -> Result<Self> {
+        self.derive(|d| {
+            let mut flipped = vec![false; d.shape.len()];
+            for &dim in dims {
+                d.check_dim(dim)?;
+                if std::mem::replace(&mut flipped[dim], true) {
+                    return Err(shape_error(format!("dimension {dim} given twice")));
+                }
+                if d.shape[dim] > 0 {
+                    d.offset += (d.shape[dim] as i64 - 1) * d.strides[dim];
+                }
+                d.strides[dim] = -d.strides[dim];
+            }
+            Ok(())
+        })
+    }
+
+    /// Diagonal of the `dim1` × `dim2` planes, shifted above (`offset > 0`) or below
+    /// the main one. Both dimensions are removed and the diagonal appended last.
+    pub fn diagonal(&self, offset: isize, dim1: usize, dim2: usize) -> Result<Self> {
+        self.derive(|d| {
+            d.check_dim(dim1)?;
+            d.check_dim(dim2)?;
+            if dim1 == dim2 {
+                return Err(shape_error(format!("diagonal dimensions must differ, got {dim1} twice")));
+            }
+            let (rows, cols) = (d.shape[dim1] as isize, d.shape[dim2] as isize);
+            let len = (rows - (-offset).max(0)).min(cols - offset.max(0)).max(0) as usize;
+            if len > 0 {
+                d.offset += offset.max(0) as i64 * d.strides[dim2] + (-offset).max(0) as i64 * d.strides[dim1];
+            }
+            let stride = d.strides[dim1] + d.strides[dim2];
+            let keep = |i: &usize| *i != dim1 && *i != dim2;
+            d.shape = (0..d.shape.len()).filter(keep).map(|i| d.shape[i]).chain([len]).collect();
+            d.strides = (0..d.strides.len()).filter(keep).map(|i| d.strides[i]).chain([stride]).collect();
+            Ok(())
+        })
+    }
+
+    /// Whether the view covers its elements in row-major order with no gap
+    pub fn is_contiguous(&self) -> bool {
+        is_contiguous(&self.view)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pollster::block_on;
+    use memory::MemoryManager;
+    use vknp_core::GpuContext;
+
+    fn arange(mm: &MemoryManager, shape: &[usize]) -> Tensor<u32> {
+        let n = shape.iter().product::<usize>() as u32;
+        Tensor::from_vec(mm, &(0..n).collect::<Vec<_>>(), shape, 0)
+    }
+
+    #[test]
+    fn test_views_share_the_buffer() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let t = arange(&mm, &[2, 3]);
+
+        let tt = t.transpose(0, 1).unwrap();
+        assert_eq!(tt.buffer_id(), t.buffer_id());
+        assert!(!tt.is_contiguous());
+        assert_eq!(tt.to_vec(&mm), vec![0, 3, 1, 4, 2, 5]);
+
+        assert_eq!(t.reshape(&[3, 2]).unwrap().to_vec(&mm), (0..6).collect::<Vec<_>>());
+        assert_eq!(t.permute(&[1, 0]).unwrap().view(), tt.view());
+        assert_eq!(t.flip(&[0, 1]).unwrap().to_vec(&mm), vec![5, 4, 3, 2, 1, 0]);
+        assert_eq!(t.unsqueeze(1).unwrap().squeeze(None).unwrap().view(), t.view());
+        assert_eq!(t.narrow(1, 1, 2).unwrap().to_vec(&mm), vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_slice_with_step_and_diagonal() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let t = arange(&mm, &[4, 5]);
+
+        let s = t.slice(&[(1..4).into(), Slice::all().step(2)]).unwrap();
+        assert_eq!(s.view().shape[..2], [3, 3]);
+        assert_eq!(s.to_vec(&mm), vec![5, 7, 9, 10, 12, 14, 15, 17, 19]);
+
+        assert_eq!(t.diagonal(0, 0, 1).unwrap().to_vec(&mm), vec![0, 6, 12, 18]);
+        assert_eq!(t.diagonal(2, 0, 1).unwrap().to_vec(&mm), vec![2, 8, 14]);
+        assert_eq!(t.diagonal(-1, 0, 1).unwrap().to_vec(&mm), vec![5, 11, 17]);
+        // flipped columns: the anti-diagonal
+        assert_eq!(t.flip(&[1]).unwrap().diagonal(0, 0, 1).unwrap().to_vec(&mm), vec![4, 8, 12, 16]);
+    }
+
+    #[test]
+    fn test_reshape_of_non_contiguous_views() {
+        let ctx = block_on(GpuContext::new()).unwrap();
+        let mm = MemoryManager::new(ctx);
+        let t = arange(&mm, &[2, 3, 4]);
+
+        // merging dimensions that are still adjacent in memory needs no copy
+        let rows = t.narrow(1, 0, 2).unwrap().reshape(&[2, 8]).unwrap();
+        assert_eq!(rows.to_vec(&mm)[..8], [0, 1, 2, 3, 4, 5, 6, 7]);
+        // splitting a transposed dimension, too
+        let split = t.transpose(0, 2).unwrap().reshape(&[2, 2, 3, 2]).unwrap();
+        assert_eq!(split.to_vec(&mm), t.transpose(0, 2).unwrap().to_vec(&mm));
+
+        let err = t.transpose(0, 1).unwrap().reshape(&[24]).err().unwrap();
+        assert!(matches!(err, VknpError::Shape(_)), "{err}");
+        assert!(t.reshape(&[5, 5]).is_err());
+        assert!(t.unsqueeze(0).unwrap().unsqueeze(0).unwrap().unsqueeze(0).unwrap()
+                 .unsqueeze(0).unwrap().unsqueeze(0).unwrap().unsqueeze(0).is_err(), "more than MAX_DIMS");
+        assert!(t.squeeze(Some(0)).is_err());
+        assert!(t.permute(&[0, 0, 1]).is_err());
+    }
+}